@@ -26,7 +26,7 @@ fn bench_histogram_with_label_values(c: &mut Criterion) {
         b.iter(|| {
             histogram
                 .with_label_values(&["eins", "zwei", "drei"])
-                .observe(3.1415)
+                .observe(std::f64::consts::PI)
         })
     });
 }
@@ -38,7 +38,7 @@ fn bench_histogram_no_labels(c: &mut Criterion) {
     ))
     .unwrap();
     c.bench_function("bench_histogram_no_labels", |b| {
-        b.iter(|| histogram.observe(3.1415))
+        b.iter(|| histogram.observe(std::f64::consts::PI))
     });
 }
 
@@ -60,7 +60,7 @@ fn bench_histogram_local(c: &mut Criterion) {
     .unwrap();
     let local = histogram.local();
     c.bench_function("bench_histogram_local", |b| {
-        b.iter(|| local.observe(3.1415));
+        b.iter(|| local.observe(std::f64::consts::PI));
     });
     local.flush();
 }
@@ -0,0 +1,75 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use prometheus::{Summary, SummaryOpts, SummaryVec};
+
+fn bench_summary_with_label_values(c: &mut Criterion) {
+    let summary = SummaryVec::new(
+        SummaryOpts::new("benchmark_summary", "A summary to benchmark it.").objectives(vec![
+            (0.5, 0.05),
+            (0.9, 0.01),
+            (0.99, 0.001),
+        ]),
+        &["one", "two", "three"],
+    )
+    .unwrap();
+    c.bench_function("bench_summary_with_label_values", |b| {
+        b.iter(|| {
+            summary
+                .with_label_values(&["eins", "zwei", "drei"])
+                .observe(std::f64::consts::PI)
+        })
+    });
+}
+
+fn bench_summary_no_objectives(c: &mut Criterion) {
+    let summary = Summary::with_opts(SummaryOpts::new(
+        "benchmark_summary",
+        "A summary to benchmark it.",
+    ))
+    .unwrap();
+    c.bench_function("bench_summary_no_objectives", |b| {
+        b.iter(|| summary.observe(std::f64::consts::PI))
+    });
+}
+
+fn bench_summary_objectives(c: &mut Criterion) {
+    let summary = Summary::with_opts(
+        SummaryOpts::new("benchmark_summary", "A summary to benchmark it.").objectives(vec![
+            (0.5, 0.05),
+            (0.9, 0.01),
+            (0.99, 0.001),
+        ]),
+    )
+    .unwrap();
+    let mut i = 0u64;
+    c.bench_function("bench_summary_objectives", |b| {
+        b.iter(|| {
+            i = i.wrapping_add(1);
+            summary.observe((i % 1_000) as f64)
+        })
+    });
+}
+
+fn bench_summary_local(c: &mut Criterion) {
+    let summary =
+        Summary::with_opts(
+            SummaryOpts::new("benchmark_summary_local", "A summary to benchmark it.")
+                .objectives(vec![(0.5, 0.05), (0.9, 0.01), (0.99, 0.001)]),
+        )
+        .unwrap();
+    let local = summary.local();
+    c.bench_function("bench_summary_local", |b| {
+        b.iter(|| local.observe(std::f64::consts::PI));
+    });
+    local.flush();
+}
+
+criterion_group!(
+    benches,
+    bench_summary_with_label_values,
+    bench_summary_no_objectives,
+    bench_summary_objectives,
+    bench_summary_local,
+);
+criterion_main!(benches);
@@ -3,7 +3,6 @@
 use prometheus::register_counter;
 
 /// small example that uses rust 2018 style macro imports
-
 #[allow(unused)]
 fn main() {
     register_counter!("test_macro_3", "help");
@@ -0,0 +1,92 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use crate::desc::Desc;
+use crate::errors::Result;
+use crate::metrics::Collector;
+use crate::proto::MetricFamily;
+use crate::timer;
+
+/// A [`Collector`] memoizing the metrics collected by an expensive inner
+/// collector, e.g. a `ProcessCollector`, for a time to live.
+///
+/// Collections within the time to live of the last successful collection
+/// return the cached metrics. Collections are single-flight: concurrent
+/// collections of an expired cache wait for a single collection of the inner
+/// collector and share its result. Failed collections are not cached.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use prometheus::{CachedCollector, Counter, Registry};
+///
+/// let counter = Counter::new("expensive", "An expensive counter.").unwrap();
+/// let cached = CachedCollector::new(counter, Duration::from_secs(5));
+///
+/// let r = Registry::new();
+/// r.register(Box::new(cached)).unwrap();
+/// ```
+pub struct CachedCollector<C: Collector> {
+    inner: C,
+    ttl_millis: u64,
+    /// The last collected metrics and the time they were collected at, in
+    /// [`timer::now_millis`].
+    cache: Mutex<Option<(u64, Vec<MetricFamily>)>>,
+}
+
+impl<C: Collector> CachedCollector<C> {
+    /// Create a [`CachedCollector`] caching the metrics of `inner` for `ttl`.
+    pub fn new(inner: C, ttl: Duration) -> CachedCollector<C> {
+        CachedCollector {
+            inner,
+            ttl_millis: timer::duration_to_millis(ttl),
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Return the inner collector.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    /// Drop the cached metrics, the next collection collects the inner
+    /// collector.
+    pub fn invalidate(&self) {
+        *self.cache.lock() = None;
+    }
+}
+
+impl<C: Collector> Collector for CachedCollector<C> {
+    fn desc(&self) -> Vec<&Desc> {
+        self.inner.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.try_collect().unwrap_or_default()
+    }
+
+    fn try_collect(&self) -> Result<Vec<MetricFamily>> {
+        // The lock is held while collecting, so that concurrent collections
+        // wait for the result instead of collecting again.
+        let mut cache = self.cache.lock();
+        if let Some((collected_at, ref mfs)) = *cache {
+            if timer::now_millis() < collected_at.saturating_add(self.ttl_millis) {
+                return Ok(mfs.clone());
+            }
+        }
+
+        let mfs = self.inner.try_collect()?;
+        *cache = Some((timer::now_millis(), mfs.clone()));
+        Ok(mfs)
+    }
+}
+
+impl<C: Collector> std::fmt::Debug for CachedCollector<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CachedCollector {{ ttl: {}ms }}", self.ttl_millis)
+    }
+}
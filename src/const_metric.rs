@@ -0,0 +1,376 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::time::SystemTime;
+
+use crate::desc::Desc;
+use crate::errors::{Error, Result};
+use crate::metrics::{Collector, Metric};
+use crate::proto::{self, MetricFamily, MetricType};
+use crate::value::{make_label_pairs, timestamp_millis, ValueType};
+
+/// A [`Metric`] with a fixed value, built on the fly by custom [`Collector`]s,
+/// e.g. exporters that proxy the metrics of another system.
+///
+/// A [`ConstMetric`] is created with [`new_const_metric`],
+/// [`new_const_histogram`] or [`new_const_summary`]. Its [`Collector`] impl
+/// collects a family holding just this metric; the registry merges the
+/// families of several const metrics sharing the same [`Desc`].
+///
+/// # Examples
+///
+/// ```
+/// use prometheus::core::{Collector, Desc};
+/// use prometheus::proto::MetricFamily;
+/// use prometheus::{new_const_metric, ValueType};
+///
+/// struct QueueCollector {
+///     desc: Desc,
+/// }
+///
+/// impl Collector for QueueCollector {
+///     fn desc(&self) -> Vec<&Desc> {
+///         vec![&self.desc]
+///     }
+///
+///     fn collect(&self) -> Vec<MetricFamily> {
+///         // Queue lengths fetched from elsewhere.
+///         vec![("high", 2.0), ("low", 5.0)]
+///             .into_iter()
+///             .flat_map(|(queue, len)| {
+///                 new_const_metric(&self.desc, ValueType::Gauge, len, &[queue])
+///                     .unwrap()
+///                     .collect()
+///             })
+///             .collect()
+///     }
+/// }
+///
+/// let desc = Desc::new(
+///     "queue_length".to_owned(),
+///     "Number of queued items.".to_owned(),
+///     vec!["queue".to_owned()],
+///     Default::default(),
+/// )
+/// .unwrap();
+/// let r = prometheus::Registry::new();
+/// r.register(Box::new(QueueCollector { desc })).unwrap();
+/// assert_eq!(r.gather()[0].get_metric().len(), 2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ConstMetric {
+    desc: Desc,
+    metric_type: MetricType,
+    metric: proto::Metric,
+}
+
+impl ConstMetric {
+    /// Return the type of the metric.
+    pub fn metric_type(&self) -> MetricType {
+        self.metric_type
+    }
+
+    /// Attach the time the value was measured at, e.g. when relaying values
+    /// sampled elsewhere. The timestamp is exposed along with the samples of
+    /// the metric.
+    pub fn with_timestamp(mut self, timestamp: SystemTime) -> ConstMetric {
+        self.metric.set_timestamp_ms(timestamp_millis(timestamp));
+        self
+    }
+}
+
+impl Metric for ConstMetric {
+    fn metric(&self) -> proto::Metric {
+        self.metric.clone()
+    }
+}
+
+impl Collector for ConstMetric {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let mut mf = MetricFamily::default();
+        mf.set_name(self.desc.fq_name.clone());
+        mf.set_help(self.desc.help.clone());
+        if !self.desc.unit.is_empty() {
+            mf.set_unit(self.desc.unit.clone());
+        }
+        mf.set_field_type(self.metric_type);
+        mf.set_metric(from_vec!(vec![self.metric.clone()]));
+        vec![mf]
+    }
+}
+
+fn new_metric(desc: &Desc, label_values: &[&str]) -> Result<proto::Metric> {
+    let label_pairs = make_label_pairs(desc, label_values)?;
+    let mut m = proto::Metric::default();
+    m.set_label(from_vec!(label_pairs));
+    Ok(m)
+}
+
+/// `new_const_metric` creates a counter or gauge [`ConstMetric`] with the
+/// fixed `value`. The label values must match the variable labels of `desc`
+/// in number and order. A counter value must not be negative or NaN.
+pub fn new_const_metric(
+    desc: &Desc,
+    value_type: ValueType,
+    value: f64,
+    label_values: &[&str],
+) -> Result<ConstMetric> {
+    if value_type == ValueType::Counter && (value.is_nan() || value < 0.0) {
+        return Err(Error::Msg(format!(
+            "counter {} has negative or NaN value {}",
+            desc.fq_name, value
+        )));
+    }
+
+    let mut metric = new_metric(desc, label_values)?;
+    match value_type {
+        ValueType::Counter => {
+            let mut counter = proto::Counter::default();
+            counter.set_value(value);
+            metric.set_counter(counter);
+        }
+        ValueType::Gauge => {
+            let mut gauge = proto::Gauge::default();
+            gauge.set_value(value);
+            metric.set_gauge(gauge);
+        }
+    }
+
+    Ok(ConstMetric {
+        desc: desc.clone(),
+        metric_type: value_type.metric_type(),
+        metric,
+    })
+}
+
+/// `new_const_histogram` creates a histogram [`ConstMetric`] from the total
+/// `count` and `sum` of the observations and `buckets`, pairs of an upper
+/// bound and the cumulative count of the observations less than or equal to
+/// it. The +Inf bucket is implied by `count` and may be omitted.
+///
+/// The upper bounds must be distinct, the cumulative counts must not
+/// decrease with the upper bound and none may exceed `count`. The label
+/// values must match the variable labels of `desc` in number and order.
+pub fn new_const_histogram(
+    desc: &Desc,
+    count: u64,
+    sum: f64,
+    buckets: &[(f64, u64)],
+    label_values: &[&str],
+) -> Result<ConstMetric> {
+    let mut buckets = buckets.to_vec();
+    if buckets.iter().any(|(upper_bound, _)| upper_bound.is_nan()) {
+        return Err(Error::Msg(format!(
+            "histogram {} has a NaN bucket upper bound",
+            desc.fq_name
+        )));
+    }
+    buckets.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut last: Option<(f64, u64)> = None;
+    for &(upper_bound, cumulative_count) in &buckets {
+        if let Some((last_bound, last_count)) = last {
+            if upper_bound == last_bound {
+                return Err(Error::Msg(format!(
+                    "histogram {} has duplicate bucket upper bound {}",
+                    desc.fq_name, upper_bound
+                )));
+            }
+            if cumulative_count < last_count {
+                return Err(Error::Msg(format!(
+                    "histogram {} has decreasing cumulative count at upper bound {}",
+                    desc.fq_name, upper_bound
+                )));
+            }
+        }
+        if cumulative_count > count {
+            return Err(Error::Msg(format!(
+                "histogram {} has bucket count {} exceeding the total count {}",
+                desc.fq_name, cumulative_count, count
+            )));
+        }
+        last = Some((upper_bound, cumulative_count));
+    }
+
+    let mut h = proto::Histogram::default();
+    h.set_sample_count(count);
+    h.set_sample_sum(sum);
+    let buckets = buckets
+        .into_iter()
+        .map(|(upper_bound, cumulative_count)| {
+            let mut b = proto::Bucket::default();
+            b.set_upper_bound(upper_bound);
+            b.set_cumulative_count(cumulative_count);
+            b
+        })
+        .collect();
+    h.set_bucket(from_vec!(buckets));
+
+    let mut metric = new_metric(desc, label_values)?;
+    metric.set_histogram(h);
+    Ok(ConstMetric {
+        desc: desc.clone(),
+        metric_type: MetricType::HISTOGRAM,
+        metric,
+    })
+}
+
+/// `new_const_summary` creates a summary [`ConstMetric`] from the total
+/// `count` and `sum` of the observations and `quantiles`, pairs of a
+/// quantile in `[0, 1]` and its value.
+///
+/// The quantiles must be distinct. The label values must match the variable
+/// labels of `desc` in number and order.
+pub fn new_const_summary(
+    desc: &Desc,
+    count: u64,
+    sum: f64,
+    quantiles: &[(f64, f64)],
+    label_values: &[&str],
+) -> Result<ConstMetric> {
+    let mut quantiles = quantiles.to_vec();
+    if let Some((q, _)) = quantiles.iter().find(|(q, _)| !(0.0..=1.0).contains(q)) {
+        return Err(Error::Msg(format!(
+            "summary {} has quantile {} outside of [0, 1]",
+            desc.fq_name, q
+        )));
+    }
+    quantiles.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    if let Some(w) = quantiles.windows(2).find(|w| w[0].0 == w[1].0) {
+        return Err(Error::Msg(format!(
+            "summary {} has duplicate quantile {}",
+            desc.fq_name, w[0].0
+        )));
+    }
+
+    let mut s = proto::Summary::default();
+    s.set_sample_count(count);
+    s.set_sample_sum(sum);
+    let quantiles = quantiles
+        .into_iter()
+        .map(|(quantile, value)| {
+            let mut q = proto::Quantile::default();
+            q.set_quantile(quantile);
+            q.set_value(value);
+            q
+        })
+        .collect();
+    s.set_quantile(from_vec!(quantiles));
+
+    let mut metric = new_metric(desc, label_values)?;
+    metric.set_summary(s);
+    Ok(ConstMetric {
+        desc: desc.clone(),
+        metric_type: MetricType::SUMMARY,
+        metric,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use crate::encoder::TextEncoder;
+
+    fn new_desc() -> Desc {
+        let mut const_labels = HashMap::new();
+        const_labels.insert("c".to_owned(), "1".to_owned());
+        Desc::new(
+            "test_const".to_owned(),
+            "test help".to_owned(),
+            vec!["b".to_owned(), "a".to_owned()],
+            const_labels,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_const_metric() {
+        let desc = new_desc();
+        let m = new_const_metric(&desc, ValueType::Gauge, -1.5, &["x", "y"]).unwrap();
+        let mfs = m.collect();
+        assert_eq!(mfs.len(), 1);
+        assert_eq!(mfs[0].get_name(), "test_const");
+        assert_eq!(mfs[0].get_field_type(), MetricType::GAUGE);
+
+        let metric = &mfs[0].get_metric()[0];
+        let labels: Vec<_> = metric
+            .get_label()
+            .iter()
+            .map(|l| (l.get_name(), l.get_value()))
+            .collect();
+        assert_eq!(labels, vec![("a", "y"), ("b", "x"), ("c", "1")]);
+        assert!((metric.get_gauge().get_value() + 1.5).abs() < f64::EPSILON);
+
+        let m = new_const_metric(&desc, ValueType::Counter, 3.0, &["x", "y"]).unwrap();
+        assert_eq!(m.metric_type(), MetricType::COUNTER);
+        assert!((m.metric().get_counter().get_value() - 3.0).abs() < f64::EPSILON);
+
+        assert!(new_const_metric(&desc, ValueType::Counter, -1.0, &["x", "y"]).is_err());
+        assert!(new_const_metric(&desc, ValueType::Counter, f64::NAN, &["x", "y"]).is_err());
+        assert!(new_const_metric(&desc, ValueType::Gauge, 1.0, &["x"]).is_err());
+    }
+
+    #[test]
+    fn test_const_metric_with_timestamp() {
+        let desc = new_desc();
+        let timestamp = UNIX_EPOCH + Duration::from_millis(1_500);
+        let m = new_const_metric(&desc, ValueType::Counter, 3.0, &["x", "y"])
+            .unwrap()
+            .with_timestamp(timestamp);
+        assert_eq!(m.metric().get_timestamp_ms(), 1_500);
+        let txt = TextEncoder::new().encode_to_string(&m.collect()).unwrap();
+        assert!(txt.ends_with("test_const{a=\"y\",b=\"x\",c=\"1\"} 3 1500\n"));
+    }
+
+    #[test]
+    fn test_const_histogram() {
+        let desc = new_desc();
+        let m = new_const_histogram(&desc, 5, 7.5, &[(2.0, 4), (1.0, 1)], &["x", "y"]).unwrap();
+        assert_eq!(m.metric_type(), MetricType::HISTOGRAM);
+        let metric = m.metric();
+        let h = metric.get_histogram();
+        assert_eq!(h.get_sample_count(), 5);
+        assert!((h.get_sample_sum() - 7.5).abs() < f64::EPSILON);
+        let buckets: Vec<_> = h
+            .get_bucket()
+            .iter()
+            .map(|b| (b.get_upper_bound(), b.get_cumulative_count()))
+            .collect();
+        assert_eq!(buckets, vec![(1.0, 1), (2.0, 4)]);
+
+        let invalid: &[&[(f64, u64)]] = &[
+            &[(1.0, 2), (2.0, 1)],
+            &[(1.0, 1), (1.0, 2)],
+            &[(1.0, 6)],
+            &[(f64::NAN, 1)],
+        ];
+        for buckets in invalid {
+            assert!(new_const_histogram(&desc, 5, 7.5, buckets, &["x", "y"]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_const_summary() {
+        let desc = new_desc();
+        let m = new_const_summary(&desc, 5, 7.5, &[(0.9, 2.0), (0.5, 1.0)], &["x", "y"]).unwrap();
+        assert_eq!(m.metric_type(), MetricType::SUMMARY);
+        let metric = m.metric();
+        let quantiles: Vec<_> = metric
+            .get_summary()
+            .get_quantile()
+            .iter()
+            .map(|q| (q.get_quantile(), q.get_value()))
+            .collect();
+        assert_eq!(quantiles, vec![(0.5, 1.0), (0.9, 2.0)]);
+
+        assert!(new_const_summary(&desc, 5, 7.5, &[(1.5, 1.0)], &["x", "y"]).is_err());
+        assert!(new_const_summary(&desc, 5, 7.5, &[(f64::NAN, 1.0)], &["x", "y"]).is_err());
+        assert!(new_const_summary(&desc, 5, 7.5, &[(0.5, 1.0), (0.5, 2.0)], &["x", "y"]).is_err());
+    }
+}
@@ -11,8 +11,8 @@ use crate::desc::Desc;
 use crate::errors::Result;
 use crate::metrics::{Collector, LocalMetric, Metric, Opts};
 use crate::proto;
-use crate::value::{Value, ValueType};
-use crate::vec::{MetricVec, MetricVecBuilder};
+use crate::value::{make_exemplar, Value, ValueType};
+use crate::vec::{ChildLimits, LabelValuesMap, MetricVec, MetricVecBuilder};
 
 /// The underlying implementation for [`Counter`] and [`IntCounter`].
 #[derive(Debug)]
@@ -63,6 +63,27 @@ impl<P: Atomic> GenericCounter<P> {
         self.v.inc_by(v);
     }
 
+    /// Increase the given value to the counter and attach an exemplar built
+    /// from `exemplar_labels`, e.g. a trace ID, to it. The exemplar replaces
+    /// any previously attached one.
+    ///
+    /// Returns an error and leaves the counter untouched if the exemplar labels
+    /// are invalid or longer than [`MAX_EXEMPLAR_LABEL_LENGTH`](crate::MAX_EXEMPLAR_LABEL_LENGTH).
+    ///
+    /// # Panics
+    ///
+    /// Panics in debug build if the value is < 0.
+    pub fn inc_by_with_exemplar(
+        &self,
+        v: P::T,
+        exemplar_labels: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        debug_assert!(v >= P::T::from_i64(0));
+        let exemplar = make_exemplar(v.into_f64(), exemplar_labels)?;
+        self.v.inc_by_with_exemplar(v, exemplar);
+        Ok(())
+    }
+
     /// Increase the counter by 1.
     #[inline]
     pub fn inc(&self) {
@@ -75,10 +96,11 @@ impl<P: Atomic> GenericCounter<P> {
         self.v.get()
     }
 
-    /// Restart the counter, resetting its value back to 0.
+    /// Restart the counter, resetting its value back to 0 and its creation
+    /// time to now.
     #[inline]
     pub fn reset(&self) {
-        self.v.set(P::T::from_i64(0))
+        self.v.reset()
     }
 
     /// Return a [`GenericLocalCounter`] for single thread usage.
@@ -101,6 +123,10 @@ impl<P: Atomic> Metric for GenericCounter<P> {
     fn metric(&self) -> proto::Metric {
         self.v.metric()
     }
+
+    fn last_touched(&self) -> Option<u64> {
+        self.v.last_touched.get()
+    }
 }
 
 #[derive(Debug)]
@@ -151,8 +177,15 @@ impl<P: Atomic> GenericCounterVec<P> {
     pub fn new(opts: Opts, label_names: &[&str]) -> Result<Self> {
         let variable_names = label_names.iter().map(|s| (*s).to_owned()).collect();
         let opts = opts.variable_labels(variable_names);
-        let metric_vec =
-            MetricVec::create(proto::MetricType::COUNTER, CounterVecBuilder::new(), opts)?;
+        let limits = ChildLimits::from_opts(&opts);
+        let desc = opts.describe_as(proto::MetricType::COUNTER)?;
+        let metric_vec = MetricVec::create_with_limits(
+            proto::MetricType::COUNTER,
+            CounterVecBuilder::new(),
+            opts,
+            desc,
+            limits,
+        )?;
 
         Ok(metric_vec as Self)
     }
@@ -254,16 +287,12 @@ impl<P: Atomic> Clone for GenericLocalCounter<P> {
 /// and [`LocalIntCounterVec`].
 pub struct GenericLocalCounterVec<P: Atomic> {
     vec: GenericCounterVec<P>,
-    local: HashMap<u64, GenericLocalCounter<P>>,
+    local: LabelValuesMap<GenericLocalCounter<P>>,
 }
 
 impl<P: Atomic> std::fmt::Debug for GenericLocalCounterVec<P> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "GenericLocalCounterVec ({} locals)",
-            self.local.keys().len()
-        )
+        write!(f, "GenericLocalCounterVec ({} locals)", self.local.len())
     }
 }
 
@@ -277,26 +306,25 @@ pub type LocalIntCounterVec = GenericLocalCounterVec<AtomicU64>;
 
 impl<P: Atomic> GenericLocalCounterVec<P> {
     fn new(vec: GenericCounterVec<P>) -> Self {
-        let local = HashMap::with_capacity(vec.v.children.read().len());
+        let local = LabelValuesMap::with_capacity(vec.v.children.read().len());
         Self { vec, local }
     }
 
     /// Get a [`GenericLocalCounter`] by label values.
     /// See more [MetricVec::with_label_values].
     pub fn with_label_values<'a>(&'a mut self, vals: &[&str]) -> &'a mut GenericLocalCounter<P> {
-        let hash = self.vec.v.hash_label_values(vals).unwrap();
+        let hash = self.vec.hash_label_values(vals).unwrap();
         let vec = &self.vec;
         self.local
-            .entry(hash)
-            .or_insert_with(|| vec.with_label_values(vals).local())
+            .get_or_insert_with(hash, vals, || vec.with_label_values(vals).local())
     }
 
     /// Remove a [`GenericLocalCounter`] by label values.
     /// See more [MetricVec::remove_label_values].
     pub fn remove_label_values(&mut self, vals: &[&str]) -> Result<()> {
-        let hash = self.vec.v.hash_label_values(vals)?;
-        self.local.remove(&hash);
-        self.vec.v.delete_label_values(vals)
+        let hash = self.vec.hash_label_values(vals)?;
+        self.local.remove(hash, vals);
+        self.vec.remove_label_values(vals)
     }
 
     /// Flush the local metrics to the [`CounterVec`] metric.
@@ -323,7 +351,6 @@ impl<P: Atomic> Clone for GenericLocalCounterVec<P> {
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
-    use std::f64::EPSILON;
 
     use super::*;
     use crate::metrics::{Collector, Opts};
@@ -343,7 +370,7 @@ mod tests {
         assert_eq!(mfs.len(), 1);
 
         let mf = mfs.pop().unwrap();
-        let m = mf.get_metric().get(0).unwrap();
+        let m = mf.get_metric().first().unwrap();
         assert_eq!(m.get_label().len(), 2);
         assert_eq!(m.get_counter().get_value() as u64, 43);
 
@@ -351,6 +378,28 @@ mod tests {
         assert_eq!(counter.get() as u64, 0);
     }
 
+    #[test]
+    fn test_counter_created_timestamp() {
+        let created = |counter: &IntCounter| {
+            let ts = counter
+                .metric()
+                .get_counter()
+                .get_created_timestamp()
+                .clone();
+            (ts.get_seconds(), ts.get_nanos())
+        };
+
+        let counter = IntCounter::new("foo", "bar").unwrap();
+        let first = created(&counter);
+        assert!(first.0 > 0);
+        counter.inc();
+        assert_eq!(created(&counter), first);
+
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        counter.reset();
+        assert!(created(&counter) > first);
+    }
+
     #[test]
     fn test_int_counter() {
         let counter = IntCounter::new("foo", "bar").unwrap();
@@ -363,12 +412,47 @@ mod tests {
         assert_eq!(mfs.len(), 1);
 
         let mf = mfs.pop().unwrap();
-        let m = mf.get_metric().get(0).unwrap();
+        let m = mf.get_metric().first().unwrap();
         assert_eq!(m.get_label().len(), 0);
         assert_eq!(m.get_counter().get_value() as u64, 12);
 
         counter.reset();
-        assert_eq!(counter.get() as u64, 0);
+        assert_eq!(counter.get(), 0);
+    }
+
+    #[test]
+    fn test_counter_with_exemplar() {
+        let counter = IntCounter::new("foo", "bar").unwrap();
+        let m = counter.metric();
+        assert!(!m.get_counter().has_exemplar());
+
+        let mut labels = HashMap::new();
+        labels.insert("trace_id", "abc123");
+        counter.inc_by_with_exemplar(3, &labels).unwrap();
+        assert_eq!(counter.get(), 3);
+
+        let m = counter.metric();
+        assert!(m.get_counter().has_exemplar());
+        let exemplar = m.get_counter().get_exemplar();
+        assert_eq!(exemplar.get_value() as u64, 3);
+        assert_eq!(exemplar.get_label().len(), 1);
+        assert_eq!(exemplar.get_label()[0].get_name(), "trace_id");
+        assert_eq!(exemplar.get_label()[0].get_value(), "abc123");
+        assert!(exemplar.get_timestamp().get_seconds() > 0);
+
+        // Invalid exemplars are rejected without touching the counter.
+        let long_value = "x".repeat(crate::MAX_EXEMPLAR_LABEL_LENGTH);
+        let mut labels = HashMap::new();
+        labels.insert("trace_id", long_value.as_str());
+        assert!(counter.inc_by_with_exemplar(1, &labels).is_err());
+        let mut labels = HashMap::new();
+        labels.insert("__invalid-name", "1");
+        assert!(counter.inc_by_with_exemplar(1, &labels).is_err());
+        assert_eq!(counter.get(), 3);
+        assert_eq!(
+            counter.metric().get_counter().get_exemplar().get_label()[0].get_value(),
+            "abc123"
+        );
     }
 
     #[test]
@@ -414,9 +498,9 @@ mod tests {
 
         local_counter.reset();
         counter.reset();
-        assert_eq!(counter.get() as u64, 0);
+        assert_eq!(counter.get(), 0);
         local_counter.flush();
-        assert_eq!(counter.get() as u64, 0);
+        assert_eq!(counter.get(), 0);
     }
 
     #[test]
@@ -502,48 +586,48 @@ mod tests {
         assert!(local_vec_1.remove_label_values(&["v1", "v2"]).is_err());
 
         local_vec_1.with_label_values(&["v1", "v2"]).inc_by(23.0);
-        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 23.0) <= EPSILON);
-        assert!((vec.with_label_values(&["v1", "v2"]).get() - 0.0) <= EPSILON);
+        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 23.0) <= f64::EPSILON);
+        assert!((vec.with_label_values(&["v1", "v2"]).get() - 0.0) <= f64::EPSILON);
 
         local_vec_1.flush();
-        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 0.0) <= EPSILON);
-        assert!((vec.with_label_values(&["v1", "v2"]).get() - 23.0) <= EPSILON);
+        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 0.0) <= f64::EPSILON);
+        assert!((vec.with_label_values(&["v1", "v2"]).get() - 23.0) <= f64::EPSILON);
 
         local_vec_1.flush();
-        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 0.0) <= EPSILON);
-        assert!((vec.with_label_values(&["v1", "v2"]).get() - 23.0) <= EPSILON);
+        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 0.0) <= f64::EPSILON);
+        assert!((vec.with_label_values(&["v1", "v2"]).get() - 23.0) <= f64::EPSILON);
 
         local_vec_1.with_label_values(&["v1", "v2"]).inc_by(11.0);
-        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 11.0) <= EPSILON);
-        assert!((vec.with_label_values(&["v1", "v2"]).get() - 23.0) <= EPSILON);
+        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 11.0) <= f64::EPSILON);
+        assert!((vec.with_label_values(&["v1", "v2"]).get() - 23.0) <= f64::EPSILON);
 
         local_vec_1.flush();
-        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 0.0) <= EPSILON);
-        assert!((vec.with_label_values(&["v1", "v2"]).get() - 34.0) <= EPSILON);
+        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 0.0) <= f64::EPSILON);
+        assert!((vec.with_label_values(&["v1", "v2"]).get() - 34.0) <= f64::EPSILON);
 
         // When calling `remove_label_values`, it is "flushed" immediately.
         assert!(local_vec_1.remove_label_values(&["v1", "v2"]).is_ok());
-        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 0.0) <= EPSILON);
-        assert!((vec.with_label_values(&["v1", "v2"]).get() - 0.0) <= EPSILON);
+        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 0.0) <= f64::EPSILON);
+        assert!((vec.with_label_values(&["v1", "v2"]).get() - 0.0) <= f64::EPSILON);
 
         local_vec_1.with_label_values(&["v1", "v2"]).inc();
         assert!(local_vec_1.remove_label_values(&["v1"]).is_err());
         assert!(local_vec_1.remove_label_values(&["v1", "v3"]).is_err());
 
         local_vec_1.with_label_values(&["v1", "v2"]).inc_by(13.0);
-        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 14.0) <= EPSILON);
-        assert!((vec.with_label_values(&["v1", "v2"]).get() - 0.0) <= EPSILON);
+        assert!((local_vec_1.with_label_values(&["v1", "v2"]).get() - 14.0) <= f64::EPSILON);
+        assert!((vec.with_label_values(&["v1", "v2"]).get() - 0.0) <= f64::EPSILON);
 
         local_vec_2.with_label_values(&["v1", "v2"]).inc_by(7.0);
-        assert!((local_vec_2.with_label_values(&["v1", "v2"]).get() - 7.0) <= EPSILON);
+        assert!((local_vec_2.with_label_values(&["v1", "v2"]).get() - 7.0) <= f64::EPSILON);
 
         local_vec_1.flush();
         local_vec_2.flush();
-        assert!((vec.with_label_values(&["v1", "v2"]).get() - 21.0) <= EPSILON);
+        assert!((vec.with_label_values(&["v1", "v2"]).get() - 21.0) <= f64::EPSILON);
 
         local_vec_1.flush();
         local_vec_2.flush();
-        assert!((vec.with_label_values(&["v1", "v2"]).get() - 21.0) <= EPSILON);
+        assert!((vec.with_label_values(&["v1", "v2"]).get() - 21.0) <= f64::EPSILON);
     }
 
     #[test]
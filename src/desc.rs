@@ -8,7 +8,7 @@ use fnv::FnvHasher;
 
 use crate::errors::{Error, Result};
 use crate::metrics::SEPARATOR_BYTE;
-use crate::proto::LabelPair;
+use crate::proto::{LabelPair, MetricType};
 
 // [a-zA-Z_]
 fn matches_charset_without_colon(c: char) -> bool {
@@ -27,7 +27,7 @@ fn is_valid_ident<F: FnMut(char) -> bool>(input: &str, mut charset_validator: F)
     zeroth
         .and_then(|zeroth| {
             if charset_validator(zeroth) {
-                Some(chars.all(|c| charset_validator(c) || c.is_digit(10)))
+                Some(chars.all(|c| charset_validator(c) || c.is_ascii_digit()))
             } else {
                 None
             }
@@ -73,10 +73,13 @@ pub struct Desc {
     /// must be unique among all registered descriptors and can therefore be
     /// used as an identifier of the descriptor.
     pub id: u64,
-    /// dim_hash is a hash of the label names (preset and variable) and the
-    /// Help string. Each Desc with the same fqName must have the same
-    /// dimHash.
+    /// dim_hash is a hash of the label names (preset and variable), the
+    /// Help string and the unit. Each Desc with the same fqName must have the
+    /// same dimHash.
     pub dim_hash: u64,
+    /// unit is the unit of the metric values, e.g. `seconds`, or empty if the
+    /// metric has no unit.
+    pub unit: String,
 }
 
 impl Desc {
@@ -96,6 +99,7 @@ impl Desc {
             variable_labels,
             id: 0,
             dim_hash: 0,
+            unit: String::new(),
         };
 
         if desc.help.is_empty() {
@@ -184,6 +188,49 @@ impl Desc {
 
         Ok(desc)
     }
+
+    /// Set the unit of the metric values, e.g. `seconds`. The unit must
+    /// consist of ASCII letters, digits and underscores, and the
+    /// fully-qualified name must end with it, optionally followed by
+    /// `_total` if `metric_type` is a counter. An empty unit leaves the
+    /// [`Desc`] unchanged.
+    pub fn with_unit<S: Into<String>>(self, unit: S, metric_type: MetricType) -> Result<Desc> {
+        let unit = unit.into();
+        if unit.is_empty() {
+            return Ok(self);
+        }
+
+        if !unit.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(Error::Msg(format!("'{}' is not a valid unit", unit)));
+        }
+        let suffix = format!("_{}", unit);
+        let name = match metric_type {
+            MetricType::COUNTER => self.fq_name.strip_suffix("_total"),
+            _ => None,
+        };
+        if !self.fq_name.ends_with(&suffix) && !matches!(name, Some(n) if n.ends_with(&suffix)) {
+            return Err(Error::Msg(format!(
+                "metric name {} does not end with its unit {}",
+                self.fq_name, unit
+            )));
+        }
+
+        Ok(self.set_unit(unit))
+    }
+
+    /// `set_unit` sets a unit already checked against the name.
+    pub(crate) fn set_unit(mut self, unit: String) -> Desc {
+        if unit.is_empty() {
+            return self;
+        }
+
+        let mut h = FnvHasher::default();
+        h.write_u64(self.dim_hash);
+        h.write(unit.as_bytes());
+        self.dim_hash = h.finish();
+        self.unit = unit;
+        self
+    }
 }
 
 /// An interface for describing the immutable meta-data of a [`Metric`](crate::core::Metric).
@@ -198,6 +245,7 @@ mod tests {
 
     use crate::desc::{is_valid_label_name, is_valid_metric_name, Desc};
     use crate::errors::Error;
+    use crate::proto::MetricType;
 
     #[test]
     fn test_is_valid_metric_name() {
@@ -254,7 +302,7 @@ mod tests {
                 HashMap::new(),
             )
             .err()
-            .expect(format!("expected error for {}", name).as_ref());
+            .unwrap_or_else(|| panic!("expected error for {}", name));
             match res {
                 Error::Msg(msg) => assert_eq!(msg, format!("'{}' is not a valid label name", name)),
                 other => panic!("{}", other),
@@ -269,7 +317,7 @@ mod tests {
             labels.insert(name.into(), "value".into());
             let res = Desc::new("name".into(), "help".into(), vec![], labels)
                 .err()
-                .expect(format!("expected error for {}", name).as_ref());
+                .unwrap_or_else(|| panic!("expected error for {}", name));
             match res {
                 Error::Msg(msg) => assert_eq!(msg, format!("'{}' is not a valid label name", name)),
                 other => panic!("{}", other),
@@ -282,7 +330,7 @@ mod tests {
         for &name in &["-dash", "9gag", "has space"] {
             let res = Desc::new(name.into(), "help".into(), vec![], HashMap::new())
                 .err()
-                .expect(format!("expected error for {}", name).as_ref());
+                .unwrap_or_else(|| panic!("expected error for {}", name));
             match res {
                 Error::Msg(msg) => {
                     assert_eq!(msg, format!("'{}' is not a valid metric name", name))
@@ -291,4 +339,48 @@ mod tests {
             };
         }
     }
+
+    #[test]
+    fn test_desc_with_unit() {
+        let new_desc = |name: &str| Desc::new(name.into(), "help".into(), vec![], HashMap::new());
+
+        let desc = new_desc("request_duration_seconds").unwrap();
+        let dim_hash = desc.dim_hash;
+        let desc = desc.with_unit("seconds", MetricType::GAUGE).unwrap();
+        assert_eq!(desc.unit, "seconds");
+        assert_ne!(desc.dim_hash, dim_hash);
+
+        let desc = new_desc("sent_bytes_total").unwrap();
+        let desc = desc.with_unit("bytes", MetricType::COUNTER).unwrap();
+        assert_eq!(desc.unit, "bytes");
+        let desc = new_desc("sent_bytes").unwrap();
+        let desc = desc.with_unit("bytes", MetricType::COUNTER).unwrap();
+        assert_eq!(desc.unit, "bytes");
+        let desc = new_desc("requests").unwrap();
+        let desc = desc.with_unit("", MetricType::GAUGE).unwrap();
+        assert_eq!(desc.unit, "");
+
+        // Only counters may end in `_total`.
+        for metric_type in &[
+            MetricType::GAUGE,
+            MetricType::HISTOGRAM,
+            MetricType::SUMMARY,
+            MetricType::UNTYPED,
+        ] {
+            let res = new_desc("request_duration_seconds_total")
+                .unwrap()
+                .with_unit("seconds", *metric_type);
+            assert!(res.is_err(), "expected error for {:?}", metric_type);
+        }
+
+        for &(name, unit) in &[
+            ("request_duration_seconds", "milliseconds"),
+            ("request_duration_seconds", "onds"),
+            ("request_duration_seconds", "se-conds"),
+            ("seconds", "seconds"),
+        ] {
+            let res = new_desc(name).unwrap().with_unit(unit, MetricType::COUNTER);
+            assert!(res.is_err(), "expected error for {} {}", name, unit);
+        }
+    }
 }
@@ -0,0 +1,328 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::io::Write;
+
+use crate::errors::Result;
+use crate::proto::{self, MetricFamily, MetricType};
+
+use super::text::{StringBuf, WriteUtf8};
+use super::{check_metric_family, Encoder};
+
+/// The JSON format of metric families.
+pub const JSON_FORMAT: &str = "application/json";
+
+/// An implementation of an [`Encoder`] that converts a [`MetricFamily`] proto
+/// message into JSON, e.g. for debugging endpoints or log pipelines.
+///
+/// The metric families are encoded as an array of objects with the following
+/// schema:
+///
+/// ```json
+/// [
+///   {
+///     "name": "http_request_duration_seconds",
+///     "help": "The HTTP request latencies in seconds.",
+///     "type": "histogram",
+///     "unit": "seconds",
+///     "metrics": [
+///       {
+///         "labels": { "handler": "all" },
+///         "timestamp_ms": 1500000000000,
+///         "count": 2,
+///         "sum": 0.75,
+///         "buckets": [{ "upper_bound": 0.5, "cumulative_count": 1 }]
+///       }
+///     ]
+///   }
+/// ]
+/// ```
+///
+/// The `type` is the lowercase name of the [`MetricType`] and the `unit` is
+/// empty for metrics without unit. Counters, gauges, untyped, info and state
+/// set metrics have a `value`, histograms a `count`, `sum` and `buckets`,
+/// summaries a `count`, `sum` and `quantiles` made of a `quantile` and a
+/// `value`. `timestamp_ms` and `created_timestamp_ms`, in milliseconds since
+/// the Unix epoch, are only present if set.
+///
+/// Non-finite floats, which JSON can not represent as numbers, are encoded as
+/// the strings `"NaN"`, `"+Inf"` and `"-Inf"`.
+#[derive(Debug, Default)]
+pub struct JsonEncoder;
+
+impl JsonEncoder {
+    /// Create a new JSON encoder.
+    pub fn new() -> JsonEncoder {
+        JsonEncoder
+    }
+    /// Appends metrics to a given `String` buffer.
+    ///
+    /// This is a convenience wrapper around `<JsonEncoder as Encoder>::encode`.
+    pub fn encode_utf8(&self, metric_families: &[MetricFamily], buf: &mut String) -> Result<()> {
+        self.encode_impl(metric_families, &mut StringBuf(buf))?;
+        Ok(())
+    }
+    /// Converts metrics to `String`.
+    ///
+    /// This is a convenience wrapper around `<JsonEncoder as Encoder>::encode`.
+    pub fn encode_to_string(&self, metric_families: &[MetricFamily]) -> Result<String> {
+        let mut buf = String::new();
+        self.encode_utf8(metric_families, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn encode_impl(
+        &self,
+        metric_families: &[MetricFamily],
+        writer: &mut dyn WriteUtf8,
+    ) -> Result<()> {
+        writer.write_all("[")?;
+        for (i, mf) in metric_families.iter().enumerate() {
+            // Fail-fast checks.
+            check_metric_family(mf)?;
+
+            if i > 0 {
+                writer.write_all(",")?;
+            }
+            let metric_type = mf.get_field_type();
+            writer.write_all("{\"name\":")?;
+            write_string(writer, mf.get_name())?;
+            writer.write_all(",\"help\":")?;
+            write_string(writer, mf.get_help())?;
+            writer.write_all(",\"type\":")?;
+            write_string(writer, &format!("{:?}", metric_type).to_lowercase())?;
+            writer.write_all(",\"unit\":")?;
+            write_string(writer, mf.get_unit())?;
+
+            writer.write_all(",\"metrics\":[")?;
+            for (j, m) in mf.get_metric().iter().enumerate() {
+                if j > 0 {
+                    writer.write_all(",")?;
+                }
+                write_metric(writer, metric_type, m)?;
+            }
+            writer.write_all("]}")?;
+        }
+        writer.write_all("]\n")?;
+
+        Ok(())
+    }
+}
+
+impl Encoder for JsonEncoder {
+    fn encode<W: Write>(&self, metric_families: &[MetricFamily], writer: &mut W) -> Result<()> {
+        self.encode_impl(metric_families, &mut *writer)
+    }
+
+    fn format_type(&self) -> &str {
+        JSON_FORMAT
+    }
+}
+
+/// `write_metric` writes a single metric as a JSON object to `writer`.
+fn write_metric(
+    writer: &mut dyn WriteUtf8,
+    metric_type: MetricType,
+    m: &proto::Metric,
+) -> Result<()> {
+    writer.write_all("{\"labels\":{")?;
+    for (i, lp) in m.get_label().iter().enumerate() {
+        if i > 0 {
+            writer.write_all(",")?;
+        }
+        write_string(writer, lp.get_name())?;
+        writer.write_all(":")?;
+        write_string(writer, lp.get_value())?;
+    }
+    writer.write_all("}")?;
+
+    let timestamp = m.get_timestamp_ms();
+    if timestamp != 0 {
+        writer.write_all(",\"timestamp_ms\":")?;
+        writer.write_all(&timestamp.to_string())?;
+    }
+
+    match metric_type {
+        MetricType::COUNTER => {
+            let c = m.get_counter();
+            write_float_field(writer, "value", c.get_value())?;
+            if c.has_created_timestamp() {
+                let ts = c.get_created_timestamp();
+                write_created(writer, ts.get_seconds(), ts.get_nanos())?;
+            }
+        }
+        MetricType::GAUGE | MetricType::INFO | MetricType::STATESET => {
+            write_float_field(writer, "value", m.get_gauge().get_value())?;
+        }
+        #[allow(deprecated)]
+        MetricType::UNTYPED => {
+            write_float_field(writer, "value", m.get_untyped().get_value())?;
+        }
+        MetricType::HISTOGRAM | MetricType::GAUGE_HISTOGRAM => {
+            let h = m.get_histogram();
+            writer.write_all(",\"count\":")?;
+            writer.write_all(&h.get_sample_count().to_string())?;
+            write_float_field(writer, "sum", h.get_sample_sum())?;
+            writer.write_all(",\"buckets\":[")?;
+            for (i, b) in h.get_bucket().iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(",")?;
+                }
+                writer.write_all("{\"upper_bound\":")?;
+                write_float(writer, b.get_upper_bound())?;
+                writer.write_all(",\"cumulative_count\":")?;
+                writer.write_all(&b.get_cumulative_count().to_string())?;
+                writer.write_all("}")?;
+            }
+            writer.write_all("]")?;
+            if h.has_created_timestamp() {
+                let ts = h.get_created_timestamp();
+                write_created(writer, ts.get_seconds(), ts.get_nanos())?;
+            }
+        }
+        MetricType::SUMMARY => {
+            let s = m.get_summary();
+            writer.write_all(",\"count\":")?;
+            writer.write_all(&s.get_sample_count().to_string())?;
+            write_float_field(writer, "sum", s.get_sample_sum())?;
+            writer.write_all(",\"quantiles\":[")?;
+            for (i, q) in s.get_quantile().iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(",")?;
+                }
+                writer.write_all("{\"quantile\":")?;
+                write_float(writer, q.get_quantile())?;
+                writer.write_all(",\"value\":")?;
+                write_float(writer, q.get_value())?;
+                writer.write_all("}")?;
+            }
+            writer.write_all("]")?;
+            if s.has_created_timestamp() {
+                let ts = s.get_created_timestamp();
+                write_created(writer, ts.get_seconds(), ts.get_nanos())?;
+            }
+        }
+    }
+
+    writer.write_all("}")?;
+    Ok(())
+}
+
+/// `write_created` writes the `created_timestamp_ms` field, given the seconds
+/// and nanoseconds of a created timestamp.
+fn write_created(writer: &mut dyn WriteUtf8, seconds: i64, nanos: i32) -> Result<()> {
+    let created_ms = seconds * 1000 + i64::from(nanos) / 1_000_000;
+    writer.write_all(",\"created_timestamp_ms\":")?;
+    writer.write_all(&created_ms.to_string())?;
+    Ok(())
+}
+
+/// `write_float_field` writes `,"name":v` to `writer`.
+fn write_float_field(writer: &mut dyn WriteUtf8, name: &str, v: f64) -> Result<()> {
+    writer.write_all(",\"")?;
+    writer.write_all(name)?;
+    writer.write_all("\":")?;
+    write_float(writer, v)
+}
+
+/// `write_float` writes `v` as a JSON number, or as a string if it is not
+/// finite.
+fn write_float(writer: &mut dyn WriteUtf8, v: f64) -> Result<()> {
+    if v.is_nan() {
+        writer.write_all("\"NaN\"")?;
+    } else if v.is_infinite() {
+        writer.write_all(if v.is_sign_positive() {
+            "\"+Inf\""
+        } else {
+            "\"-Inf\""
+        })?;
+    } else {
+        writer.write_all(&v.to_string())?;
+    }
+    Ok(())
+}
+
+/// `write_string` writes `s` as a quoted JSON string, escaping quotes,
+/// backslashes and control characters.
+fn write_string(writer: &mut dyn WriteUtf8, s: &str) -> Result<()> {
+    writer.write_all("\"")?;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        let escaped = match c {
+            '"' => "\\\"".to_owned(),
+            '\\' => "\\\\".to_owned(),
+            '\n' => "\\n".to_owned(),
+            '\r' => "\\r".to_owned(),
+            '\t' => "\\t".to_owned(),
+            c if c < ' ' => format!("\\u{:04x}", c as u32),
+            _ => continue,
+        };
+        writer.write_all(&s[start..i])?;
+        writer.write_all(&escaped)?;
+        start = i + c.len_utf8();
+    }
+    writer.write_all(&s[start..])?;
+    writer.write_all("\"")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::Counter;
+    use crate::gauge::Gauge;
+    use crate::histogram::{Histogram, HistogramOpts};
+    use crate::metrics::{Collector, Opts};
+
+    #[test]
+    fn test_write_string() {
+        let mut buf = String::new();
+        write_string(&mut StringBuf(&mut buf), "a\"b\\c\nd\u{1}é").unwrap();
+        assert_eq!(buf, r#""a\"b\\c\nd\u0001é""#);
+    }
+
+    #[test]
+    fn test_json_encoder() {
+        let opts = Opts::new("test_gauge", "test \"help\"").const_label("a", "1");
+        let gauge = Gauge::with_opts(opts).unwrap();
+        gauge.set(f64::NAN);
+
+        let opts = HistogramOpts::new("test_histogram_seconds", "test help")
+            .unit("seconds")
+            .buckets(vec![0.5]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+        histogram.observe(0.25);
+        histogram.observe(0.5);
+
+        let mut mfs = gauge.collect();
+        mfs[0].mut_metric()[0].set_timestamp_ms(1_500);
+        mfs.extend(histogram.collect());
+        let h = mfs[1].get_metric()[0].get_histogram();
+        let ts = h.get_created_timestamp();
+        let created_ms = ts.get_seconds() * 1000 + i64::from(ts.get_nanos()) / 1_000_000;
+
+        let encoder = JsonEncoder::new();
+        let mut writer = Vec::<u8>::new();
+        encoder.encode(&mfs, &mut writer).unwrap();
+        let ans = format!(
+            concat!(
+                r#"[{{"name":"test_gauge","help":"test \"help\"","type":"gauge","unit":"","#,
+                r#""metrics":[{{"labels":{{"a":"1"}},"timestamp_ms":1500,"value":"NaN"}}]}},"#,
+                r#"{{"name":"test_histogram_seconds","help":"test help","type":"histogram","#,
+                r#""unit":"seconds","metrics":[{{"labels":{{}},"count":2,"sum":0.75,"#,
+                r#""buckets":[{{"upper_bound":0.5,"cumulative_count":2}}],"#,
+                r#""created_timestamp_ms":{}}}]}}]"#,
+                "\n"
+            ),
+            created_ms
+        );
+        assert_eq!(ans, std::str::from_utf8(&writer).unwrap());
+        assert_eq!(ans, encoder.encode_to_string(&mfs).unwrap());
+        assert_eq!(encoder.format_type(), JSON_FORMAT);
+
+        let counter = Counter::new("test_counter", "test help").unwrap();
+        let txt = encoder.encode_to_string(&counter.collect()).unwrap();
+        assert!(txt.contains(r#""type":"counter","#));
+        assert!(txt.contains(r#""value":0,"created_timestamp_ms":"#));
+        assert_eq!(encoder.encode_to_string(&[]).unwrap(), "[]\n");
+    }
+}
@@ -1,14 +1,22 @@
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
+mod json;
+mod openmetrics;
 #[cfg(feature = "protobuf")]
 mod pb;
 mod text;
+mod text_decoder;
 
+pub use self::json::{JsonEncoder, JSON_FORMAT};
+pub use self::openmetrics::{OpenMetricsEncoder, OPENMETRICS_FORMAT};
 #[cfg(feature = "protobuf")]
-pub use self::pb::{ProtobufEncoder, PROTOBUF_FORMAT};
+pub use self::pb::{
+    ProtobufDecodeIter, ProtobufDecoder, ProtobufEncoder, DEFAULT_MAX_MESSAGE_SIZE, PROTOBUF_FORMAT,
+};
 pub use self::text::{TextEncoder, TEXT_FORMAT};
+pub use self::text_decoder::{parse_text, TextDecoder};
 
-use std::io::Write;
+use std::io::{Read, Write};
 
 use crate::errors::{Error, Result};
 use crate::proto::MetricFamily;
@@ -26,6 +34,14 @@ pub trait Encoder {
     fn format_type(&self) -> &str;
 }
 
+/// An interface for decoding metric families from an underlying wire protocol,
+/// the counterpart of [`Encoder`].
+pub trait Decoder {
+    /// `decode` reads all the data from `reader` and converts it into a vector
+    /// of MetricFamily proto messages.
+    fn decode<R: Read>(&self, reader: &mut R) -> Result<Vec<MetricFamily>>;
+}
+
 fn check_metric_family(mf: &MetricFamily) -> Result<()> {
     if mf.get_metric().is_empty() {
         return Err(Error::Msg(format!("MetricFamily has no metrics: {:?}", mf)));
@@ -66,7 +82,7 @@ mod tests {
         let mut mfs = cv.collect();
 
         // Empty name
-        (&mut mfs[0]).clear_name();
+        mfs[0].clear_name();
         check_metric_family(&mfs[0]).unwrap_err();
         pb_encoder.encode(&mfs, &mut writer).unwrap_err();
         assert_eq!(writer.len(), 0);
@@ -93,7 +109,7 @@ mod tests {
         let mut mfs = cv.collect();
 
         // Empty name
-        (&mut mfs[0]).clear_name();
+        mfs[0].clear_name();
         check_metric_family(&mfs[0]).unwrap_err();
         text_encoder.encode(&mfs, &mut writer).unwrap_err();
         assert_eq!(writer.len(), 0);
@@ -0,0 +1,674 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::io::Write;
+
+use crate::errors::Result;
+use crate::histogram::BUCKET_LABEL;
+use crate::proto::{self, MetricFamily, MetricType};
+
+use super::text::{escape_string, StringBuf, WriteUtf8};
+use super::{check_metric_family, Encoder};
+
+/// The OpenMetrics text format of metric family.
+pub const OPENMETRICS_FORMAT: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+const POSITIVE_INF: &str = "+Inf";
+const QUANTILE: &str = "quantile";
+const TOTAL_SUFFIX: &str = "_total";
+const INFO_SUFFIX: &str = "_info";
+const CREATED_SUFFIX: &str = "_created";
+
+/// An implementation of an [`Encoder`] that converts a [`MetricFamily`] proto message
+/// into the [OpenMetrics] 1.0 text format.
+///
+/// Compared to [`TextEncoder`](super::TextEncoder), counter families are exposed
+/// without their `_total` suffix while their samples always carry it, values are
+/// always rendered as floats, timestamps are expressed in seconds and the
+/// exposition is terminated by a `# EOF` line. Counters, histograms and
+/// summaries carrying a created timestamp expose it as a `_created` sample.
+///
+/// [OpenMetrics]: https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md
+#[derive(Debug, Default)]
+pub struct OpenMetricsEncoder;
+
+impl OpenMetricsEncoder {
+    /// Create a new OpenMetrics encoder.
+    pub fn new() -> OpenMetricsEncoder {
+        OpenMetricsEncoder
+    }
+    /// Appends metrics to a given `String` buffer.
+    ///
+    /// This is a convenience wrapper around `<OpenMetricsEncoder as Encoder>::encode`.
+    pub fn encode_utf8(&self, metric_families: &[MetricFamily], buf: &mut String) -> Result<()> {
+        self.encode_impl(metric_families, &mut StringBuf(buf))?;
+        Ok(())
+    }
+    /// Converts metrics to `String`.
+    ///
+    /// This is a convenience wrapper around `<OpenMetricsEncoder as Encoder>::encode`.
+    pub fn encode_to_string(&self, metric_families: &[MetricFamily]) -> Result<String> {
+        let mut buf = String::new();
+        self.encode_utf8(metric_families, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn encode_impl(
+        &self,
+        metric_families: &[MetricFamily],
+        writer: &mut dyn WriteUtf8,
+    ) -> Result<()> {
+        for mf in metric_families {
+            // Fail-fast checks.
+            check_metric_family(mf)?;
+
+            let metric_type = mf.get_field_type();
+            let name = mf.get_name();
+            // Counter and info families are named without the `_total` and
+            // `_info` suffix respectively, which belongs to the sample name only.
+            let family_name = match metric_type {
+                MetricType::COUNTER => name.strip_suffix(TOTAL_SUFFIX).unwrap_or(name),
+                MetricType::INFO => name.strip_suffix(INFO_SUFFIX).unwrap_or(name),
+                _ => name,
+            };
+
+            // Write `# TYPE` header.
+            writer.write_all("# TYPE ")?;
+            writer.write_all(family_name)?;
+            writer.write_all(" ")?;
+            writer.write_all(type_name(metric_type))?;
+            writer.write_all("\n")?;
+
+            // Write `# UNIT` header.
+            let unit = mf.get_unit();
+            if !unit.is_empty() {
+                writer.write_all("# UNIT ")?;
+                writer.write_all(family_name)?;
+                writer.write_all(" ")?;
+                writer.write_all(unit)?;
+                writer.write_all("\n")?;
+            }
+
+            // Write `# HELP` header.
+            let help = mf.get_help();
+            if !help.is_empty() {
+                writer.write_all("# HELP ")?;
+                writer.write_all(family_name)?;
+                writer.write_all(" ")?;
+                writer.write_all(&escape_string(help, true))?;
+                writer.write_all("\n")?;
+            }
+
+            for m in mf.get_metric() {
+                match metric_type {
+                    MetricType::COUNTER => {
+                        let value = format_float(m.get_counter().get_value());
+                        let c = m.get_counter();
+                        let exemplar = Some(c.get_exemplar()).filter(|_| c.has_exemplar());
+                        write_sample(
+                            writer,
+                            family_name,
+                            Some(TOTAL_SUFFIX),
+                            m,
+                            None,
+                            &value,
+                            exemplar,
+                        )?;
+                        if c.has_created_timestamp() {
+                            let ts = c.get_created_timestamp();
+                            write_created(
+                                writer,
+                                family_name,
+                                m,
+                                ts.get_seconds(),
+                                ts.get_nanos(),
+                            )?;
+                        }
+                    }
+                    MetricType::GAUGE => {
+                        let value = format_float(m.get_gauge().get_value());
+                        write_sample(writer, family_name, None, m, None, &value, None)?;
+                    }
+                    MetricType::INFO => {
+                        write_sample(writer, family_name, Some(INFO_SUFFIX), m, None, "1", None)?;
+                    }
+                    MetricType::STATESET => {
+                        let value = if m.get_gauge().get_value() == 0.0 {
+                            "0"
+                        } else {
+                            "1"
+                        };
+                        write_sample(writer, family_name, None, m, None, value, None)?;
+                    }
+                    #[allow(deprecated)]
+                    MetricType::UNTYPED => {
+                        let value = format_float(m.get_untyped().get_value());
+                        write_sample(writer, family_name, None, m, None, &value, None)?;
+                    }
+                    MetricType::HISTOGRAM | MetricType::GAUGE_HISTOGRAM => {
+                        let h = m.get_histogram();
+                        // Gauge histograms expose their sum and count as
+                        // `_gsum` and `_gcount`, as they may go down.
+                        let (sum_suffix, count_suffix) = match metric_type {
+                            MetricType::GAUGE_HISTOGRAM => ("_gsum", "_gcount"),
+                            _ => ("_sum", "_count"),
+                        };
+
+                        let mut inf_seen = false;
+                        for b in h.get_bucket() {
+                            let upper_bound = b.get_upper_bound();
+                            write_sample(
+                                writer,
+                                family_name,
+                                Some("_bucket"),
+                                m,
+                                Some((BUCKET_LABEL, &format_float(upper_bound))),
+                                &b.get_cumulative_count().to_string(),
+                                Some(b.get_exemplar()).filter(|_| b.has_exemplar()),
+                            )?;
+                            if upper_bound.is_sign_positive() && upper_bound.is_infinite() {
+                                inf_seen = true;
+                            }
+                        }
+                        if !inf_seen {
+                            write_sample(
+                                writer,
+                                family_name,
+                                Some("_bucket"),
+                                m,
+                                Some((BUCKET_LABEL, POSITIVE_INF)),
+                                &h.get_sample_count().to_string(),
+                                None,
+                            )?;
+                        }
+
+                        write_sample(
+                            writer,
+                            family_name,
+                            Some(sum_suffix),
+                            m,
+                            None,
+                            &format_float(h.get_sample_sum()),
+                            None,
+                        )?;
+
+                        write_sample(
+                            writer,
+                            family_name,
+                            Some(count_suffix),
+                            m,
+                            None,
+                            &h.get_sample_count().to_string(),
+                            None,
+                        )?;
+
+                        // Gauge histograms have no created timestamp.
+                        if metric_type == MetricType::HISTOGRAM && h.has_created_timestamp() {
+                            let ts = h.get_created_timestamp();
+                            write_created(
+                                writer,
+                                family_name,
+                                m,
+                                ts.get_seconds(),
+                                ts.get_nanos(),
+                            )?;
+                        }
+                    }
+                    MetricType::SUMMARY => {
+                        let s = m.get_summary();
+
+                        for q in s.get_quantile() {
+                            write_sample(
+                                writer,
+                                family_name,
+                                None,
+                                m,
+                                Some((QUANTILE, &format_float(q.get_quantile()))),
+                                &format_float(q.get_value()),
+                                None,
+                            )?;
+                        }
+
+                        write_sample(
+                            writer,
+                            family_name,
+                            Some("_sum"),
+                            m,
+                            None,
+                            &format_float(s.get_sample_sum()),
+                            None,
+                        )?;
+
+                        write_sample(
+                            writer,
+                            family_name,
+                            Some("_count"),
+                            m,
+                            None,
+                            &s.get_sample_count().to_string(),
+                            None,
+                        )?;
+
+                        if s.has_created_timestamp() {
+                            let ts = s.get_created_timestamp();
+                            write_created(
+                                writer,
+                                family_name,
+                                m,
+                                ts.get_seconds(),
+                                ts.get_nanos(),
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+
+        writer.write_all("# EOF\n")?;
+
+        Ok(())
+    }
+}
+
+impl Encoder for OpenMetricsEncoder {
+    fn encode<W: Write>(&self, metric_families: &[MetricFamily], writer: &mut W) -> Result<()> {
+        self.encode_impl(metric_families, &mut *writer)
+    }
+
+    fn format_type(&self) -> &str {
+        OPENMETRICS_FORMAT
+    }
+}
+
+/// `type_name` returns the OpenMetrics name of the given metric type.
+fn type_name(metric_type: MetricType) -> &'static str {
+    match metric_type {
+        MetricType::COUNTER => "counter",
+        MetricType::GAUGE => "gauge",
+        MetricType::SUMMARY => "summary",
+        MetricType::UNTYPED => "unknown",
+        MetricType::HISTOGRAM => "histogram",
+        MetricType::GAUGE_HISTOGRAM => "gaugehistogram",
+        MetricType::INFO => "info",
+        MetricType::STATESET => "stateset",
+    }
+}
+
+/// `format_float` renders `v` the way OpenMetrics expects a float to look like,
+/// i.e. always with a decimal point and with `+Inf`, `-Inf` and `NaN` spelled
+/// out.
+fn format_float(v: f64) -> String {
+    if v.is_nan() {
+        return "NaN".to_owned();
+    }
+    if v.is_infinite() {
+        return if v.is_sign_positive() { "+Inf" } else { "-Inf" }.to_owned();
+    }
+    let mut s = v.to_string();
+    if !s.contains('.') {
+        s.push_str(".0");
+    }
+    s
+}
+
+/// `write_sample` writes a single sample in OpenMetrics text format to
+/// `writer`, given the metric name, an optional metric name postfix, the metric
+/// proto message itself, optionally an additional label name and value, the
+/// already formatted value and optionally an exemplar.
+fn write_sample(
+    writer: &mut dyn WriteUtf8,
+    name: &str,
+    name_postfix: Option<&str>,
+    mc: &proto::Metric,
+    additional_label: Option<(&str, &str)>,
+    value: &str,
+    exemplar: Option<&proto::Exemplar>,
+) -> Result<()> {
+    writer.write_all(name)?;
+    if let Some(postfix) = name_postfix {
+        writer.write_all(postfix)?;
+    }
+
+    label_pairs_to_text(mc.get_label(), additional_label, writer)?;
+
+    writer.write_all(" ")?;
+    writer.write_all(value)?;
+
+    let timestamp = mc.get_timestamp_ms();
+    if timestamp != 0 {
+        writer.write_all(" ")?;
+        writer.write_all(&format_timestamp(timestamp))?;
+    }
+
+    if let Some(exemplar) = exemplar {
+        write_exemplar(writer, exemplar)?;
+    }
+
+    writer.write_all("\n")?;
+
+    Ok(())
+}
+
+/// `write_exemplar` appends `exemplar` to the sample line being written, in the
+/// form ` # {labels} value timestamp`.
+fn write_exemplar(writer: &mut dyn WriteUtf8, exemplar: &proto::Exemplar) -> Result<()> {
+    writer.write_all(" # ")?;
+    if exemplar.get_label().is_empty() {
+        writer.write_all("{}")?;
+    } else {
+        label_pairs_to_text(exemplar.get_label(), None, writer)?;
+    }
+    writer.write_all(" ")?;
+    writer.write_all(&format_float(exemplar.get_value()))?;
+
+    if exemplar.has_timestamp() {
+        let ts = exemplar.get_timestamp();
+        writer.write_all(" ")?;
+        writer.write_all(&format_timestamp(to_millis(
+            ts.get_seconds(),
+            ts.get_nanos(),
+        )))?;
+    }
+
+    Ok(())
+}
+
+/// `write_created` writes the `_created` sample of a counter, histogram or
+/// summary, given the seconds and nanoseconds of its created timestamp.
+fn write_created(
+    writer: &mut dyn WriteUtf8,
+    name: &str,
+    mc: &proto::Metric,
+    seconds: i64,
+    nanos: i32,
+) -> Result<()> {
+    let created = format_timestamp(to_millis(seconds, nanos));
+    write_sample(writer, name, Some(CREATED_SUFFIX), mc, None, &created, None)
+}
+
+/// `to_millis` converts a proto timestamp, given as seconds and nanoseconds,
+/// into milliseconds.
+fn to_millis(seconds: i64, nanos: i32) -> i64 {
+    seconds * 1000 + i64::from(nanos) / 1_000_000
+}
+
+/// `format_timestamp` converts a timestamp in milliseconds into the seconds
+/// based representation used by OpenMetrics.
+fn format_timestamp(timestamp_ms: i64) -> String {
+    let secs = timestamp_ms.div_euclid(1000);
+    let millis = timestamp_ms.rem_euclid(1000);
+    format!("{}.{:03}", secs, millis)
+}
+
+/// `label_pairs_to_text` converts a slice of `LabelPair` proto messages plus
+/// the explicitly given additional label pair into text formatted as required
+/// by the OpenMetrics format and writes it to `writer`.
+fn label_pairs_to_text(
+    pairs: &[proto::LabelPair],
+    additional_label: Option<(&str, &str)>,
+    writer: &mut dyn WriteUtf8,
+) -> Result<()> {
+    if pairs.is_empty() && additional_label.is_none() {
+        return Ok(());
+    }
+
+    let mut separator = "{";
+    for lp in pairs {
+        writer.write_all(separator)?;
+        writer.write_all(lp.get_name())?;
+        writer.write_all("=\"")?;
+        writer.write_all(&escape_string(lp.get_value(), true))?;
+        writer.write_all("\"")?;
+
+        separator = ",";
+    }
+
+    if let Some((name, value)) = additional_label {
+        writer.write_all(separator)?;
+        writer.write_all(name)?;
+        writer.write_all("=\"")?;
+        writer.write_all(&escape_string(value, true))?;
+        writer.write_all("\"")?;
+    }
+
+    writer.write_all("}")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::Counter;
+    use crate::gauge::Gauge;
+    use crate::histogram::{Histogram, HistogramOpts};
+    use crate::metrics::{Collector, Opts};
+
+    #[test]
+    fn test_format_float() {
+        assert_eq!(format_float(1.0), "1.0");
+        assert_eq!(format_float(0.25), "0.25");
+        assert_eq!(format_float(-3.0), "-3.0");
+        assert_eq!(format_float(f64::INFINITY), "+Inf");
+        assert_eq!(format_float(f64::NEG_INFINITY), "-Inf");
+        assert_eq!(format_float(f64::NAN), "NaN");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(1_500), "1.500");
+        assert_eq!(format_timestamp(1_000), "1.000");
+        assert_eq!(format_timestamp(-1), "-1.999");
+    }
+
+    #[test]
+    fn test_openmetrics_encoder() {
+        let counter_opts = Opts::new("test_counter_total", "test \"help\"")
+            .const_label("a", "1")
+            .const_label("b", "2");
+        let counter = Counter::with_opts(counter_opts).unwrap();
+        counter.inc();
+
+        let gauge_opts = Opts::new("test_gauge", "test help").const_label("a", "1");
+        let gauge = Gauge::with_opts(gauge_opts).unwrap();
+        gauge.set(42.5);
+
+        let mut mfs = counter.collect();
+        mfs.extend(gauge.collect());
+        let ts = mfs[0].get_metric()[0].get_counter().get_created_timestamp();
+        let created = format_timestamp(to_millis(ts.get_seconds(), ts.get_nanos()));
+
+        let encoder = OpenMetricsEncoder::new();
+        let mut writer = Vec::<u8>::new();
+        encoder.encode(&mfs, &mut writer).unwrap();
+
+        let ans = format!(
+            r##"# TYPE test_counter counter
+# HELP test_counter test \"help\"
+test_counter_total{{a="1",b="2"}} 1.0
+test_counter_created{{a="1",b="2"}} {}
+# TYPE test_gauge gauge
+# HELP test_gauge test help
+test_gauge{{a="1"}} 42.5
+# EOF
+"##,
+            created
+        );
+        assert_eq!(ans, std::str::from_utf8(&writer).unwrap());
+        assert_eq!(ans, encoder.encode_to_string(&mfs).unwrap());
+    }
+
+    #[test]
+    fn test_openmetrics_encoder_unit() {
+        let opts = Opts::new("test_temperature_celsius", "test help").unit("celsius");
+        let gauge = Gauge::with_opts(opts).unwrap();
+        gauge.set(21.5);
+
+        let txt = OpenMetricsEncoder::new()
+            .encode_to_string(&gauge.collect())
+            .unwrap();
+        let ans = r##"# TYPE test_temperature_celsius gauge
+# UNIT test_temperature_celsius celsius
+# HELP test_temperature_celsius test help
+test_temperature_celsius 21.5
+# EOF
+"##;
+        assert_eq!(ans, txt);
+
+        let opts = HistogramOpts::new("test_histogram", "test help").unit("seconds");
+        assert!(Histogram::with_opts(opts).is_err());
+    }
+
+    #[test]
+    fn test_openmetrics_encoder_counter_without_suffix() {
+        let counter = Counter::new("test_requests", "test help").unwrap();
+        counter.inc_by(3.0);
+
+        let mfs = counter.collect();
+        let ts = mfs[0].get_metric()[0].get_counter().get_created_timestamp();
+        let created = format_timestamp(to_millis(ts.get_seconds(), ts.get_nanos()));
+
+        let encoder = OpenMetricsEncoder::new();
+        let txt = encoder.encode_to_string(&mfs).unwrap();
+        let ans = format!(
+            r##"# TYPE test_requests counter
+# HELP test_requests test help
+test_requests_total 3.0
+test_requests_created {}
+# EOF
+"##,
+            created
+        );
+        assert_eq!(ans, txt);
+    }
+
+    #[test]
+    fn test_openmetrics_encoder_histogram() {
+        let opts = HistogramOpts::new("test_histogram", "test help")
+            .const_label("a", "1")
+            .buckets(vec![0.5, 1.0]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+        histogram.observe(0.25);
+        histogram.observe(2.0);
+
+        let mfs = histogram.collect();
+        let ts = mfs[0].get_metric()[0]
+            .get_histogram()
+            .get_created_timestamp();
+        let created = format_timestamp(to_millis(ts.get_seconds(), ts.get_nanos()));
+
+        let encoder = OpenMetricsEncoder::new();
+        let txt = encoder.encode_to_string(&mfs).unwrap();
+        let ans = format!(
+            r##"# TYPE test_histogram histogram
+# HELP test_histogram test help
+test_histogram_bucket{{a="1",le="0.5"}} 1
+test_histogram_bucket{{a="1",le="1.0"}} 1
+test_histogram_bucket{{a="1",le="+Inf"}} 2
+test_histogram_sum{{a="1"}} 2.25
+test_histogram_count{{a="1"}} 2
+test_histogram_created{{a="1"}} {}
+# EOF
+"##,
+            created
+        );
+        assert_eq!(ans, txt);
+    }
+
+    #[test]
+    fn test_openmetrics_encoder_summary_and_timestamp() {
+        use crate::proto::{Metric, Quantile, Summary};
+
+        let mut metric_family = MetricFamily::default();
+        metric_family.set_name("test_summary".to_string());
+        metric_family.set_field_type(MetricType::SUMMARY);
+
+        let mut summary = Summary::default();
+        summary.set_sample_count(5);
+        summary.set_sample_sum(15.0);
+
+        let mut quantile = Quantile::default();
+        quantile.set_quantile(0.5);
+        quantile.set_value(3.0);
+        summary.set_quantile(from_vec!(vec!(quantile)));
+
+        let mut metric = Metric::default();
+        metric.set_summary(summary);
+        metric.set_timestamp_ms(1_234_567);
+        metric_family.set_metric(from_vec!(vec!(metric)));
+
+        let encoder = OpenMetricsEncoder::new();
+        let txt = encoder.encode_to_string(&[metric_family]).unwrap();
+        let ans = r##"# TYPE test_summary summary
+test_summary{quantile="0.5"} 3.0 1234.567
+test_summary_sum 15.0 1234.567
+test_summary_count 5 1234.567
+# EOF
+"##;
+        assert_eq!(ans, txt);
+    }
+
+    #[test]
+    #[cfg(feature = "protobuf")]
+    fn test_openmetrics_encoder_exemplar() {
+        use crate::proto::{Exemplar, LabelPair, Metric};
+        use protobuf::well_known_types::Timestamp;
+
+        let mut label = LabelPair::default();
+        label.set_name("trace_id".to_string());
+        label.set_value("abc".to_string());
+        let mut timestamp = Timestamp::default();
+        timestamp.set_seconds(1_600_000_000);
+        timestamp.set_nanos(250_000_000);
+        let mut exemplar = Exemplar::default();
+        exemplar.set_label(from_vec!(vec![label]));
+        exemplar.set_value(2.0);
+        exemplar.set_timestamp(timestamp);
+
+        let mut counter = crate::proto::Counter::default();
+        counter.set_value(5.0);
+        counter.set_exemplar(exemplar);
+        let mut metric = Metric::default();
+        metric.set_counter(counter);
+
+        let mut metric_family = MetricFamily::default();
+        metric_family.set_name("test_requests_total".to_string());
+        metric_family.set_field_type(MetricType::COUNTER);
+        metric_family.set_metric(from_vec!(vec![metric]));
+
+        let encoder = OpenMetricsEncoder::new();
+        let txt = encoder.encode_to_string(&[metric_family]).unwrap();
+        let ans = r##"# TYPE test_requests counter
+test_requests_total 5.0 # {trace_id="abc"} 2.0 1600000000.250
+# EOF
+"##;
+        assert_eq!(ans, txt);
+    }
+
+    #[test]
+    fn test_openmetrics_encoder_histogram_exemplar() {
+        use std::collections::HashMap;
+
+        let opts = HistogramOpts::new("test_histogram", "test help").buckets(vec![1.0]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+        let mut labels = HashMap::new();
+        labels.insert("trace_id", "abc");
+        histogram.observe_with_exemplar(0.5, &labels).unwrap();
+
+        let encoder = OpenMetricsEncoder::new();
+        let txt = encoder.encode_to_string(&histogram.collect()).unwrap();
+        let line = txt.lines().nth(2).unwrap();
+        assert!(
+            line.starts_with(r#"test_histogram_bucket{le="1.0"} 1 # {trace_id="abc"} 0.5 "#),
+            "{}",
+            line
+        );
+        assert!(txt.contains("test_histogram_bucket{le=\"+Inf\"} 1\n"));
+    }
+
+    #[test]
+    fn test_openmetrics_encoder_empty() {
+        let encoder = OpenMetricsEncoder::new();
+        assert_eq!(encoder.encode_to_string(&[]).unwrap(), "# EOF\n");
+        assert_eq!(encoder.format_type(), OPENMETRICS_FORMAT);
+    }
+}
@@ -1,13 +1,13 @@
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
-use std::io::Write;
+use std::io::{self, BufRead, BufReader, Read, Write};
 
 use protobuf::Message;
 
-use crate::errors::Result;
-use crate::proto::MetricFamily;
+use crate::errors::{Error, Result};
+use crate::proto::{MetricFamily, MetricType};
 
-use super::{check_metric_family, Encoder};
+use super::{check_metric_family, Decoder, Encoder};
 
 /// The protocol buffer format of metric family.
 pub const PROTOBUF_FORMAT: &str = "application/vnd.google.protobuf; \
@@ -31,7 +31,15 @@ impl Encoder for ProtobufEncoder {
         for mf in metric_families {
             // Fail-fast checks.
             check_metric_family(mf)?;
-            mf.write_length_delimited_to_writer(writer)?;
+            match mf.get_field_type() {
+                // Unknown to the upstream client model, hence exposed as gauges.
+                MetricType::INFO | MetricType::STATESET => {
+                    let mut mf = mf.clone();
+                    mf.set_field_type(MetricType::GAUGE);
+                    mf.write_length_delimited_to_writer(writer)?;
+                }
+                _ => mf.write_length_delimited_to_writer(writer)?,
+            }
         }
         Ok(())
     }
@@ -41,11 +49,146 @@ impl Encoder for ProtobufEncoder {
     }
 }
 
+/// The default maximum size of a single encoded [`MetricFamily`] accepted by a
+/// [`ProtobufDecoder`], 16 MiB.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 << 20;
+
+/// The maximum number of bytes of a varint encoded `u64`.
+const MAX_VARINT_LEN: usize = 10;
+
+/// An implementation of a [`Decoder`] that reads length-delimited
+/// [`MetricFamily`] proto messages, as written by [`ProtobufEncoder`].
+#[derive(Debug)]
+pub struct ProtobufDecoder {
+    max_message_size: usize,
+}
+
+impl Default for ProtobufDecoder {
+    fn default() -> ProtobufDecoder {
+        ProtobufDecoder::new()
+    }
+}
+
+impl ProtobufDecoder {
+    /// Create a new protobuf decoder accepting messages of up to
+    /// [`DEFAULT_MAX_MESSAGE_SIZE`] bytes.
+    pub fn new() -> ProtobufDecoder {
+        ProtobufDecoder {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Set the maximum size in bytes of a single message. Larger messages are
+    /// rejected with an error before any of their bytes are buffered.
+    pub fn max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Return an iterator which lazily decodes the [`MetricFamily`] messages
+    /// read from `reader`, one at a time.
+    ///
+    /// The iterator ends at the end of the input and stops after the first
+    /// error, e.g. a truncated frame or an oversized message.
+    pub fn decode_iter<R: Read>(&self, reader: R) -> ProtobufDecodeIter<R> {
+        ProtobufDecodeIter {
+            reader: BufReader::new(reader),
+            max_message_size: self.max_message_size,
+            buf: Vec::new(),
+            done: false,
+        }
+    }
+}
+
+impl Decoder for ProtobufDecoder {
+    fn decode<R: Read>(&self, reader: &mut R) -> Result<Vec<MetricFamily>> {
+        self.decode_iter(reader).collect()
+    }
+}
+
+/// An iterator over the [`MetricFamily`] messages of a length-delimited
+/// protobuf stream, see [`ProtobufDecoder::decode_iter`].
+#[derive(Debug)]
+pub struct ProtobufDecodeIter<R> {
+    reader: BufReader<R>,
+    max_message_size: usize,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> ProtobufDecodeIter<R> {
+    /// Read the varint length prefix of the next frame. Returns `None` if the
+    /// stream ends cleanly before the frame.
+    fn read_length(&mut self) -> Result<Option<u64>> {
+        let mut length = 0u64;
+        for i in 0..MAX_VARINT_LEN {
+            let byte = match self.reader.fill_buf()?.first() {
+                Some(&byte) => byte,
+                None if i == 0 => return Ok(None),
+                None => {
+                    return Err(Error::Msg(
+                        "truncated protobuf frame: incomplete length prefix".to_owned(),
+                    ))
+                }
+            };
+            self.reader.consume(1);
+
+            length |= u64::from(byte & 0x7f) << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(Some(length));
+            }
+        }
+        Err(Error::Msg(
+            "invalid protobuf frame: length prefix is too long".to_owned(),
+        ))
+    }
+
+    fn read_message(&mut self) -> Result<Option<MetricFamily>> {
+        let length = match self.read_length()? {
+            Some(length) => length,
+            None => return Ok(None),
+        };
+        if length > self.max_message_size as u64 {
+            return Err(Error::Msg(format!(
+                "protobuf message of {} bytes exceeds the maximum size of {} bytes",
+                length, self.max_message_size
+            )));
+        }
+
+        self.buf.resize(length as usize, 0);
+        if let Err(e) = self.reader.read_exact(&mut self.buf) {
+            return Err(match e.kind() {
+                io::ErrorKind::UnexpectedEof => Error::Msg(format!(
+                    "truncated protobuf frame: expected a message of {} bytes",
+                    length
+                )),
+                _ => e.into(),
+            });
+        }
+        Ok(Some(MetricFamily::parse_from_bytes(&self.buf)?))
+    }
+}
+
+impl<R: Read> Iterator for ProtobufDecodeIter<R> {
+    type Item = Result<MetricFamily>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.read_message().transpose();
+        if !matches!(res, Some(Ok(_))) {
+            self.done = true;
+        }
+        res
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::counter::CounterVec;
-    use crate::encoder::Encoder;
-    use crate::metrics::Opts;
+    use crate::encoder::{Decoder, Encoder};
+    use crate::metrics::{Collector, Opts};
     use crate::registry;
 
     // TODO: add more tests.
@@ -59,7 +202,9 @@ mod tests {
         reg.register(Box::new(cv.clone())).unwrap();
 
         cv.get_metric_with_label_values(&["2230"]).unwrap().inc();
-        let mf = reg.gather();
+        let mut mf = reg.gather();
+        // The created timestamp differs between runs, leave it out.
+        mf[0].mut_metric()[0].mut_counter().clear_created_timestamp();
         let mut writer = Vec::<u8>::new();
         let encoder = super::ProtobufEncoder::new();
         let res = encoder.encode(&mf, &mut writer);
@@ -74,4 +219,79 @@ mod tests {
                        240, 63];
         assert_eq!(ans, writer);
     }
+
+    #[test]
+    fn test_protobuf_decoder() {
+        let cv = CounterVec::new(
+            Opts::new("test_counter_vec", "help information"),
+            &["labelname"],
+        )
+        .unwrap();
+        let gv = crate::GaugeVec::new(
+            Opts::new("test_gauge_vec", "help information"),
+            &["labelname"],
+        )
+        .unwrap();
+        let reg = registry::Registry::new();
+        reg.register(Box::new(cv.clone())).unwrap();
+        reg.register(Box::new(gv.clone())).unwrap();
+        cv.with_label_values(&["a"]).inc();
+        cv.with_label_values(&["b"]).inc_by(2.0);
+        gv.with_label_values(&["c"]).set(-3.5);
+
+        let mfs = reg.gather();
+        let mut writer = Vec::<u8>::new();
+        super::ProtobufEncoder::new()
+            .encode(&mfs, &mut writer)
+            .unwrap();
+
+        let decoder = super::ProtobufDecoder::new();
+        let decoded = decoder.decode(&mut writer.as_slice()).unwrap();
+        assert_eq!(mfs, decoded);
+
+        let mut iter = decoder.decode_iter(writer.as_slice());
+        assert_eq!(iter.next().unwrap().unwrap(), mfs[0]);
+        assert_eq!(iter.next().unwrap().unwrap(), mfs[1]);
+        assert!(iter.next().is_none());
+
+        assert!(decoder.decode(&mut [].as_ref()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_protobuf_decoder_errors() {
+        let cv = CounterVec::new(
+            Opts::new("test_counter_vec", "help information"),
+            &["labelname"],
+        )
+        .unwrap();
+        cv.with_label_values(&["a"]).inc();
+        let mut writer = Vec::<u8>::new();
+        super::ProtobufEncoder::new()
+            .encode(&cv.collect(), &mut writer)
+            .unwrap();
+        let decoder = super::ProtobufDecoder::new();
+
+        // Truncated message.
+        let truncated = &writer[..writer.len() - 1];
+        let err = decoder.decode(&mut &truncated[..]).unwrap_err();
+        assert!(format!("{}", err).contains("truncated"), "{}", err);
+
+        // Truncated length prefix.
+        let err = decoder.decode(&mut [0x80].as_ref()).unwrap_err();
+        assert!(format!("{}", err).contains("truncated"), "{}", err);
+
+        // Invalid length prefix.
+        let err = decoder.decode(&mut [0xff; 11].as_ref()).unwrap_err();
+        assert!(format!("{}", err).contains("too long"), "{}", err);
+
+        // Oversized message.
+        let small = super::ProtobufDecoder::new().max_message_size(writer.len() - 2);
+        let err = small.decode(&mut writer.as_slice()).unwrap_err();
+        assert!(format!("{}", err).contains("exceeds"), "{}", err);
+
+        // The iterator stops after the first error.
+        let mut iter = decoder.decode_iter(truncated);
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
 }
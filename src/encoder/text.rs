@@ -67,7 +67,13 @@ impl TextEncoder {
 
             // Write `# TYPE` header.
             let metric_type = mf.get_field_type();
-            let lowercase_type = format!("{:?}", metric_type).to_lowercase();
+            let lowercase_type = match metric_type {
+                // The text format has no info, stateset and gauge histogram
+                // types.
+                MetricType::INFO | MetricType::STATESET => "gauge".to_owned(),
+                MetricType::GAUGE_HISTOGRAM => "histogram".to_owned(),
+                _ => format!("{:?}", metric_type).to_lowercase(),
+            };
             writer.write_all("# TYPE ")?;
             writer.write_all(name)?;
             writer.write_all(" ")?;
@@ -79,26 +85,31 @@ impl TextEncoder {
                     MetricType::COUNTER => {
                         write_sample(writer, name, None, m, None, m.get_counter().get_value())?;
                     }
-                    MetricType::GAUGE => {
+                    MetricType::GAUGE | MetricType::INFO | MetricType::STATESET => {
                         write_sample(writer, name, None, m, None, m.get_gauge().get_value())?;
                     }
-                    MetricType::HISTOGRAM => {
+                    MetricType::HISTOGRAM | MetricType::GAUGE_HISTOGRAM => {
                         let h = m.get_histogram();
 
                         let mut inf_seen = false;
                         for b in h.get_bucket() {
                             let upper_bound = b.get_upper_bound();
+                            let is_inf =
+                                upper_bound.is_sign_positive() && upper_bound.is_infinite();
+                            let upper_bound = if is_inf {
+                                POSITIVE_INF.to_owned()
+                            } else {
+                                upper_bound.to_string()
+                            };
                             write_sample(
                                 writer,
                                 name,
                                 Some("_bucket"),
                                 m,
-                                Some((BUCKET_LABEL, &upper_bound.to_string())),
+                                Some((BUCKET_LABEL, &upper_bound)),
                                 b.get_cumulative_count() as f64,
                             )?;
-                            if upper_bound.is_sign_positive() && upper_bound.is_infinite() {
-                                inf_seen = true;
-                            }
+                            inf_seen |= is_inf;
                         }
                         if !inf_seen {
                             write_sample(
@@ -255,7 +266,7 @@ fn find_first_occurence(v: &str, include_double_quote: bool) -> Option<usize> {
 ///
 /// Implementation adapted from
 /// https://lise-henry.github.io/articles/optimising_strings.html
-fn escape_string(v: &str, include_double_quote: bool) -> Cow<'_, str> {
+pub(super) fn escape_string(v: &str, include_double_quote: bool) -> Cow<'_, str> {
     let first_occurence = find_first_occurence(v, include_double_quote);
 
     if let Some(first) = first_occurence {
@@ -286,7 +297,7 @@ fn escape_string(v: &str, include_double_quote: bool) -> Cow<'_, str> {
     }
 }
 
-trait WriteUtf8 {
+pub(super) trait WriteUtf8 {
     fn write_all(&mut self, text: &str) -> io::Result<()>;
 }
 
@@ -298,7 +309,7 @@ impl<W: Write> WriteUtf8 for W {
 
 /// Coherence forbids to impl `WriteUtf8` directly on `String`, need this
 /// wrapper as a work-around.
-struct StringBuf<'a>(&'a mut String);
+pub(super) struct StringBuf<'a>(pub(super) &'a mut String);
 
 impl WriteUtf8 for StringBuf<'_> {
     fn write_all(&mut self, text: &str) -> io::Result<()> {
@@ -431,7 +442,7 @@ test_histogram_count{a="1"} 1
 
         let mut writer = Vec::<u8>::new();
         let encoder = TextEncoder::new();
-        let res = encoder.encode(&vec![metric_family], &mut writer);
+        let res = encoder.encode(&[metric_family], &mut writer);
         assert!(res.is_ok());
 
         let ans = r##"# HELP test_summary This is a test summary statistic
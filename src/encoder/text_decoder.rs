@@ -0,0 +1,731 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use crate::desc::{is_valid_label_name, is_valid_metric_name};
+use crate::errors::{Error, Result};
+use crate::histogram::BUCKET_LABEL;
+use crate::proto::{self, MetricFamily, MetricType};
+
+use super::Decoder;
+
+const QUANTILE: &str = "quantile";
+
+/// An implementation of a [`Decoder`] that parses the text format produced by
+/// [`TextEncoder`](super::TextEncoder) back into [`MetricFamily`] proto messages.
+///
+/// See [`parse_text`] for the details of the parsing rules.
+#[derive(Debug, Default)]
+pub struct TextDecoder;
+
+impl TextDecoder {
+    /// Create a new text decoder.
+    pub fn new() -> TextDecoder {
+        TextDecoder
+    }
+}
+
+impl Decoder for TextDecoder {
+    fn decode<R: Read>(&self, reader: &mut R) -> Result<Vec<MetricFamily>> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        parse_text(&text)
+    }
+}
+
+/// `parse_text` parses metrics in the Prometheus text exposition format into
+/// [`MetricFamily`] proto messages, in the order their families first appear.
+///
+/// Samples without a preceding `# TYPE` line are treated as untyped. The
+/// `_bucket`, `_sum` and `_count` samples of a histogram and the `quantile`,
+/// `_sum` and `_count` samples of a summary are grouped into a single metric
+/// per label set. Families that end up without any sample are dropped.
+pub fn parse_text(text: &str) -> Result<Vec<MetricFamily>> {
+    let mut parser = TextParser::default();
+    for (i, line) in text.lines().enumerate() {
+        parser.parse_line(line).map_err(|msg| {
+            Error::Msg(format!(
+                "text format parsing error in line {}: {}",
+                i + 1,
+                msg
+            ))
+        })?;
+    }
+    Ok(parser.finish())
+}
+
+type Labels = Vec<(String, String)>;
+
+/// The samples of a single metric, i.e. a single label set, of a family.
+#[derive(Debug, Default)]
+struct ParsedMetric {
+    labels: Labels,
+    timestamp_ms: Option<i64>,
+    value: f64,
+    buckets: Vec<(f64, u64)>,
+    quantiles: Vec<(f64, f64)>,
+    sample_sum: f64,
+    sample_count: u64,
+}
+
+#[derive(Debug)]
+struct ParsedFamily {
+    name: String,
+    help: Option<String>,
+    metric_type: Option<MetricType>,
+    metrics: Vec<ParsedMetric>,
+    /// Indexes into `metrics` by label set, used to group the samples of
+    /// histograms and summaries.
+    metrics_by_labels: HashMap<Labels, usize>,
+}
+
+impl ParsedFamily {
+    fn new(name: &str) -> ParsedFamily {
+        ParsedFamily {
+            name: name.to_owned(),
+            help: None,
+            metric_type: None,
+            metrics: Vec::new(),
+            metrics_by_labels: HashMap::new(),
+        }
+    }
+
+    fn metric_type(&self) -> MetricType {
+        self.metric_type.unwrap_or(MetricType::UNTYPED)
+    }
+
+    fn grouped_metric(&mut self, labels: Labels) -> &mut ParsedMetric {
+        let metrics = &mut self.metrics;
+        let index = *self
+            .metrics_by_labels
+            .entry(labels)
+            .or_insert_with_key(|labels| {
+                metrics.push(ParsedMetric {
+                    labels: labels.clone(),
+                    ..Default::default()
+                });
+                metrics.len() - 1
+            });
+        &mut self.metrics[index]
+    }
+
+    fn into_proto(self) -> MetricFamily {
+        let metric_type = self.metric_type();
+        let metrics = self
+            .metrics
+            .into_iter()
+            .map(|pm| pm.into_proto(metric_type))
+            .collect();
+
+        let mut mf = MetricFamily::default();
+        mf.set_name(self.name);
+        if let Some(help) = self.help {
+            mf.set_help(help);
+        }
+        mf.set_field_type(metric_type);
+        mf.set_metric(from_vec!(metrics));
+        mf
+    }
+}
+
+impl ParsedMetric {
+    #[allow(deprecated)]
+    fn into_proto(self, metric_type: MetricType) -> proto::Metric {
+        let mut m = proto::Metric::default();
+        let label_pairs = self
+            .labels
+            .into_iter()
+            .map(|(name, value)| {
+                let mut lp = proto::LabelPair::default();
+                lp.set_name(name);
+                lp.set_value(value);
+                lp
+            })
+            .collect();
+        m.set_label(from_vec!(label_pairs));
+        if let Some(timestamp_ms) = self.timestamp_ms {
+            m.set_timestamp_ms(timestamp_ms);
+        }
+
+        match metric_type {
+            MetricType::COUNTER => {
+                let mut counter = proto::Counter::default();
+                counter.set_value(self.value);
+                m.set_counter(counter);
+            }
+            MetricType::GAUGE | MetricType::INFO | MetricType::STATESET => {
+                let mut gauge = proto::Gauge::default();
+                gauge.set_value(self.value);
+                m.set_gauge(gauge);
+            }
+            MetricType::UNTYPED => {
+                let mut untyped = proto::Untyped::default();
+                untyped.set_value(self.value);
+                m.set_untyped(untyped);
+            }
+            MetricType::HISTOGRAM | MetricType::GAUGE_HISTOGRAM => {
+                let buckets = self
+                    .buckets
+                    .into_iter()
+                    .map(|(upper_bound, cumulative_count)| {
+                        let mut b = proto::Bucket::default();
+                        b.set_upper_bound(upper_bound);
+                        b.set_cumulative_count(cumulative_count);
+                        b
+                    })
+                    .collect();
+                let mut h = proto::Histogram::default();
+                h.set_bucket(from_vec!(buckets));
+                h.set_sample_sum(self.sample_sum);
+                h.set_sample_count(self.sample_count);
+                m.set_histogram(h);
+            }
+            MetricType::SUMMARY => {
+                let quantiles = self
+                    .quantiles
+                    .into_iter()
+                    .map(|(quantile, value)| {
+                        let mut q = proto::Quantile::default();
+                        q.set_quantile(quantile);
+                        q.set_value(value);
+                        q
+                    })
+                    .collect();
+                let mut s = proto::Summary::default();
+                s.set_quantile(from_vec!(quantiles));
+                s.set_sample_sum(self.sample_sum);
+                s.set_sample_count(self.sample_count);
+                m.set_summary(s);
+            }
+        }
+
+        m
+    }
+}
+
+/// Which part of a metric a sample line contributes to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SampleKind {
+    Value,
+    Bucket,
+    Sum,
+    Count,
+}
+
+#[derive(Debug, Default)]
+struct TextParser {
+    families: Vec<ParsedFamily>,
+    families_by_name: HashMap<String, usize>,
+}
+
+impl TextParser {
+    fn parse_line(&mut self, line: &str) -> std::result::Result<(), String> {
+        let line = line.trim_matches(is_blank);
+        if line.is_empty() {
+            return Ok(());
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            return self.parse_comment(comment);
+        }
+        self.parse_sample(line)
+    }
+
+    fn family_mut(&mut self, name: &str) -> &mut ParsedFamily {
+        let families = &mut self.families;
+        let index = *self
+            .families_by_name
+            .entry(name.to_owned())
+            .or_insert_with(|| {
+                families.push(ParsedFamily::new(name));
+                families.len() - 1
+            });
+        &mut self.families[index]
+    }
+
+    /// Parse `# HELP` and `# TYPE` lines. Any other comment is ignored.
+    fn parse_comment(&mut self, comment: &str) -> std::result::Result<(), String> {
+        let mut cursor = Cursor::new(comment);
+        cursor.skip_blanks();
+        let keyword = cursor.token();
+        if keyword != "HELP" && keyword != "TYPE" {
+            return Ok(());
+        }
+        cursor.skip_blanks();
+        let name = cursor.token();
+        if !is_valid_metric_name(name) {
+            return Err(format!("invalid metric name {:?}", name));
+        }
+        // Exactly one separator precedes the docstring, which may itself
+        // start with blanks.
+        cursor.skip_one_blank();
+        let rest = cursor.rest();
+
+        let family = self.family_mut(name);
+        if keyword == "HELP" {
+            if family.help.is_some() {
+                return Err(format!("second HELP line for metric name {:?}", name));
+            }
+            family.help = Some(unescape(rest, false)?);
+            return Ok(());
+        }
+
+        if family.metric_type.is_some() {
+            return Err(format!("second TYPE line for metric name {:?}", name));
+        }
+        if !family.metrics.is_empty() {
+            return Err(format!(
+                "TYPE line for metric name {:?} must come before its samples",
+                name
+            ));
+        }
+        family.metric_type = Some(match rest.trim_matches(is_blank) {
+            "counter" => MetricType::COUNTER,
+            "gauge" => MetricType::GAUGE,
+            "histogram" => MetricType::HISTOGRAM,
+            "summary" => MetricType::SUMMARY,
+            "untyped" => MetricType::UNTYPED,
+            other => return Err(format!("unknown metric type {:?}", other)),
+        });
+        Ok(())
+    }
+
+    /// Resolve the family a sample named `name` belongs to, taking the
+    /// `_bucket`, `_sum` and `_count` suffixes of histograms and summaries into
+    /// account. Unknown names start a new untyped family.
+    fn resolve_family(&mut self, name: &str) -> (usize, SampleKind) {
+        if let Some(&index) = self.families_by_name.get(name) {
+            return (index, SampleKind::Value);
+        }
+        let suffixes = [
+            ("_bucket", SampleKind::Bucket),
+            ("_sum", SampleKind::Sum),
+            ("_count", SampleKind::Count),
+        ];
+        for (suffix, kind) in suffixes {
+            let family_name = match name.strip_suffix(suffix) {
+                Some(family_name) => family_name,
+                None => continue,
+            };
+            if let Some(&index) = self.families_by_name.get(family_name) {
+                match (self.families[index].metric_type(), kind) {
+                    (MetricType::HISTOGRAM, _)
+                    | (MetricType::SUMMARY, SampleKind::Sum)
+                    | (MetricType::SUMMARY, SampleKind::Count) => return (index, kind),
+                    _ => {}
+                }
+            }
+        }
+        self.family_mut(name);
+        (self.families.len() - 1, SampleKind::Value)
+    }
+
+    fn parse_sample(&mut self, line: &str) -> std::result::Result<(), String> {
+        let mut cursor = Cursor::new(line);
+        let name = cursor.metric_name();
+        if !is_valid_metric_name(name) {
+            return Err(format!("invalid metric name in sample line {:?}", line));
+        }
+        cursor.skip_blanks();
+        let mut labels = if cursor.peek() == Some(b'{') {
+            cursor.advance(1);
+            cursor.label_pairs()?
+        } else {
+            Vec::new()
+        };
+        cursor.skip_blanks();
+        let value = parse_float(cursor.token())?;
+        cursor.skip_blanks();
+        let timestamp_ms = match cursor.token() {
+            "" => None,
+            ts => Some(
+                ts.parse::<i64>()
+                    .map_err(|_| format!("invalid timestamp {:?}", ts))?,
+            ),
+        };
+        cursor.skip_blanks();
+        if !cursor.rest().is_empty() {
+            return Err(format!("unexpected trailing content {:?}", cursor.rest()));
+        }
+
+        let (index, kind) = self.resolve_family(name);
+        let family = &mut self.families[index];
+        let metric_type = family.metric_type();
+        let metric = match (metric_type, kind) {
+            (MetricType::HISTOGRAM, SampleKind::Bucket) => {
+                let le = take_label(&mut labels, BUCKET_LABEL).ok_or_else(|| {
+                    format!("missing {:?} label in bucket {:?}", BUCKET_LABEL, name)
+                })?;
+                let upper_bound = parse_float(&le)?;
+                let metric = family.grouped_metric(labels);
+                metric.buckets.push((upper_bound, value as u64));
+                metric
+            }
+            (MetricType::HISTOGRAM, SampleKind::Value) => {
+                return Err(format!(
+                    "sample {:?} of histogram needs a _bucket, _sum or _count suffix",
+                    name
+                ));
+            }
+            (MetricType::SUMMARY, SampleKind::Value) => {
+                let quantile = take_label(&mut labels, QUANTILE)
+                    .ok_or_else(|| format!("missing {:?} label in summary {:?}", QUANTILE, name))?;
+                let quantile = parse_float(&quantile)?;
+                let metric = family.grouped_metric(labels);
+                metric.quantiles.push((quantile, value));
+                metric
+            }
+            (MetricType::HISTOGRAM, SampleKind::Sum) | (MetricType::SUMMARY, SampleKind::Sum) => {
+                let metric = family.grouped_metric(labels);
+                metric.sample_sum = value;
+                metric
+            }
+            (MetricType::HISTOGRAM, SampleKind::Count)
+            | (MetricType::SUMMARY, SampleKind::Count) => {
+                let metric = family.grouped_metric(labels);
+                metric.sample_count = value as u64;
+                metric
+            }
+            _ => {
+                family.metrics.push(ParsedMetric {
+                    labels,
+                    value,
+                    ..Default::default()
+                });
+                family.metrics.last_mut().unwrap()
+            }
+        };
+        if timestamp_ms.is_some() {
+            metric.timestamp_ms = timestamp_ms;
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Vec<MetricFamily> {
+        self.families
+            .into_iter()
+            .filter(|family| !family.metrics.is_empty())
+            .map(ParsedFamily::into_proto)
+            .collect()
+    }
+}
+
+/// A minimal cursor over a single line of the text format.
+struct Cursor<'a> {
+    line: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(line: &'a str) -> Cursor<'a> {
+        Cursor { line, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.line.as_bytes().get(self.pos).copied()
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.pos += n;
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.pos..]
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.peek(), Some(b' ') | Some(b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_one_blank(&mut self) {
+        if matches!(self.peek(), Some(b' ') | Some(b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume everything up to the next blank.
+    fn token(&mut self) -> &'a str {
+        let start = self.pos;
+        while !matches!(self.peek(), None | Some(b' ') | Some(b'\t')) {
+            self.pos += 1;
+        }
+        &self.line[start..self.pos]
+    }
+
+    /// Consume a metric name, which ends at a blank or at `{`.
+    fn metric_name(&mut self) -> &'a str {
+        let start = self.pos;
+        while !matches!(self.peek(), None | Some(b' ') | Some(b'\t') | Some(b'{')) {
+            self.pos += 1;
+        }
+        &self.line[start..self.pos]
+    }
+
+    /// Consume the label pairs following an opening `{` up to and including
+    /// the closing `}`.
+    fn label_pairs(&mut self) -> std::result::Result<Labels, String> {
+        let mut labels: Labels = Vec::new();
+        loop {
+            self.skip_blanks();
+            if self.peek() == Some(b'}') {
+                self.advance(1);
+                return Ok(labels);
+            }
+
+            let start = self.pos;
+            while !matches!(self.peek(), None | Some(b' ') | Some(b'\t') | Some(b'=')) {
+                self.pos += 1;
+            }
+            let name = &self.line[start..self.pos];
+            if !is_valid_label_name(name) {
+                return Err(format!("invalid label name {:?}", name));
+            }
+            if labels.iter().any(|(n, _)| n == name) {
+                return Err(format!("duplicate label name {:?}", name));
+            }
+
+            self.skip_blanks();
+            if self.peek() != Some(b'=') {
+                return Err(format!("expected '=' after label name {:?}", name));
+            }
+            self.advance(1);
+            self.skip_blanks();
+            if self.peek() != Some(b'"') {
+                return Err(format!("expected '\"' to open value of label {:?}", name));
+            }
+            self.advance(1);
+
+            let start = self.pos;
+            loop {
+                match self.peek() {
+                    None => return Err(format!("unterminated value of label {:?}", name)),
+                    Some(b'\\') => self.advance(2),
+                    Some(b'"') => break,
+                    Some(_) => self.advance(1),
+                }
+            }
+            let value = unescape(&self.line[start..self.pos], true)?;
+            self.advance(1);
+            labels.push((name.to_owned(), value));
+
+            self.skip_blanks();
+            match self.peek() {
+                Some(b',') => self.advance(1),
+                Some(b'}') => {}
+                _ => return Err(format!("expected ',' or '}}' after label {:?}", name)),
+            }
+        }
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+/// `unescape` reverses the escaping of [`escape_string`](super::text::escape_string),
+/// i.e. it replaces `\\` by `\`, `\n` by a new line character and, if
+/// `include_double_quote` is true, `\"` by `"`.
+fn unescape(v: &str, include_double_quote: bool) -> std::result::Result<String, String> {
+    if !v.contains('\\') {
+        return Ok(v.to_owned());
+    }
+
+    let mut unescaped = String::with_capacity(v.len());
+    let mut chars = v.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('n') => unescaped.push('\n'),
+            Some('"') if include_double_quote => unescaped.push('"'),
+            Some(c) => return Err(format!("invalid escape sequence '\\{}'", c)),
+            None => return Err("unterminated escape sequence".to_owned()),
+        }
+    }
+    Ok(unescaped)
+}
+
+/// `parse_float` parses a sample value, accepting the `+Inf`, `-Inf` and `NaN`
+/// spellings used by the text format.
+fn parse_float(v: &str) -> std::result::Result<f64, String> {
+    match v {
+        "+Inf" | "Inf" => Ok(f64::INFINITY),
+        "-Inf" => Ok(f64::NEG_INFINITY),
+        "NaN" => Ok(f64::NAN),
+        _ => v
+            .parse::<f64>()
+            .map_err(|_| format!("invalid float value {:?}", v)),
+    }
+}
+
+fn take_label(labels: &mut Labels, name: &str) -> Option<String> {
+    let index = labels.iter().position(|(n, _)| n == name)?;
+    Some(labels.remove(index).1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::CounterVec;
+    use crate::encoder::{Encoder, TextEncoder};
+    use crate::histogram::{Histogram, HistogramOpts};
+    use crate::metrics::{Collector, Opts};
+
+    #[test]
+    fn test_parse_text() {
+        let text = r##"
+# HELP http_requests_total The total number of HTTP requests.
+# TYPE http_requests_total counter
+http_requests_total{method="post",code="200"} 1027 1395066363000
+http_requests_total{method="post",code="400"}    3 1395066363000
+
+# A normal comment.
+msdos_file_access_time_seconds{path="C:\\DIR\\FILE.TXT",error="Cannot find file:\n\"FILE.TXT\""} 1.458255915e9
+
+# HELP metric_without_timestamp_and_labels Multi-line\nhelp with a \\ backslash.
+# TYPE metric_without_timestamp_and_labels gauge
+metric_without_timestamp_and_labels 12.47
+
+# TYPE rpc_duration_seconds summary
+rpc_duration_seconds{quantile="0.5"} 4773
+rpc_duration_seconds{quantile="0.99"} 76656
+rpc_duration_seconds_sum 1.7560473e+07
+rpc_duration_seconds_count 2693
+
+# TYPE unused gauge
+"##;
+        let mfs = parse_text(text).unwrap();
+        assert_eq!(mfs.len(), 4);
+
+        let mf = &mfs[0];
+        assert_eq!(mf.get_name(), "http_requests_total");
+        assert_eq!(mf.get_help(), "The total number of HTTP requests.");
+        assert_eq!(mf.get_field_type(), MetricType::COUNTER);
+        assert_eq!(mf.get_metric().len(), 2);
+        let m = &mf.get_metric()[1];
+        assert_eq!(m.get_counter().get_value() as u64, 3);
+        assert_eq!(m.get_timestamp_ms(), 1395066363000);
+        assert_eq!(m.get_label()[0].get_name(), "method");
+        assert_eq!(m.get_label()[1].get_value(), "400");
+
+        let mf = &mfs[1];
+        assert_eq!(mf.get_name(), "msdos_file_access_time_seconds");
+        assert_eq!(mf.get_field_type(), MetricType::UNTYPED);
+        let m = &mf.get_metric()[0];
+        assert_eq!(m.get_label()[0].get_value(), r"C:\DIR\FILE.TXT");
+        assert_eq!(
+            m.get_label()[1].get_value(),
+            "Cannot find file:\n\"FILE.TXT\""
+        );
+
+        let mf = &mfs[2];
+        assert_eq!(mf.get_help(), "Multi-line\nhelp with a \\ backslash.");
+        assert!((mf.get_metric()[0].get_gauge().get_value() - 12.47).abs() < f64::EPSILON);
+
+        let mf = &mfs[3];
+        assert_eq!(mf.get_field_type(), MetricType::SUMMARY);
+        assert_eq!(mf.get_metric().len(), 1);
+        let s = mf.get_metric()[0].get_summary();
+        assert_eq!(s.get_quantile().len(), 2);
+        assert!((s.get_quantile()[1].get_quantile() - 0.99).abs() < f64::EPSILON);
+        assert!((s.get_sample_sum() - 1.7560473e+07).abs() < f64::EPSILON);
+        assert_eq!(s.get_sample_count(), 2693);
+    }
+
+    #[test]
+    fn test_parse_text_histogram() {
+        let text = r##"
+# TYPE http_request_duration_seconds histogram
+http_request_duration_seconds_bucket{le="0.05",code="200"} 24054
+http_request_duration_seconds_bucket{le="0.05",code="500"} 1
+http_request_duration_seconds_bucket{code="200",le="+Inf"} 144320
+http_request_duration_seconds_bucket{code="500",le="+Inf"} 2
+http_request_duration_seconds_sum{code="200"} 53423
+http_request_duration_seconds_count{code="200"} 144320
+http_request_duration_seconds_sum{code="500"} 0.5
+http_request_duration_seconds_count{code="500"} 2
+"##;
+        let mfs = parse_text(text).unwrap();
+        assert_eq!(mfs.len(), 1);
+        let mf = &mfs[0];
+        assert_eq!(mf.get_field_type(), MetricType::HISTOGRAM);
+        assert_eq!(mf.get_metric().len(), 2);
+
+        let m = &mf.get_metric()[0];
+        assert_eq!(m.get_label().len(), 1);
+        assert_eq!(m.get_label()[0].get_value(), "200");
+        let h = m.get_histogram();
+        assert_eq!(h.get_bucket().len(), 2);
+        assert_eq!(h.get_bucket()[0].get_cumulative_count(), 24054);
+        assert!(h.get_bucket()[1].get_upper_bound().is_infinite());
+        assert_eq!(h.get_sample_count(), 144320);
+
+        let h = mf.get_metric()[1].get_histogram();
+        assert!((h.get_sample_sum() - 0.5).abs() < f64::EPSILON);
+        assert_eq!(h.get_sample_count(), 2);
+    }
+
+    #[test]
+    fn test_parse_text_errors() {
+        let cases = [
+            "metric{label=\"value} 1",
+            "metric{label=\"\\t\"} 1",
+            "metric{0label=\"value\"} 1",
+            "metric{a=\"1\",a=\"2\"} 1",
+            "metric{a=\"1\" 1",
+            "metric one",
+            "metric 1 abc",
+            "metric 1 2 3",
+            "0metric 1",
+            "# TYPE metric counter\n# TYPE metric gauge",
+            "# HELP metric one\n# HELP metric two",
+            "metric 1\n# TYPE metric counter",
+            "# TYPE metric foo",
+            "# TYPE metric histogram\nmetric 1",
+            "# TYPE metric histogram\nmetric_bucket 1",
+            "# TYPE metric summary\nmetric 1",
+        ];
+        for case in &cases {
+            assert!(parse_text(case).is_err(), "{:?} should not parse", case);
+        }
+    }
+
+    #[test]
+    fn test_text_decoder_round_trip() {
+        let cv = CounterVec::new(
+            Opts::new("test_counter", "test \\ help\nwith newline"),
+            &["label"],
+        )
+        .unwrap();
+        cv.with_label_values(&["a \"quoted\"\nvalue"]).inc_by(3.0);
+        cv.with_label_values(&["b"]).inc();
+
+        let opts = HistogramOpts::new("test_histogram", "test help").const_label("a", "1");
+        let histogram = Histogram::with_opts(opts).unwrap();
+        histogram.observe(0.25);
+        histogram.observe(20.0);
+
+        let mut mfs = cv.collect();
+        mfs.extend(histogram.collect());
+
+        let encoder = TextEncoder::new();
+        let mut text = Vec::new();
+        encoder.encode(&mfs, &mut text).unwrap();
+
+        let decoded = TextDecoder::new().decode(&mut text.as_slice()).unwrap();
+        assert_eq!(decoded.len(), 2);
+
+        let mut text_again = Vec::new();
+        encoder.encode(&decoded, &mut text_again).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&text).unwrap(),
+            std::str::from_utf8(&text_again).unwrap()
+        );
+    }
+}
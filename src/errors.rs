@@ -1,7 +1,11 @@
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
+use std::fmt;
+
 use thiserror::Error;
 
+use crate::proto::MetricFamily;
+
 /// The error types for prometheus.
 #[derive(Debug, Error)]
 pub enum Error {
@@ -28,5 +32,53 @@ pub enum Error {
     Protobuf(#[from] protobuf::error::ProtobufError),
 }
 
+/// The errors found by [`Registry::try_gather`](crate::Registry::try_gather).
+#[derive(Debug)]
+pub struct GatherErrors {
+    errors: Vec<Error>,
+    metric_families: Vec<MetricFamily>,
+}
+
+impl GatherErrors {
+    pub(crate) fn new(errors: Vec<Error>, metric_families: Vec<MetricFamily>) -> GatherErrors {
+        GatherErrors {
+            errors,
+            metric_families,
+        }
+    }
+
+    /// Return the errors found while gathering.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// Return the metric families gathered without the offending metrics.
+    pub fn metric_families(&self) -> &[MetricFamily] {
+        &self.metric_families
+    }
+
+    /// Consume `self`, returning the metric families gathered without the
+    /// offending metrics.
+    pub fn into_metric_families(self) -> Vec<MetricFamily> {
+        self.metric_families
+    }
+}
+
+impl fmt::Display for GatherErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} error(s) occurred during gathering:",
+            self.errors.len()
+        )?;
+        for e in &self.errors {
+            write!(f, "\n* {}", e)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GatherErrors {}
+
 /// A specialized Result type for prometheus.
 pub type Result<T> = std::result::Result<T, Error>;
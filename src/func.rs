@@ -0,0 +1,538 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::desc::Desc;
+use crate::errors::Result;
+use crate::metrics::{Collector, Metric, Opts};
+use crate::proto::{self, LabelPair, MetricFamily};
+use crate::value::{make_label_pairs, timestamp_millis, ValueType};
+
+/// A closure returning a value, optionally measured at a known time.
+type ValueFn = dyn Fn() -> (f64, Option<SystemTime>) + Send + Sync;
+/// A closure returning the label values and value of each child, optionally
+/// measured at a known time.
+type ValuesFn = dyn Fn() -> Vec<(Vec<String>, f64, Option<SystemTime>)> + Send + Sync;
+
+/// The shared implementation of [`GaugeFunc`] and [`CounterFunc`].
+#[derive(Clone)]
+struct ValueFunc {
+    desc: Desc,
+    val_type: ValueType,
+    label_pairs: Vec<LabelPair>,
+    f: Arc<ValueFn>,
+}
+
+impl ValueFunc {
+    fn new(opts: &Opts, val_type: ValueType, f: Arc<ValueFn>) -> Result<ValueFunc> {
+        let desc = opts.describe_as(val_type.metric_type())?;
+        let label_pairs = make_label_pairs(&desc, &[])?;
+        Ok(ValueFunc {
+            desc,
+            val_type,
+            label_pairs,
+            f,
+        })
+    }
+
+    fn get(&self) -> f64 {
+        (self.f)().0
+    }
+
+    fn metric(&self) -> proto::Metric {
+        let (v, timestamp) = (self.f)();
+        make_metric(self.val_type, self.label_pairs.clone(), v, timestamp)
+    }
+
+    fn collect(&self) -> MetricFamily {
+        make_family(&self.desc, self.val_type, vec![self.metric()])
+    }
+}
+
+/// The shared implementation of [`GaugeFuncVec`] and [`CounterFuncVec`].
+#[derive(Clone)]
+struct ValueFuncVec {
+    desc: Desc,
+    val_type: ValueType,
+    f: Arc<ValuesFn>,
+}
+
+impl ValueFuncVec {
+    fn new(
+        opts: Opts,
+        label_names: &[&str],
+        val_type: ValueType,
+        f: Arc<ValuesFn>,
+    ) -> Result<ValueFuncVec> {
+        let variable_names = label_names.iter().map(|s| (*s).to_owned()).collect();
+        let desc = opts
+            .variable_labels(variable_names)
+            .describe_as(val_type.metric_type())?;
+        Ok(ValueFuncVec { desc, val_type, f })
+    }
+
+    fn child_metric(
+        &self,
+        vals: &[String],
+        v: f64,
+        timestamp: Option<SystemTime>,
+    ) -> Result<proto::Metric> {
+        let vals: Vec<&str> = vals.iter().map(String::as_str).collect();
+        let label_pairs = make_label_pairs(&self.desc, &vals)?;
+        Ok(make_metric(self.val_type, label_pairs, v, timestamp))
+    }
+
+    /// `collect` skips the label sets with a wrong number of values.
+    fn collect(&self) -> MetricFamily {
+        let metrics = (self.f)()
+            .into_iter()
+            .filter_map(|(vals, v, timestamp)| self.child_metric(&vals, v, timestamp).ok())
+            .collect();
+        make_family(&self.desc, self.val_type, metrics)
+    }
+
+    /// `try_collect` fails on the first label set with a wrong number of
+    /// values.
+    fn try_collect(&self) -> Result<MetricFamily> {
+        let metrics = (self.f)()
+            .into_iter()
+            .map(|(vals, v, timestamp)| self.child_metric(&vals, v, timestamp))
+            .collect::<Result<_>>()?;
+        Ok(make_family(&self.desc, self.val_type, metrics))
+    }
+}
+
+fn make_metric(
+    val_type: ValueType,
+    label_pairs: Vec<LabelPair>,
+    v: f64,
+    timestamp: Option<SystemTime>,
+) -> proto::Metric {
+    let mut m = proto::Metric::default();
+    m.set_label(from_vec!(label_pairs));
+    if let Some(timestamp) = timestamp {
+        m.set_timestamp_ms(timestamp_millis(timestamp));
+    }
+    match val_type {
+        ValueType::Counter => {
+            let mut counter = proto::Counter::default();
+            counter.set_value(v);
+            m.set_counter(counter);
+        }
+        ValueType::Gauge => {
+            let mut gauge = proto::Gauge::default();
+            gauge.set_value(v);
+            m.set_gauge(gauge);
+        }
+    }
+    m
+}
+
+fn make_family(desc: &Desc, val_type: ValueType, metrics: Vec<proto::Metric>) -> MetricFamily {
+    let mut mf = MetricFamily::default();
+    mf.set_name(desc.fq_name.clone());
+    mf.set_help(desc.help.clone());
+    if !desc.unit.is_empty() {
+        mf.set_unit(desc.unit.clone());
+    }
+    mf.set_field_type(val_type.metric_type());
+    mf.set_metric(from_vec!(metrics));
+    mf
+}
+
+/// A [`Collector`] exporting a gauge whose value is obtained by calling a
+/// closure at collect time, e.g. the length of a queue owned elsewhere.
+///
+/// The closure is called on every gather, so it should be cheap and must
+/// not block.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use prometheus::GaugeFunc;
+///
+/// let connections = Arc::new(AtomicUsize::new(0));
+/// let c = Arc::clone(&connections);
+/// let gauge = GaugeFunc::new("open_connections", "Number of open connections.", move || {
+///     c.load(Ordering::Relaxed) as f64
+/// })
+/// .unwrap();
+/// connections.store(3, Ordering::Relaxed);
+/// assert_eq!(gauge.get() as u64, 3);
+/// ```
+#[derive(Clone)]
+pub struct GaugeFunc {
+    v: ValueFunc,
+}
+
+impl GaugeFunc {
+    /// Create a [`GaugeFunc`] with the `name` and `help` arguments, reporting
+    /// the values returned by `f`.
+    pub fn new<S1, S2, F>(name: S1, help: S2, f: F) -> Result<GaugeFunc>
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+        F: Fn() -> f64 + Send + Sync + 'static,
+    {
+        GaugeFunc::with_opts(Opts::new(name, help), f)
+    }
+
+    /// Create a [`GaugeFunc`] with the `opts` options, reporting the values
+    /// returned by `f`.
+    pub fn with_opts<F>(opts: Opts, f: F) -> Result<GaugeFunc>
+    where
+        F: Fn() -> f64 + Send + Sync + 'static,
+    {
+        let v = ValueFunc::new(&opts, ValueType::Gauge, Arc::new(move || (f(), None)))?;
+        Ok(GaugeFunc { v })
+    }
+
+    /// Create a [`GaugeFunc`] with the `opts` options, reporting the values
+    /// returned by `f` along with the time they were measured at, e.g. when
+    /// relaying values sampled elsewhere.
+    pub fn with_opts_timestamped<F>(opts: Opts, f: F) -> Result<GaugeFunc>
+    where
+        F: Fn() -> (f64, SystemTime) + Send + Sync + 'static,
+    {
+        let f = move || {
+            let (v, timestamp) = f();
+            (v, Some(timestamp))
+        };
+        let v = ValueFunc::new(&opts, ValueType::Gauge, Arc::new(f))?;
+        Ok(GaugeFunc { v })
+    }
+
+    /// Return the current value by calling the closure.
+    pub fn get(&self) -> f64 {
+        self.v.get()
+    }
+}
+
+impl Collector for GaugeFunc {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.v.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        vec![self.v.collect()]
+    }
+}
+
+impl Metric for GaugeFunc {
+    fn metric(&self) -> proto::Metric {
+        self.v.metric()
+    }
+}
+
+impl std::fmt::Debug for GaugeFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GaugeFunc {{ {} }}", self.v.desc.fq_name)
+    }
+}
+
+/// A [`Collector`] exporting a counter whose value is obtained by calling a
+/// closure at collect time, e.g. a total maintained by another library.
+///
+/// The closure must return monotonically increasing values. It is called on
+/// every gather, so it should be cheap and must not block.
+#[derive(Clone)]
+pub struct CounterFunc {
+    v: ValueFunc,
+}
+
+impl CounterFunc {
+    /// Create a [`CounterFunc`] with the `name` and `help` arguments,
+    /// reporting the values returned by `f`.
+    pub fn new<S1, S2, F>(name: S1, help: S2, f: F) -> Result<CounterFunc>
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+        F: Fn() -> f64 + Send + Sync + 'static,
+    {
+        CounterFunc::with_opts(Opts::new(name, help), f)
+    }
+
+    /// Create a [`CounterFunc`] with the `opts` options, reporting the values
+    /// returned by `f`.
+    pub fn with_opts<F>(opts: Opts, f: F) -> Result<CounterFunc>
+    where
+        F: Fn() -> f64 + Send + Sync + 'static,
+    {
+        let v = ValueFunc::new(&opts, ValueType::Counter, Arc::new(move || (f(), None)))?;
+        Ok(CounterFunc { v })
+    }
+
+    /// Create a [`CounterFunc`] with the `opts` options, reporting the values
+    /// returned by `f` along with the time they were measured at, e.g. when
+    /// relaying values sampled elsewhere.
+    pub fn with_opts_timestamped<F>(opts: Opts, f: F) -> Result<CounterFunc>
+    where
+        F: Fn() -> (f64, SystemTime) + Send + Sync + 'static,
+    {
+        let f = move || {
+            let (v, timestamp) = f();
+            (v, Some(timestamp))
+        };
+        let v = ValueFunc::new(&opts, ValueType::Counter, Arc::new(f))?;
+        Ok(CounterFunc { v })
+    }
+
+    /// Return the current value by calling the closure.
+    pub fn get(&self) -> f64 {
+        self.v.get()
+    }
+}
+
+impl Collector for CounterFunc {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.v.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        vec![self.v.collect()]
+    }
+}
+
+impl Metric for CounterFunc {
+    fn metric(&self) -> proto::Metric {
+        self.v.metric()
+    }
+}
+
+impl std::fmt::Debug for CounterFunc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CounterFunc {{ {} }}", self.v.desc.fq_name)
+    }
+}
+
+/// A [`Collector`] exporting a set of gauges that share the same [`Desc`]
+/// but differ in their variable label values, all obtained by calling a
+/// closure at collect time.
+///
+/// The closure returns the label values, in the order of the label names,
+/// along with the value of each gauge. Entries with a wrong number of label
+/// values are skipped by [`collect`](Collector::collect) and fail
+/// [`try_collect`](Collector::try_collect), which the
+/// [`Registry`](crate::Registry) reports as a collector failure.
+///
+/// # Examples
+///
+/// ```
+/// use prometheus::core::Collector;
+/// use prometheus::{GaugeFuncVec, Opts};
+///
+/// let vec = GaugeFuncVec::new(
+///     Opts::new("queue_length", "Number of queued items."),
+///     &["queue"],
+///     || vec![(vec!["high".to_owned()], 2.0), (vec!["low".to_owned()], 5.0)],
+/// )
+/// .unwrap();
+/// assert_eq!(vec.collect()[0].get_metric().len(), 2);
+/// ```
+#[derive(Clone)]
+pub struct GaugeFuncVec {
+    v: ValueFuncVec,
+}
+
+impl GaugeFuncVec {
+    /// Create a [`GaugeFuncVec`] based on the provided [`Opts`] and
+    /// partitioned by the given label names, reporting the values returned
+    /// by `f`.
+    pub fn new<F>(opts: Opts, label_names: &[&str], f: F) -> Result<GaugeFuncVec>
+    where
+        F: Fn() -> Vec<(Vec<String>, f64)> + Send + Sync + 'static,
+    {
+        let f = move || f().into_iter().map(|(vals, v)| (vals, v, None)).collect();
+        let v = ValueFuncVec::new(opts, label_names, ValueType::Gauge, Arc::new(f))?;
+        Ok(GaugeFuncVec { v })
+    }
+
+    /// Create a [`GaugeFuncVec`] like [`GaugeFuncVec::new`], with `f` also returning the
+    /// time each value was measured at.
+    pub fn new_timestamped<F>(opts: Opts, label_names: &[&str], f: F) -> Result<GaugeFuncVec>
+    where
+        F: Fn() -> Vec<(Vec<String>, f64, SystemTime)> + Send + Sync + 'static,
+    {
+        let f = move || {
+            f().into_iter()
+                .map(|(vals, v, timestamp)| (vals, v, Some(timestamp)))
+                .collect()
+        };
+        let v = ValueFuncVec::new(opts, label_names, ValueType::Gauge, Arc::new(f))?;
+        Ok(GaugeFuncVec { v })
+    }
+}
+
+impl Collector for GaugeFuncVec {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.v.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        vec![self.v.collect()]
+    }
+    fn try_collect(&self) -> Result<Vec<MetricFamily>> {
+        Ok(vec![self.v.try_collect()?])
+    }
+}
+
+impl std::fmt::Debug for GaugeFuncVec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GaugeFuncVec {{ {} }}", self.v.desc.fq_name)
+    }
+}
+
+/// A [`Collector`] exporting a set of counters that share the same [`Desc`]
+/// but differ in their variable label values, all obtained by calling a
+/// closure at collect time. See [`GaugeFuncVec`].
+#[derive(Clone)]
+pub struct CounterFuncVec {
+    v: ValueFuncVec,
+}
+
+impl CounterFuncVec {
+    /// Create a [`CounterFuncVec`] based on the provided [`Opts`] and
+    /// partitioned by the given label names, reporting the values returned
+    /// by `f`.
+    pub fn new<F>(opts: Opts, label_names: &[&str], f: F) -> Result<CounterFuncVec>
+    where
+        F: Fn() -> Vec<(Vec<String>, f64)> + Send + Sync + 'static,
+    {
+        let f = move || f().into_iter().map(|(vals, v)| (vals, v, None)).collect();
+        let v = ValueFuncVec::new(opts, label_names, ValueType::Counter, Arc::new(f))?;
+        Ok(CounterFuncVec { v })
+    }
+
+    /// Create a [`CounterFuncVec`] like [`CounterFuncVec::new`], with `f` also returning the
+    /// time each value was measured at.
+    pub fn new_timestamped<F>(opts: Opts, label_names: &[&str], f: F) -> Result<CounterFuncVec>
+    where
+        F: Fn() -> Vec<(Vec<String>, f64, SystemTime)> + Send + Sync + 'static,
+    {
+        let f = move || {
+            f().into_iter()
+                .map(|(vals, v, timestamp)| (vals, v, Some(timestamp)))
+                .collect()
+        };
+        let v = ValueFuncVec::new(opts, label_names, ValueType::Counter, Arc::new(f))?;
+        Ok(CounterFuncVec { v })
+    }
+}
+
+impl Collector for CounterFuncVec {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.v.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        vec![self.v.collect()]
+    }
+    fn try_collect(&self) -> Result<Vec<MetricFamily>> {
+        Ok(vec![self.v.try_collect()?])
+    }
+}
+
+impl std::fmt::Debug for CounterFuncVec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CounterFuncVec {{ {} }}", self.v.desc.fq_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::*;
+    use crate::proto::MetricType;
+
+    #[test]
+    fn test_gauge_func() {
+        let value = Arc::new(AtomicU64::new(0));
+        let v = Arc::clone(&value);
+        let opts = Opts::new("test_gauge_func", "test help").const_label("a", "1");
+        let gauge = GaugeFunc::with_opts(opts, move || v.load(Ordering::Relaxed) as f64).unwrap();
+
+        value.store(42, Ordering::Relaxed);
+        let mfs = gauge.collect();
+        assert_eq!(mfs.len(), 1);
+        assert_eq!(mfs[0].get_field_type(), MetricType::GAUGE);
+        let m = &mfs[0].get_metric()[0];
+        assert_eq!(m.get_label().len(), 1);
+        assert!((m.get_gauge().get_value() - 42.0).abs() < f64::EPSILON);
+
+        value.store(7, Ordering::Relaxed);
+        assert!((gauge.get() - 7.0).abs() < f64::EPSILON);
+
+        let opts = Opts::new("test_gauge_func", "test help").variable_label("a");
+        assert!(GaugeFunc::with_opts(opts, || 0.0).is_err());
+    }
+
+    #[test]
+    fn test_counter_func() {
+        let counter = CounterFunc::new("test_counter_func", "test help", || 3.0).unwrap();
+        let mfs = counter.collect();
+        assert_eq!(mfs[0].get_field_type(), MetricType::COUNTER);
+        assert!((mfs[0].get_metric()[0].get_counter().get_value() - 3.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_func_timestamped() {
+        let timestamp = UNIX_EPOCH + Duration::from_secs(3);
+        let opts = Opts::new("test_gauge_func", "test help");
+        let gauge = GaugeFunc::with_opts_timestamped(opts, move || (1.0, timestamp)).unwrap();
+        assert!((gauge.get() - 1.0).abs() < f64::EPSILON);
+        assert_eq!(gauge.metric().get_timestamp_ms(), 3_000);
+
+        let vec = CounterFuncVec::new_timestamped(
+            Opts::new("test_counter_func_vec", "test help"),
+            &["a"],
+            move || vec![(vec!["1".to_owned()], 2.0, timestamp)],
+        )
+        .unwrap();
+        let mfs = vec.collect();
+        assert_eq!(mfs[0].get_metric()[0].get_timestamp_ms(), 3_000);
+
+        let counter = CounterFunc::new("test_counter_func", "test help", || 3.0).unwrap();
+        assert_eq!(counter.metric().get_timestamp_ms(), 0);
+    }
+
+    #[test]
+    fn test_func_vec() {
+        let vec = CounterFuncVec::new(
+            Opts::new("test_counter_func_vec", "test help"),
+            &["a", "b"],
+            || {
+                vec![
+                    (vec!["1".to_owned(), "2".to_owned()], 1.0),
+                    (vec!["3".to_owned()], 2.0),
+                    (vec!["4".to_owned(), "5".to_owned()], 3.0),
+                ]
+            },
+        )
+        .unwrap();
+
+        let mfs = vec.collect();
+        assert_eq!(mfs[0].get_field_type(), MetricType::COUNTER);
+        let metrics = mfs[0].get_metric();
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[1].get_label()[0].get_value(), "4");
+        assert_eq!(metrics[1].get_label()[1].get_value(), "5");
+        assert!((metrics[1].get_counter().get_value() - 3.0).abs() < f64::EPSILON);
+        assert!(vec.try_collect().is_err());
+
+        let vec = GaugeFuncVec::new(
+            Opts::new("test_gauge_func_vec", "test help"),
+            &["a"],
+            Vec::new,
+        )
+        .unwrap();
+        assert_eq!(vec.collect()[0].get_field_type(), MetricType::GAUGE);
+        assert!(vec.collect()[0].get_metric().is_empty());
+        assert!(vec.try_collect().unwrap()[0].get_metric().is_empty());
+    }
+}
@@ -10,7 +10,7 @@ use crate::errors::Result;
 use crate::metrics::{Collector, Metric, Opts};
 use crate::proto;
 use crate::value::{Value, ValueType};
-use crate::vec::{MetricVec, MetricVecBuilder};
+use crate::vec::{ChildLimits, MetricVec, MetricVecBuilder};
 
 /// The underlying implementation for [`Gauge`] and [`IntGauge`].
 #[derive(Debug)]
@@ -104,6 +104,10 @@ impl<P: Atomic> Metric for GenericGauge<P> {
     fn metric(&self) -> proto::Metric {
         self.v.metric()
     }
+
+    fn last_touched(&self) -> Option<u64> {
+        self.v.last_touched.get()
+    }
 }
 
 #[derive(Debug)]
@@ -154,7 +158,15 @@ impl<P: Atomic> GenericGaugeVec<P> {
     pub fn new(opts: Opts, label_names: &[&str]) -> Result<Self> {
         let variable_names = label_names.iter().map(|s| (*s).to_owned()).collect();
         let opts = opts.variable_labels(variable_names);
-        let metric_vec = MetricVec::create(proto::MetricType::GAUGE, GaugeVecBuilder::new(), opts)?;
+        let limits = ChildLimits::from_opts(&opts);
+        let desc = opts.describe_as(proto::MetricType::GAUGE)?;
+        let metric_vec = MetricVec::create_with_limits(
+            proto::MetricType::GAUGE,
+            GaugeVecBuilder::new(),
+            opts,
+            desc,
+            limits,
+        )?;
 
         Ok(metric_vec as Self)
     }
@@ -165,6 +177,7 @@ mod tests {
     use std::collections::HashMap;
 
     use super::*;
+    use crate::counter::{Counter, CounterVec};
     use crate::metrics::{Collector, Opts};
 
     #[test]
@@ -188,7 +201,7 @@ mod tests {
         assert_eq!(mfs.len(), 1);
 
         let mf = mfs.pop().unwrap();
-        let m = mf.get_metric().get(0).unwrap();
+        let m = mf.get_metric().first().unwrap();
         assert_eq!(m.get_label().len(), 2);
         assert_eq!(m.get_gauge().get_value() as u64, 42);
     }
@@ -237,4 +250,15 @@ mod tests {
         assert!(vec.remove_label_values(&["v1"]).is_err());
         assert!(vec.remove_label_values(&["v1", "v3"]).is_err());
     }
+
+    #[test]
+    fn test_gauge_unit_with_total_suffix() {
+        let opts = Opts::new("test_duration_seconds_total", "test help").unit("seconds");
+        assert!(Gauge::with_opts(opts.clone()).is_err());
+        assert!(GaugeVec::new(opts.clone(), &["l1"]).is_err());
+
+        // Only counters may end in `_total` after their unit.
+        assert!(Counter::with_opts(opts.clone()).is_ok());
+        assert!(CounterVec::new(opts, &["l1"]).is_ok());
+    }
 }
@@ -8,15 +8,15 @@ use std::sync::{
     atomic::{AtomicU64 as StdAtomicU64, Ordering},
     Arc, Mutex,
 };
-use std::time::{Duration, Instant as StdInstant};
+use std::time::{Duration, Instant as StdInstant, SystemTime};
 
-use crate::atomic64::{Atomic, AtomicF64, AtomicU64};
+use crate::atomic64::{Atomic, AtomicF64, AtomicI64, AtomicU64};
 use crate::desc::{Desc, Describer};
 use crate::errors::{Error, Result};
-use crate::metrics::{Collector, LocalMetric, Metric, Opts};
+use crate::metrics::{CardinalityPolicy, Collector, LocalMetric, Metric, Opts};
 use crate::proto;
-use crate::value::make_label_pairs;
-use crate::vec::{MetricVec, MetricVecBuilder};
+use crate::value::{make_exemplar, make_label_pairs, make_timestamp};
+use crate::vec::{ChildLimits, LabelValuesMap, LastTouched, MetricVec, MetricVecBuilder};
 
 /// The default [`Histogram`] buckets. The default buckets are
 /// tailored to broadly measure the response time (in seconds) of a
@@ -66,6 +66,13 @@ fn check_and_adjust_buckets(mut buckets: Vec<f64>) -> Result<Vec<f64>> {
     Ok(buckets)
 }
 
+/// Return the index of the first bucket whose upper bound is greater than or
+/// equal to `v`, or `None` if `v` falls into the implicit +Inf bucket.
+#[inline]
+fn find_bucket(upper_bounds: &[f64], v: f64) -> Option<usize> {
+    upper_bounds.iter().position(|f| v <= *f)
+}
+
 /// A struct that bundles the options for creating a [`Histogram`] metric. It is
 /// mandatory to set Name and Help to a non-empty string. All other fields are
 /// optional and can safely be left at their zero value.
@@ -127,6 +134,26 @@ impl HistogramOpts {
         self
     }
 
+    /// `cardinality_limit` limits the number of children of a metric vector.
+    /// See [`Opts::cardinality_limit`].
+    pub fn cardinality_limit(mut self, max_children: usize, policy: CardinalityPolicy) -> Self {
+        self.common_opts = self.common_opts.cardinality_limit(max_children, policy);
+        self
+    }
+
+    /// `ttl` sets the time after which untouched children of a metric vector
+    /// expire. See [`Opts::ttl`].
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.common_opts = self.common_opts.ttl(ttl);
+        self
+    }
+
+    /// `unit` sets the unit. See [`Opts::unit`].
+    pub fn unit<S: Into<String>>(mut self, unit: S) -> Self {
+        self.common_opts = self.common_opts.unit(unit);
+        self
+    }
+
     /// `fq_name` returns the fq_name.
     pub fn fq_name(&self) -> String {
         self.common_opts.fq_name()
@@ -162,6 +189,9 @@ struct Shard {
     sum: AtomicF64,
     count: AtomicU64,
     buckets: Vec<AtomicU64>,
+    /// The latest exemplar of each bucket, plus one trailing slot for the
+    /// implicit +Inf bucket.
+    exemplars: Vec<Mutex<Option<proto::Exemplar>>>,
 }
 
 impl Shard {
@@ -171,14 +201,27 @@ impl Shard {
             buckets.push(AtomicU64::new(0));
         }
 
+        let mut exemplars = Vec::new();
+        for _ in 0..=num_buckets {
+            exemplars.push(Mutex::new(None));
+        }
+
         Shard {
             sum: AtomicF64::new(0.0),
             count: AtomicU64::new(0),
             buckets,
+            exemplars,
         }
     }
 }
 
+/// Return the timestamp of `exemplar` as seconds and nanoseconds, for
+/// ordering exemplars by recency.
+fn exemplar_time(exemplar: &proto::Exemplar) -> (i64, i32) {
+    let timestamp = exemplar.get_timestamp();
+    (timestamp.get_seconds(), timestamp.get_nanos())
+}
+
 /// Index into an array of [`Shard`]s.
 ///
 /// Used in conjunction with [`ShardAndCount`] below.
@@ -284,11 +327,11 @@ impl ShardAndCount {
 /// A histogram supports two main execution paths:
 ///
 /// 1. `observe` which increases the overall observation counter, updates the
-/// observation sum and increases a single bucket counter.
+///    observation sum and increases a single bucket counter.
 ///
 /// 2. `proto` (aka. collecting the metric, from now on referred to as the
-/// collect operation) which snapshots the state of the histogram and exposes it
-/// as a Protobuf struct.
+///    collect operation) which snapshots the state of the histogram and exposes
+///    it as a Protobuf struct.
 ///
 /// If an observe and a collect operation interleave, the latter could be
 /// exposing a snapshot of the histogram that does not uphold all histogram
@@ -324,6 +367,8 @@ pub struct HistogramCore {
     shards: [Shard; 2],
 
     upper_bounds: Vec<f64>,
+    created: SystemTime,
+    last_touched: LastTouched,
 }
 
 impl HistogramCore {
@@ -351,6 +396,8 @@ impl HistogramCore {
             shards: [Shard::new(buckets.len()), Shard::new(buckets.len())],
 
             upper_bounds: buckets,
+            created: SystemTime::now(),
+            last_touched: LastTouched::new(opts.common_opts.ttl),
         })
     }
 
@@ -361,6 +408,17 @@ impl HistogramCore {
     // corresponding bucket count, adjust the shard's sum and finally increase
     // the shard's count.
     pub fn observe(&self, v: f64) {
+        self.observe_inner(v, None)
+    }
+
+    /// Record a given observation (f64) in the histogram together with an
+    /// exemplar, which replaces the previous exemplar of the matching bucket.
+    pub fn observe_with_exemplar(&self, v: f64, exemplar: proto::Exemplar) {
+        self.observe_inner(v, Some(exemplar))
+    }
+
+    #[inline]
+    fn observe_inner(&self, v: f64, exemplar: Option<proto::Exemplar>) {
         // The collect code path uses `self.shard_and_count` and
         // `self.shards[x].count` to ensure not to collect data from a shard
         // while observe calls are still operating on it.
@@ -372,18 +430,23 @@ impl HistogramCore {
         let shard: &Shard = &self.shards[usize::from(shard_index)];
 
         // Try find the bucket.
-        let mut iter = self
-            .upper_bounds
-            .iter()
-            .enumerate()
-            .filter(|&(_, f)| v <= *f);
-        if let Some((i, _)) = iter.next() {
-            shard.buckets[i].inc_by(1);
+        let bucket_index = match find_bucket(&self.upper_bounds, v) {
+            Some(i) => {
+                shard.buckets[i].inc_by(1);
+                i
+            }
+            None => self.upper_bounds.len(),
+        };
+
+        if let Some(exemplar) = exemplar {
+            *shard.exemplars[bucket_index].lock().expect("Lock poisoned") = Some(exemplar);
         }
 
         shard.sum.inc_by(v);
         // Use `Release` ordering to ensure all operations above stay above.
         shard.count.inc_by_with_ordering(1, Ordering::Release);
+
+        self.last_touched.touch();
     }
 
     /// Make a snapshot of the current histogram state exposed as a Protobuf
@@ -451,9 +514,22 @@ impl HistogramCore {
             let mut b = proto::Bucket::default();
             b.set_cumulative_count(cumulative_count);
             b.set_upper_bound(*upper_bound);
+            if let Some(exemplar) = Self::take_exemplar(cold_shard, hot_shard, i) {
+                b.set_exemplar(exemplar);
+            }
+            buckets.push(b);
+        }
+        // The +Inf bucket is implicit, only expose it when it has an exemplar.
+        if let Some(exemplar) = Self::take_exemplar(cold_shard, hot_shard, self.upper_bounds.len())
+        {
+            let mut b = proto::Bucket::default();
+            b.set_cumulative_count(overall_count);
+            b.set_upper_bound(f64::INFINITY);
+            b.set_exemplar(exemplar);
             buckets.push(b);
         }
         h.set_bucket(from_vec!(buckets));
+        h.set_created_timestamp(make_timestamp(self.created));
 
         // Update the hot shard.
         hot_shard.count.inc_by(overall_count);
@@ -464,6 +540,25 @@ impl HistogramCore {
         h
     }
 
+    /// Return the exemplar to expose for the `i`-th bucket, i.e. the later one
+    /// of the cold and the hot shard. The cold exemplar is carried over to the
+    /// hot shard unless the latter already received a newer one.
+    fn take_exemplar(cold_shard: &Shard, hot_shard: &Shard, i: usize) -> Option<proto::Exemplar> {
+        let cold = cold_shard.exemplars[i]
+            .lock()
+            .expect("Lock poisoned")
+            .take();
+        let mut hot = hot_shard.exemplars[i].lock().expect("Lock poisoned");
+        match (cold, hot.as_ref()) {
+            (Some(cold), Some(h)) if exemplar_time(h) >= exemplar_time(&cold) => Some(h.clone()),
+            (Some(cold), _) => {
+                *hot = Some(cold.clone());
+                Some(cold)
+            }
+            (None, h) => h.cloned(),
+        }
+    }
+
     fn sample_sum(&self) -> f64 {
         // Make sure to not overlap with any collect calls, as they might flip
         // the hot and cold shards.
@@ -516,7 +611,7 @@ impl Instant {
     }
 
     pub fn elapsed(&self) -> Duration {
-        match &*self {
+        match self {
             // We use `saturating_duration_since` to avoid panics caused by non-monotonic clocks.
             Instant::Monotonic(i) => StdInstant::now().saturating_duration_since(*i),
 
@@ -650,7 +745,7 @@ impl Drop for HistogramTimer {
 }
 
 /// A [`Metric`] counts individual observations from an event or sample stream
-/// in configurable buckets. Similar to a [`Summary`](crate::proto::Summary),
+/// in configurable buckets. Similar to a [`Summary`](crate::Summary),
 /// it also provides a sum of observations and an observation count.
 ///
 /// On the Prometheus server, quantiles can be calculated from a [`Histogram`] using
@@ -695,6 +790,22 @@ impl Histogram {
         self.core.observe(v)
     }
 
+    /// Add a single observation to the [`Histogram`] and attach an exemplar
+    /// built from `exemplar_labels`, e.g. a trace ID, to the bucket it falls
+    /// into. The exemplar replaces any previous one of that bucket.
+    ///
+    /// Returns an error and discards the observation if the exemplar labels
+    /// are invalid or longer than [`MAX_EXEMPLAR_LABEL_LENGTH`](crate::MAX_EXEMPLAR_LABEL_LENGTH).
+    pub fn observe_with_exemplar(
+        &self,
+        v: f64,
+        exemplar_labels: &HashMap<&str, &str>,
+    ) -> Result<()> {
+        let exemplar = make_exemplar(v, exemplar_labels)?;
+        self.core.observe_with_exemplar(v, exemplar);
+        Ok(())
+    }
+
     /// Return a [`HistogramTimer`] to track a duration.
     pub fn start_timer(&self) -> HistogramTimer {
         HistogramTimer::new(self.clone())
@@ -758,6 +869,10 @@ impl Metric for Histogram {
 
         m
     }
+
+    fn last_touched(&self) -> Option<u64> {
+        self.core.last_touched.get()
+    }
 }
 
 impl Collector for Histogram {
@@ -769,6 +884,9 @@ impl Collector for Histogram {
         let mut m = proto::MetricFamily::default();
         m.set_name(self.core.desc.fq_name.clone());
         m.set_help(self.core.desc.help.clone());
+        if !self.core.desc.unit.is_empty() {
+            m.set_unit(self.core.desc.unit.clone());
+        }
         m.set_field_type(proto::MetricType::HISTOGRAM);
         m.set_metric(from_vec!(vec![self.metric()]));
 
@@ -801,8 +919,15 @@ impl HistogramVec {
     pub fn new(opts: HistogramOpts, label_names: &[&str]) -> Result<HistogramVec> {
         let variable_names = label_names.iter().map(|s| (*s).to_owned()).collect();
         let opts = opts.variable_labels(variable_names);
-        let metric_vec =
-            MetricVec::create(proto::MetricType::HISTOGRAM, HistogramVecBuilder {}, opts)?;
+        let limits = ChildLimits::from_opts(&opts.common_opts);
+        let desc = opts.describe()?;
+        let metric_vec = MetricVec::create_with_limits(
+            proto::MetricType::HISTOGRAM,
+            HistogramVecBuilder {},
+            opts,
+            desc,
+            limits,
+        )?;
 
         Ok(metric_vec as HistogramVec)
     }
@@ -814,6 +939,204 @@ impl HistogramVec {
     }
 }
 
+/// A [`Metric`] tracking the current distribution of a set of values, e.g.
+/// the ages of the items in a queue or the sizes of the requests in flight.
+///
+/// Unlike a [`Histogram`], whose buckets only ever go up, a value is added
+/// with [`GaugeHistogram::inc`] and removed again with [`GaugeHistogram::dec`].
+/// The text format exposes it as a histogram, OpenMetrics with the
+/// `gaugehistogram` type.
+///
+/// # Examples
+///
+/// ```
+/// use prometheus::{GaugeHistogram, HistogramOpts};
+///
+/// let opts = HistogramOpts::new("queue_item_age_seconds", "Age of the queued items.")
+///     .buckets(vec![1.0, 10.0, 60.0]);
+/// let ages = GaugeHistogram::with_opts(opts).unwrap();
+/// ages.inc(0.5);
+/// ages.inc(30.0);
+/// ages.dec(0.5);
+/// assert_eq!(ages.get_sample_count(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct GaugeHistogram {
+    core: Arc<GaugeHistogramCore>,
+}
+
+#[derive(Debug)]
+struct GaugeHistogramCore {
+    desc: Desc,
+    label_pairs: Vec<proto::LabelPair>,
+    upper_bounds: Vec<f64>,
+    /// The non-cumulative count of each bucket, followed by the count of the
+    /// implicit +Inf bucket.
+    buckets: Vec<AtomicI64>,
+    sum: AtomicF64,
+    last_touched: LastTouched,
+}
+
+impl GaugeHistogram {
+    /// `with_opts` creates a [`GaugeHistogram`] with the `opts` options.
+    pub fn with_opts(opts: HistogramOpts) -> Result<GaugeHistogram> {
+        GaugeHistogram::with_opts_and_label_values(&opts, &[])
+    }
+
+    fn with_opts_and_label_values(
+        opts: &HistogramOpts,
+        label_values: &[&str],
+    ) -> Result<GaugeHistogram> {
+        let desc = opts.describe()?;
+
+        for name in &desc.variable_labels {
+            check_bucket_label(name)?;
+        }
+        for pair in &desc.const_label_pairs {
+            check_bucket_label(pair.get_name())?;
+        }
+
+        let label_pairs = make_label_pairs(&desc, label_values)?;
+        let upper_bounds = check_and_adjust_buckets(opts.buckets.clone())?;
+        let buckets = (0..=upper_bounds.len())
+            .map(|_| AtomicI64::new(0))
+            .collect();
+
+        Ok(GaugeHistogram {
+            core: Arc::new(GaugeHistogramCore {
+                desc,
+                label_pairs,
+                upper_bounds,
+                buckets,
+                sum: AtomicF64::new(0.0),
+                last_touched: LastTouched::new(opts.common_opts.ttl),
+            }),
+        })
+    }
+
+    /// Add the value `v` to the [`GaugeHistogram`].
+    pub fn inc(&self, v: f64) {
+        self.core.buckets[self.bucket_index(v)].inc_by(1);
+        self.core.sum.inc_by(v);
+        self.core.last_touched.touch();
+    }
+
+    /// Remove the value `v`, previously added with [`GaugeHistogram::inc`],
+    /// from the [`GaugeHistogram`].
+    pub fn dec(&self, v: f64) {
+        self.core.buckets[self.bucket_index(v)].dec_by(1);
+        self.core.sum.dec_by(v);
+        self.core.last_touched.touch();
+    }
+
+    /// Return the sum of the current values.
+    pub fn get_sample_sum(&self) -> f64 {
+        self.core.sum.get()
+    }
+
+    /// Return the number of current values.
+    pub fn get_sample_count(&self) -> u64 {
+        self.bucket_counts().sum()
+    }
+
+    fn bucket_index(&self, v: f64) -> usize {
+        find_bucket(&self.core.upper_bounds, v).unwrap_or(self.core.upper_bounds.len())
+    }
+
+    /// The non-cumulative bucket counts. A count is only negative while a
+    /// `dec` races ahead of its `inc`, hence it is clamped to 0.
+    fn bucket_counts(&self) -> impl Iterator<Item = u64> + '_ {
+        self.core.buckets.iter().map(|b| b.get().max(0) as u64)
+    }
+}
+
+impl Metric for GaugeHistogram {
+    fn metric(&self) -> proto::Metric {
+        let mut cumulative_count = 0;
+        let mut buckets = Vec::with_capacity(self.core.buckets.len());
+        let upper_bounds = self.core.upper_bounds.iter().chain(Some(&f64::INFINITY));
+        for (count, upper_bound) in self.bucket_counts().zip(upper_bounds) {
+            cumulative_count += count;
+            let mut b = proto::Bucket::default();
+            b.set_cumulative_count(cumulative_count);
+            b.set_upper_bound(*upper_bound);
+            buckets.push(b);
+        }
+        // The +Inf bucket is implicit.
+        buckets.pop();
+
+        let mut h = proto::Histogram::default();
+        h.set_sample_sum(self.core.sum.get());
+        h.set_sample_count(cumulative_count);
+        h.set_bucket(from_vec!(buckets));
+
+        let mut m = proto::Metric::default();
+        m.set_label(from_vec!(self.core.label_pairs.clone()));
+        m.set_histogram(h);
+        m
+    }
+
+    fn last_touched(&self) -> Option<u64> {
+        self.core.last_touched.get()
+    }
+}
+
+impl Collector for GaugeHistogram {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.core.desc]
+    }
+
+    fn collect(&self) -> Vec<proto::MetricFamily> {
+        let mut m = proto::MetricFamily::default();
+        m.set_name(self.core.desc.fq_name.clone());
+        m.set_help(self.core.desc.help.clone());
+        if !self.core.desc.unit.is_empty() {
+            m.set_unit(self.core.desc.unit.clone());
+        }
+        m.set_field_type(proto::MetricType::GAUGE_HISTOGRAM);
+        m.set_metric(from_vec!(vec![self.metric()]));
+
+        vec![m]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GaugeHistogramVecBuilder {}
+
+impl MetricVecBuilder for GaugeHistogramVecBuilder {
+    type M = GaugeHistogram;
+    type P = HistogramOpts;
+
+    fn build(&self, opts: &HistogramOpts, vals: &[&str]) -> Result<GaugeHistogram> {
+        GaugeHistogram::with_opts_and_label_values(opts, vals)
+    }
+}
+
+/// A [`Collector`] that bundles a set of GaugeHistograms that all share the
+/// same [`Desc`], but have different values for their variable labels.
+pub type GaugeHistogramVec = MetricVec<GaugeHistogramVecBuilder>;
+
+impl GaugeHistogramVec {
+    /// Create a new [`GaugeHistogramVec`] based on the provided
+    /// [`HistogramOpts`] and partitioned by the given label names. At least
+    /// one label name must be provided.
+    pub fn new(opts: HistogramOpts, label_names: &[&str]) -> Result<GaugeHistogramVec> {
+        let variable_names = label_names.iter().map(|s| (*s).to_owned()).collect();
+        let opts = opts.variable_labels(variable_names);
+        let limits = ChildLimits::from_opts(&opts.common_opts);
+        let desc = opts.describe()?;
+        let metric_vec = MetricVec::create_with_limits(
+            proto::MetricType::GAUGE_HISTOGRAM,
+            GaugeHistogramVecBuilder {},
+            opts,
+            desc,
+            limits,
+        )?;
+
+        Ok(metric_vec as GaugeHistogramVec)
+    }
+}
+
 /// Create `count` buckets, each `width` wide, where the lowest
 /// bucket has an upper bound of `start`. The final +Inf bucket is not counted
 /// and not included in the returned slice. The returned slice is meant to be
@@ -1000,14 +1323,7 @@ impl LocalHistogramCore {
 
     pub fn observe(&mut self, v: f64) {
         // Try find the bucket.
-        let mut iter = self
-            .histogram
-            .core
-            .upper_bounds
-            .iter()
-            .enumerate()
-            .filter(|&(_, f)| v <= *f);
-        if let Some((i, _)) = iter.next() {
+        if let Some(i) = find_bucket(&self.histogram.core.upper_bounds, v) {
             self.counts[i] += 1;
         }
 
@@ -1056,6 +1372,7 @@ impl LocalHistogramCore {
                 .count
                 .inc_by_with_ordering(self.count, Ordering::Release);
         }
+        self.histogram.core.last_touched.touch();
 
         self.clear()
     }
@@ -1157,31 +1474,30 @@ impl Drop for LocalHistogram {
 #[derive(Debug)]
 pub struct LocalHistogramVec {
     vec: HistogramVec,
-    local: HashMap<u64, LocalHistogram>,
+    local: LabelValuesMap<LocalHistogram>,
 }
 
 impl LocalHistogramVec {
     fn new(vec: HistogramVec) -> LocalHistogramVec {
-        let local = HashMap::with_capacity(vec.v.children.read().len());
+        let local = LabelValuesMap::with_capacity(vec.v.children.read().len());
         LocalHistogramVec { vec, local }
     }
 
     /// Get a [`LocalHistogram`] by label values.
     /// See more [`MetricVec::with_label_values`].
     pub fn with_label_values<'a>(&'a mut self, vals: &[&str]) -> &'a LocalHistogram {
-        let hash = self.vec.v.hash_label_values(vals).unwrap();
+        let hash = self.vec.hash_label_values(vals).unwrap();
         let vec = &self.vec;
         self.local
-            .entry(hash)
-            .or_insert_with(|| vec.with_label_values(vals).local())
+            .get_or_insert_with(hash, vals, || vec.with_label_values(vals).local())
     }
 
     /// Remove a [`LocalHistogram`] by label values.
     /// See more [`MetricVec::remove_label_values`].
     pub fn remove_label_values(&mut self, vals: &[&str]) -> Result<()> {
-        let hash = self.vec.v.hash_label_values(vals)?;
-        self.local.remove(&hash);
-        self.vec.v.delete_label_values(vals)
+        let hash = self.vec.hash_label_values(vals)?;
+        self.local.remove(hash, vals);
+        self.vec.remove_label_values(vals)
     }
 
     /// Flush the local metrics to the [`HistogramVec`] metric.
@@ -1207,12 +1523,12 @@ impl Clone for LocalHistogramVec {
 
 #[cfg(test)]
 mod tests {
-    use std::f64::{EPSILON, INFINITY};
     use std::thread;
     use std::time::Duration;
 
     use super::*;
     use crate::metrics::{Collector, Metric};
+    use crate::value::MAX_EXEMPLAR_LABEL_LENGTH;
 
     #[test]
     fn test_histogram() {
@@ -1237,7 +1553,7 @@ mod tests {
         assert_eq!(mfs.len(), 1);
 
         let mf = mfs.pop().unwrap();
-        let m = mf.get_metric().get(0).unwrap();
+        let m = mf.get_metric().first().unwrap();
         assert_eq!(m.get_label().len(), 2);
         let proto_histogram = m.get_histogram();
         assert_eq!(proto_histogram.get_sample_count(), 3);
@@ -1251,11 +1567,11 @@ mod tests {
         assert_eq!(mfs.len(), 1);
 
         let mf = mfs.pop().unwrap();
-        let m = mf.get_metric().get(0).unwrap();
+        let m = mf.get_metric().first().unwrap();
         assert_eq!(m.get_label().len(), 0);
         let proto_histogram = m.get_histogram();
         assert_eq!(proto_histogram.get_sample_count(), 0);
-        assert!((proto_histogram.get_sample_sum() - 0.0) < EPSILON);
+        assert!((proto_histogram.get_sample_sum() - 0.0).abs() < f64::EPSILON);
         assert_eq!(proto_histogram.get_bucket().len(), buckets.len())
     }
 
@@ -1287,7 +1603,7 @@ mod tests {
         let m = mf.get_metric().get(0).unwrap();
         let proto_histogram = m.get_histogram();
         assert_eq!(proto_histogram.get_sample_count(), 3);
-        assert!((proto_histogram.get_sample_sum() - 0.0) > EPSILON);
+        assert!((proto_histogram.get_sample_sum() - 0.0) > f64::EPSILON);
     }
 
     #[test]
@@ -1311,7 +1627,11 @@ mod tests {
             (vec![], true, DEFAULT_BUCKETS.len()),
             (vec![-2.0, -1.0, -0.5, 0.0, 0.5, 1.0, 2.0], true, 7),
             (vec![-2.0, -1.0, -0.5, 10.0, 0.5, 1.0, 2.0], false, 7),
-            (vec![-2.0, -1.0, -0.5, 0.0, 0.5, 1.0, INFINITY], true, 6),
+            (
+                vec![-2.0, -1.0, -0.5, 0.0, 0.5, 1.0, f64::INFINITY],
+                true,
+                6,
+            ),
         ];
 
         for (buckets, is_ok, length) in table {
@@ -1340,8 +1660,8 @@ mod tests {
         for (param1, param2, param3, is_ok, vec) in linear_table {
             let got = linear_buckets(param1, param2, param3);
             assert_eq!(got.is_ok(), is_ok);
-            if got.is_ok() {
-                assert_eq!(got.unwrap(), vec);
+            if let Ok(got) = got {
+                assert_eq!(got, vec);
             }
         }
 
@@ -1354,8 +1674,8 @@ mod tests {
         for (param1, param2, param3, is_ok, vec) in exponential_table {
             let got = exponential_buckets(param1, param2, param3);
             assert_eq!(got.is_ok(), is_ok);
-            if got.is_ok() {
-                assert_eq!(got.unwrap(), vec);
+            if let Ok(got) = got {
+                assert_eq!(got, vec);
             }
         }
     }
@@ -1366,7 +1686,7 @@ mod tests {
         for (millis, seconds) in tbls {
             let d = Duration::from_millis(millis);
             let v = duration_to_seconds(d);
-            assert!((v - seconds).abs() < EPSILON);
+            assert!((v - seconds).abs() < f64::EPSILON);
         }
     }
 
@@ -1405,7 +1725,7 @@ mod tests {
 
         let proto_histogram = m.get_histogram();
         assert_eq!(proto_histogram.get_sample_count(), 1);
-        assert!((proto_histogram.get_sample_sum() - 1.0) < EPSILON);
+        assert!((proto_histogram.get_sample_sum() - 1.0).abs() < f64::EPSILON);
         assert_eq!(proto_histogram.get_bucket().len(), buckets.len())
     }
 
@@ -1417,11 +1737,11 @@ mod tests {
         let histogram = Histogram::with_opts(opts).unwrap();
         let local = histogram.local();
 
-        let check = |count, sum| {
+        let check = |count, sum: f64| {
             let m = histogram.metric();
             let proto_histogram = m.get_histogram();
             assert_eq!(proto_histogram.get_sample_count(), count);
-            assert!((proto_histogram.get_sample_sum() - sum) < EPSILON);
+            assert!((proto_histogram.get_sample_sum() - sum).abs() < f64::EPSILON);
         };
 
         local.observe(1.0);
@@ -1452,11 +1772,11 @@ mod tests {
         vec.remove_label_values(&["v1", "v2"]).unwrap_err();
         local_vec.remove_label_values(&["v1", "v2"]).unwrap_err();
 
-        let check = |count, sum| {
+        let check = |count, sum: f64| {
             let ms = vec.collect()[0].take_metric();
             let proto_histogram = ms[0].get_histogram();
             assert_eq!(proto_histogram.get_sample_count(), count);
-            assert!((proto_histogram.get_sample_sum() - sum) < EPSILON);
+            assert!((proto_histogram.get_sample_sum() - sum).abs() < f64::EPSILON);
         };
 
         {
@@ -1572,4 +1892,143 @@ mod tests {
             panic!("Expected InconsistentCardinality error.")
         }
     }
+
+    #[test]
+    fn test_histogram_with_exemplar() {
+        let opts = HistogramOpts::new("test_exemplar", "test help").buckets(vec![1.0, 2.0]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+
+        let mut labels = HashMap::new();
+        labels.insert("trace_id", "first");
+        histogram.observe_with_exemplar(0.5, &labels).unwrap();
+        labels.insert("trace_id", "second");
+        histogram.observe_with_exemplar(0.7, &labels).unwrap();
+        labels.insert("trace_id", "inf");
+        histogram.observe_with_exemplar(3.0, &labels).unwrap();
+        histogram.observe(1.5);
+
+        let check = |h: &proto::Histogram| {
+            let buckets = h.get_bucket();
+            assert_eq!(buckets.len(), 3);
+            assert_eq!(h.get_sample_count(), 4);
+
+            assert!(buckets[0].has_exemplar());
+            let exemplar = buckets[0].get_exemplar();
+            assert_eq!(exemplar.get_label()[0].get_value(), "second");
+            assert!((exemplar.get_value() - 0.7).abs() < f64::EPSILON);
+
+            assert!(!buckets[1].has_exemplar());
+
+            assert!(buckets[2].get_upper_bound().is_infinite());
+            assert_eq!(buckets[2].get_cumulative_count(), 4);
+            assert_eq!(buckets[2].get_exemplar().get_label()[0].get_value(), "inf");
+        };
+
+        // Exemplars survive the hot and cold shard flip of subsequent collects.
+        check(histogram.metric().get_histogram());
+        check(histogram.metric().get_histogram());
+        check(histogram.metric().get_histogram());
+
+        let mut labels = HashMap::new();
+        labels.insert("trace_id", "x".repeat(MAX_EXEMPLAR_LABEL_LENGTH));
+        let labels = labels.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        assert!(histogram.observe_with_exemplar(0.5, &labels).is_err());
+        assert_eq!(histogram.get_sample_count(), 4);
+    }
+
+    #[test]
+    fn test_histogram_exemplar_between_collects() {
+        let opts = HistogramOpts::new("test_exemplar", "test help").buckets(vec![1.0]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+        let exemplar_label = |h: &proto::Histogram| {
+            let exemplar = h.get_bucket()[0].get_exemplar();
+            exemplar.get_label()[0].get_value().to_owned()
+        };
+
+        let mut labels = HashMap::new();
+        labels.insert("trace_id", "old");
+        histogram.observe_with_exemplar(0.5, &labels).unwrap();
+        assert_eq!(exemplar_label(histogram.metric().get_histogram()), "old");
+
+        thread::sleep(Duration::from_millis(1));
+        labels.insert("trace_id", "new");
+        histogram.observe_with_exemplar(0.5, &labels).unwrap();
+        assert_eq!(exemplar_label(histogram.metric().get_histogram()), "new");
+        assert_eq!(exemplar_label(histogram.metric().get_histogram()), "new");
+
+        // A newer exemplar in the hot shard wins over the cold one.
+        let cold = Shard::new(1);
+        let hot = Shard::new(1);
+        let old = make_exemplar(0.5, &labels).unwrap();
+        thread::sleep(Duration::from_millis(1));
+        let new = make_exemplar(0.7, &labels).unwrap();
+        *cold.exemplars[0].lock().unwrap() = Some(old);
+        *hot.exemplars[0].lock().unwrap() = Some(new);
+        let exemplar = HistogramCore::take_exemplar(&cold, &hot, 0).unwrap();
+        assert!((exemplar.get_value() - 0.7).abs() < f64::EPSILON);
+        assert!(cold.exemplars[0].lock().unwrap().is_none());
+        let hot_exemplar = hot.exemplars[0].lock().unwrap().clone().unwrap();
+        assert!((hot_exemplar.get_value() - 0.7).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_gauge_histogram() {
+        use crate::encoder::{OpenMetricsEncoder, TextEncoder};
+
+        let opts = HistogramOpts::new("test_gauge_histogram", "test help")
+            .const_label("a", "1")
+            .buckets(vec![1.0, 2.0]);
+        let histogram = GaugeHistogram::with_opts(opts).unwrap();
+        histogram.inc(0.5);
+        histogram.inc(1.5);
+        histogram.inc(1.5);
+        histogram.inc(3.0);
+        histogram.dec(1.5);
+        histogram.dec(3.0);
+        assert_eq!(histogram.get_sample_count(), 2);
+        assert!((histogram.get_sample_sum() - 2.0).abs() < f64::EPSILON);
+
+        let mfs = histogram.collect();
+        assert_eq!(mfs[0].get_field_type(), proto::MetricType::GAUGE_HISTOGRAM);
+        let expected = r#"# HELP test_gauge_histogram test help
+# TYPE test_gauge_histogram histogram
+test_gauge_histogram_bucket{a="1",le="1"} 1
+test_gauge_histogram_bucket{a="1",le="2"} 2
+test_gauge_histogram_bucket{a="1",le="+Inf"} 2
+test_gauge_histogram_sum{a="1"} 2
+test_gauge_histogram_count{a="1"} 2
+"#;
+        assert_eq!(TextEncoder::new().encode_to_string(&mfs).unwrap(), expected);
+
+        let expected = r#"# TYPE test_gauge_histogram gaugehistogram
+# HELP test_gauge_histogram test help
+test_gauge_histogram_bucket{a="1",le="1.0"} 1
+test_gauge_histogram_bucket{a="1",le="2.0"} 2
+test_gauge_histogram_bucket{a="1",le="+Inf"} 2
+test_gauge_histogram_gsum{a="1"} 2.0
+test_gauge_histogram_gcount{a="1"} 2
+# EOF
+"#;
+        assert_eq!(
+            OpenMetricsEncoder::new().encode_to_string(&mfs).unwrap(),
+            expected
+        );
+
+        // An unmatched `dec` does not expose negative counts.
+        histogram.dec(0.5);
+        histogram.dec(0.5);
+        let h = histogram.metric().get_histogram().clone();
+        assert_eq!(h.get_bucket()[0].get_cumulative_count(), 0);
+        assert_eq!(h.get_sample_count(), 1);
+
+        let vec = GaugeHistogramVec::new(
+            HistogramOpts::new("test_gauge_histogram_vec", "test help"),
+            &["l"],
+        )
+        .unwrap();
+        vec.with_label_values(&["x"]).inc(0.1);
+        let mfs = vec.collect();
+        assert_eq!(mfs[0].get_field_type(), proto::MetricType::GAUGE_HISTOGRAM);
+        assert_eq!(mfs[0].get_metric()[0].get_histogram().get_sample_count(), 1);
+    }
 }
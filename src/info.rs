@@ -0,0 +1,205 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::desc::{is_valid_label_name, Desc, Describer};
+use crate::errors::{Error, Result};
+use crate::metrics::{Collector, Metric, Opts};
+use crate::proto::{self, LabelPair, MetricFamily, MetricType};
+use crate::value::make_label_pairs;
+
+const INFO_SUFFIX: &str = "_info";
+
+/// A [`Collector`] exposing textual information about the target, e.g. the
+/// version and revision of the running binary, as the labels of a single
+/// sample with the value 1.
+///
+/// The fully-qualified name of an [`Info`] always ends with `_info`, which is
+/// appended to the name if missing. OpenMetrics output exposes it with the
+/// `info` type, the other formats as a gauge.
+///
+/// # Examples
+///
+/// ```
+/// use std::collections::HashMap;
+/// use prometheus::Info;
+///
+/// let info = Info::new("build", "Build information.").unwrap();
+/// let mut labels = HashMap::new();
+/// labels.insert("version", "1.2.3");
+/// info.set(&labels).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Info {
+    core: Arc<InfoCore>,
+}
+
+struct InfoCore {
+    desc: Desc,
+    label_pairs: RwLock<Vec<LabelPair>>,
+}
+
+impl Info {
+    /// Create an [`Info`] with the `name` and `help` arguments.
+    pub fn new<S1: Into<String>, S2: Into<String>>(name: S1, help: S2) -> Result<Info> {
+        Info::with_opts(Opts::new(name, help))
+    }
+
+    /// Create an [`Info`] with the `opts` options. The const labels of `opts`
+    /// are exposed along with the labels passed to [`Info::set`].
+    pub fn with_opts(mut opts: Opts) -> Result<Info> {
+        if !opts.name.ends_with(INFO_SUFFIX) {
+            opts.name.push_str(INFO_SUFFIX);
+        }
+        let desc = opts.describe()?;
+        let label_pairs = make_label_pairs(&desc, &[])?;
+
+        Ok(Info {
+            core: Arc::new(InfoCore {
+                desc,
+                label_pairs: RwLock::new(label_pairs),
+            }),
+        })
+    }
+
+    /// Replace the exposed information with `labels`. An error is returned
+    /// if a label name is invalid or clashes with a const label.
+    pub fn set(&self, labels: &HashMap<&str, &str>) -> Result<()> {
+        let desc = &self.core.desc;
+        let mut label_pairs = desc.const_label_pairs.clone();
+        for (name, value) in labels {
+            if !is_valid_label_name(name) {
+                return Err(Error::Msg(format!("'{}' is not a valid label name", name)));
+            }
+            if desc
+                .const_label_pairs
+                .iter()
+                .any(|lp| lp.get_name() == *name)
+            {
+                return Err(Error::Msg(format!(
+                    "label name {} is already a const label",
+                    name
+                )));
+            }
+
+            let mut label_pair = LabelPair::default();
+            label_pair.set_name((*name).to_owned());
+            label_pair.set_value((*value).to_owned());
+            label_pairs.push(label_pair);
+        }
+        label_pairs.sort();
+
+        *self.core.label_pairs.write() = label_pairs;
+        Ok(())
+    }
+
+    /// Return the exposed information, without the const labels.
+    pub fn get(&self) -> HashMap<String, String> {
+        let const_label_pairs = &self.core.desc.const_label_pairs;
+        self.core
+            .label_pairs
+            .read()
+            .iter()
+            .filter(|lp| !const_label_pairs.contains(lp))
+            .map(|lp| (lp.get_name().to_owned(), lp.get_value().to_owned()))
+            .collect()
+    }
+}
+
+impl Metric for Info {
+    fn metric(&self) -> proto::Metric {
+        let mut m = proto::Metric::default();
+        m.set_label(from_vec!(self.core.label_pairs.read().clone()));
+        let mut gauge = proto::Gauge::default();
+        gauge.set_value(1.0);
+        m.set_gauge(gauge);
+        m
+    }
+}
+
+impl Collector for Info {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.core.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let mut mf = MetricFamily::default();
+        mf.set_name(self.core.desc.fq_name.clone());
+        mf.set_help(self.core.desc.help.clone());
+        mf.set_field_type(MetricType::INFO);
+        mf.set_metric(from_vec!(vec![self.metric()]));
+        vec![mf]
+    }
+}
+
+impl std::fmt::Debug for Info {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Info {{ {} }}", self.core.desc.fq_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{OpenMetricsEncoder, TextEncoder};
+
+    #[test]
+    fn test_info() {
+        let info = Info::with_opts(Opts::new("build", "Build information.").const_label("c", "1"))
+            .unwrap();
+        assert_eq!(info.desc()[0].fq_name, "build_info");
+
+        let mut labels = HashMap::new();
+        labels.insert("version", "1.2.3");
+        labels.insert("revision", "abc");
+        info.set(&labels).unwrap();
+        assert_eq!(info.get().len(), 2);
+        assert_eq!(info.get()["version"], "1.2.3");
+
+        let mfs = info.collect();
+        let expected = r#"# HELP build_info Build information.
+# TYPE build_info gauge
+build_info{c="1",revision="abc",version="1.2.3"} 1
+"#;
+        assert_eq!(TextEncoder::new().encode_to_string(&mfs).unwrap(), expected);
+
+        let expected = r#"# TYPE build info
+# HELP build Build information.
+build_info{c="1",revision="abc",version="1.2.3"} 1
+# EOF
+"#;
+        assert_eq!(
+            OpenMetricsEncoder::new().encode_to_string(&mfs).unwrap(),
+            expected
+        );
+
+        labels.insert("c", "2");
+        assert!(info.set(&labels).is_err());
+        labels.clear();
+        labels.insert("in-valid", "2");
+        assert!(info.set(&labels).is_err());
+        assert_eq!(info.get()["revision"], "abc");
+
+        #[cfg(feature = "protobuf")]
+        {
+            use crate::encoder::{Encoder, ProtobufDecoder, ProtobufEncoder};
+
+            let mut buf = Vec::new();
+            ProtobufEncoder::new().encode(&mfs, &mut buf).unwrap();
+            let decoded: Vec<_> = ProtobufDecoder::new()
+                .decode_iter(&mut &buf[..])
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(decoded[0].get_field_type(), MetricType::GAUGE);
+        }
+
+        let info = Info::new("version_info", "Version information.").unwrap();
+        assert_eq!(info.desc()[0].fq_name, "version_info");
+        assert!(info.get().is_empty());
+        let opts = Opts::new("build", "Build information.").variable_label("a");
+        assert!(Info::with_opts(opts).is_err());
+    }
+}
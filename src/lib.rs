@@ -151,16 +151,23 @@ macro_rules! from_vec {
 mod macros;
 mod atomic64;
 mod auto_flush;
+mod cached;
+mod const_metric;
 mod counter;
 mod desc;
 mod encoder;
 mod errors;
+mod func;
 mod gauge;
 mod histogram;
+mod info;
 mod metrics;
+mod native_histogram;
 #[cfg(feature = "push")]
 mod push;
 mod registry;
+mod state_set;
+mod summary;
 mod value;
 mod vec;
 
@@ -182,6 +189,7 @@ pub mod local {
     };
     pub use super::histogram::{LocalHistogram, LocalHistogramTimer, LocalHistogramVec};
     pub use super::metrics::{LocalMetric, MayFlush};
+    pub use super::summary::{LocalSummary, LocalSummaryTimer, LocalSummaryVec};
 
     pub use super::auto_flush::{
         AFLocalCounter, AFLocalHistogram, CounterDelegator, HistogramDelegator,
@@ -201,28 +209,54 @@ pub mod core {
     };
     pub use super::desc::{Desc, Describer};
     pub use super::gauge::{GenericGauge, GenericGaugeVec};
-    pub use super::metrics::{Collector, Metric, Opts};
+    pub use super::metrics::{CardinalityLimit, CardinalityPolicy, Collector, Metric, Opts};
+    pub use super::state_set::State;
     pub use super::vec::{MetricVec, MetricVecBuilder};
 }
 
+pub use self::cached::CachedCollector;
+pub use self::const_metric::{
+    new_const_histogram, new_const_metric, new_const_summary, ConstMetric,
+};
 pub use self::counter::{Counter, CounterVec, IntCounter, IntCounterVec};
 pub use self::encoder::Encoder;
+pub use self::encoder::JsonEncoder;
+pub use self::encoder::OpenMetricsEncoder;
 #[cfg(feature = "protobuf")]
 pub use self::encoder::ProtobufEncoder;
 pub use self::encoder::TextEncoder;
+pub use self::encoder::JSON_FORMAT;
+pub use self::encoder::OPENMETRICS_FORMAT;
 #[cfg(feature = "protobuf")]
 pub use self::encoder::PROTOBUF_FORMAT;
 pub use self::encoder::TEXT_FORMAT;
-pub use self::errors::{Error, Result};
+pub use self::encoder::{parse_text, Decoder, TextDecoder};
+#[cfg(feature = "protobuf")]
+pub use self::encoder::{ProtobufDecodeIter, ProtobufDecoder, DEFAULT_MAX_MESSAGE_SIZE};
+pub use self::errors::{Error, GatherErrors, Result};
+pub use self::func::{CounterFunc, CounterFuncVec, GaugeFunc, GaugeFuncVec};
 pub use self::gauge::{Gauge, GaugeVec, IntGauge, IntGaugeVec};
 pub use self::histogram::DEFAULT_BUCKETS;
 pub use self::histogram::{exponential_buckets, linear_buckets};
-pub use self::histogram::{Histogram, HistogramOpts, HistogramTimer, HistogramVec};
-pub use self::metrics::Opts;
+pub use self::histogram::{
+    GaugeHistogram, GaugeHistogramVec, Histogram, HistogramOpts, HistogramTimer, HistogramVec,
+};
+pub use self::info::Info;
+pub use self::metrics::{CardinalityLimit, CardinalityPolicy, Opts};
+pub use self::native_histogram::{
+    NativeHistogram, NativeHistogramOpts, NativeHistogramVec, DEFAULT_NATIVE_HISTOGRAM_MAX_BUCKETS,
+    DEFAULT_NATIVE_HISTOGRAM_SCHEMA, DEFAULT_NATIVE_HISTOGRAM_ZERO_THRESHOLD,
+};
 #[cfg(feature = "push")]
 pub use self::push::{
     hostname_grouping_key, push_add_collector, push_add_metrics, push_collector, push_metrics,
     BasicAuthentication,
 };
 pub use self::registry::Registry;
-pub use self::registry::{default_registry, gather, register, unregister};
+pub use self::registry::{
+    default_registry, gather, gather_filtered, register, try_gather, unregister,
+};
+pub use self::state_set::StateSet;
+pub use self::summary::{Summary, SummaryOpts, SummaryTimer, SummaryVec};
+pub use self::summary::{DEFAULT_AGE_BUCKETS, DEFAULT_BUF_CAP, DEFAULT_MAX_AGE};
+pub use self::value::{ValueType, MAX_EXEMPLAR_LABEL_LENGTH};
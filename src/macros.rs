@@ -13,7 +13,7 @@
 ///     "foo" => "bar",
 /// };
 /// assert_eq!(labels.len(), 2);
-/// assert!(labels.get("test").is_some());
+/// assert!(labels.contains_key("test"));
 /// assert_eq!(*(labels.get("test").unwrap()), "hello");
 ///
 /// let labels: HashMap<&str, &str> = labels!{};
@@ -43,7 +43,7 @@ fn test_labels_without_trailing_comma() {
         "foo" => "bar"
     };
     assert_eq!(labels.len(), 2);
-    assert!(labels.get("test").is_some());
+    assert!(labels.contains_key("test"));
     assert_eq!(*(labels.get("test").unwrap()), "hello");
 }
 
@@ -63,7 +63,7 @@ fn test_labels_without_trailing_comma() {
 ///
 /// let opts = opts!(name, help, labels!{"test" => "hello", "foo" => "bar",});
 /// assert_eq!(opts.const_labels.len(), 2);
-/// assert!(opts.const_labels.get("foo").is_some());
+/// assert!(opts.const_labels.contains_key("foo"));
 /// assert_eq!(opts.const_labels.get("foo").unwrap(), "bar");
 ///
 /// let opts = opts!(name,
@@ -71,7 +71,7 @@ fn test_labels_without_trailing_comma() {
 ///                  labels!{"test" => "hello", "foo" => "bar",},
 ///                  labels!{"ans" => "42",});
 /// assert_eq!(opts.const_labels.len(), 3);
-/// assert!(opts.const_labels.get("ans").is_some());
+/// assert!(opts.const_labels.contains_key("ans"));
 /// assert_eq!(opts.const_labels.get("ans").unwrap(), "42");
 /// # }
 /// ```
@@ -82,9 +82,9 @@ macro_rules! opts {
             use std::collections::HashMap;
 
             let opts = $crate::Opts::new($NAME, $HELP);
-            let lbs = HashMap::<String, String>::new();
+            #[allow(unused_mut)]
+            let mut lbs = HashMap::<String, String>::new();
             $(
-                let mut lbs = lbs;
                 lbs.extend($CONST_LABELS.iter().map(|(k, v)| ((*k).into(), (*v).into())));
             )*
 
@@ -104,7 +104,7 @@ fn test_opts_trailing_comma() {
 
     let opts = opts!(name, help, labels! {"test" => "hello", "foo" => "bar",},);
     assert_eq!(opts.const_labels.len(), 2);
-    assert!(opts.const_labels.get("foo").is_some());
+    assert!(opts.const_labels.contains_key("foo"));
     assert_eq!(opts.const_labels.get("foo").unwrap(), "bar");
 
     let opts = opts!(
@@ -114,7 +114,7 @@ fn test_opts_trailing_comma() {
         labels! {"ans" => "42",},
     );
     assert_eq!(opts.const_labels.len(), 3);
-    assert!(opts.const_labels.get("ans").is_some());
+    assert!(opts.const_labels.contains_key("ans"));
     assert_eq!(opts.const_labels.get("ans").unwrap(), "42");
 }
 
@@ -145,7 +145,7 @@ fn test_opts_trailing_comma() {
 /// assert_eq!(opts.common_opts.name, name);
 /// assert_eq!(opts.common_opts.help, help);
 /// assert_eq!(opts.buckets.len(), 2);
-/// assert!(opts.common_opts.const_labels.get("key").is_some());
+/// assert!(opts.common_opts.const_labels.contains_key("key"));
 /// assert_eq!(opts.common_opts.const_labels.get("key").unwrap(), "value");
 /// # }
 /// ```
@@ -191,7 +191,7 @@ fn test_histogram_opts_trailing_comma() {
     assert_eq!(opts.common_opts.name, name);
     assert_eq!(opts.common_opts.help, help);
     assert_eq!(opts.buckets.len(), 2);
-    assert!(opts.common_opts.const_labels.get("key").is_some());
+    assert!(opts.common_opts.const_labels.contains_key("key"));
     assert_eq!(opts.common_opts.const_labels.get("key").unwrap(), "value");
 }
 
@@ -885,6 +885,267 @@ fn test_register_int_gauge_vec() {
     assert!(res.is_ok());
 }
 
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __register_func {
+    ($TYPE:ident, $OPTS:expr, $FUNC:expr) => {{
+        let func = $crate::$TYPE::with_opts($OPTS, $FUNC).unwrap();
+        $crate::register(Box::new(func.clone())).map(|_| func)
+    }};
+
+    ($TYPE:ident, $OPTS:expr, $FUNC:expr, $REGISTRY:expr) => {{
+        let func = $crate::$TYPE::with_opts($OPTS, $FUNC).unwrap();
+        $REGISTRY.register(Box::new(func.clone())).map(|_| func)
+    }};
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __register_func_vec {
+    ($TYPE:ident, $OPTS:expr, $LABELS_NAMES:expr, $FUNC:expr) => {{
+        let func_vec = $crate::$TYPE::new($OPTS, $LABELS_NAMES, $FUNC).unwrap();
+        $crate::register(Box::new(func_vec.clone())).map(|_| func_vec)
+    }};
+
+    ($TYPE:ident, $OPTS:expr, $LABELS_NAMES:expr, $FUNC:expr, $REGISTRY:expr) => {{
+        let func_vec = $crate::$TYPE::new($OPTS, $LABELS_NAMES, $FUNC).unwrap();
+        $REGISTRY
+            .register(Box::new(func_vec.clone()))
+            .map(|_| func_vec)
+    }};
+}
+
+/// Create a [`GaugeFunc`][crate::GaugeFunc] and registers to default registry.
+///
+/// # Examples
+///
+/// ```
+/// # use prometheus::{opts, register_gauge_func};
+/// # fn main() {
+/// let opts = opts!("test_macro_gauge_func", "help");
+/// let res1 = register_gauge_func!(opts, || 1.0);
+/// assert!(res1.is_ok());
+///
+/// let res2 = register_gauge_func!("test_macro_gauge_func_2", "help", || 2.0);
+/// assert!(res2.is_ok());
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! register_gauge_func {
+    ($OPTS:expr, $FUNC:expr $(,)?) => {{
+        __register_func!(GaugeFunc, $OPTS, $FUNC)
+    }};
+
+    ($NAME:expr, $HELP:expr, $FUNC:expr $(,)?) => {{
+        register_gauge_func!(opts!($NAME, $HELP), $FUNC)
+    }};
+}
+
+/// Create a [`GaugeFunc`][crate::GaugeFunc] and registers to a custom registry.
+///
+/// View docs of `register_gauge_with_registry` for examples.
+#[macro_export(local_inner_macros)]
+macro_rules! register_gauge_func_with_registry {
+    ($OPTS:expr, $FUNC:expr, $REGISTRY:expr $(,)?) => {{
+        __register_func!(GaugeFunc, $OPTS, $FUNC, $REGISTRY)
+    }};
+
+    ($NAME:expr, $HELP:expr, $FUNC:expr, $REGISTRY:expr $(,)?) => {{
+        register_gauge_func_with_registry!(opts!($NAME, $HELP), $FUNC, $REGISTRY)
+    }};
+}
+
+/// Create a [`CounterFunc`][crate::CounterFunc] and registers to default registry.
+///
+/// View docs of `register_gauge_func` for examples.
+#[macro_export(local_inner_macros)]
+macro_rules! register_counter_func {
+    ($OPTS:expr, $FUNC:expr $(,)?) => {{
+        __register_func!(CounterFunc, $OPTS, $FUNC)
+    }};
+
+    ($NAME:expr, $HELP:expr, $FUNC:expr $(,)?) => {{
+        register_counter_func!(opts!($NAME, $HELP), $FUNC)
+    }};
+}
+
+/// Create a [`CounterFunc`][crate::CounterFunc] and registers to a custom registry.
+///
+/// View docs of `register_gauge_with_registry` for examples.
+#[macro_export(local_inner_macros)]
+macro_rules! register_counter_func_with_registry {
+    ($OPTS:expr, $FUNC:expr, $REGISTRY:expr $(,)?) => {{
+        __register_func!(CounterFunc, $OPTS, $FUNC, $REGISTRY)
+    }};
+
+    ($NAME:expr, $HELP:expr, $FUNC:expr, $REGISTRY:expr $(,)?) => {{
+        register_counter_func_with_registry!(opts!($NAME, $HELP), $FUNC, $REGISTRY)
+    }};
+}
+
+#[test]
+fn test_register_func() {
+    use crate::Registry;
+
+    let res = register_gauge_func!(opts!("test_macro_gauge_func_1", "help"), || 1.0);
+    assert!(res.is_ok());
+
+    let res = register_gauge_func!("test_macro_gauge_func_2", "help", || 1.0,);
+    assert!(res.is_ok());
+
+    let res = register_counter_func!(opts!("test_macro_counter_func_1", "help"), || 1.0,);
+    assert!(res.is_ok());
+
+    let res = register_counter_func!("test_macro_counter_func_2", "help", || 1.0);
+    assert!(res.is_ok());
+
+    let custom_registry = Registry::new();
+
+    let opts = opts!("test_macro_gauge_func_1", "help");
+    let res = register_gauge_func_with_registry!(opts, || 1.0, custom_registry);
+    assert!(res.is_ok());
+
+    let res = register_gauge_func_with_registry!(
+        "test_macro_gauge_func_2",
+        "help",
+        || 1.0,
+        custom_registry,
+    );
+    assert!(res.is_ok());
+
+    let opts = opts!("test_macro_counter_func_1", "help");
+    let res = register_counter_func_with_registry!(opts, || 1.0, custom_registry,);
+    assert!(res.is_ok());
+
+    let res = register_counter_func_with_registry!(
+        "test_macro_counter_func_2",
+        "help",
+        || 1.0,
+        custom_registry
+    );
+    assert!(res.is_ok());
+}
+
+/// Create a [`GaugeFuncVec`][crate::GaugeFuncVec] and registers to default registry.
+///
+/// # Examples
+///
+/// ```
+/// # use prometheus::{opts, register_gauge_func_vec};
+/// # fn main() {
+/// let opts = opts!("test_macro_gauge_func_vec_1", "help");
+/// let res1 = register_gauge_func_vec!(opts, &["a"], || vec![(vec!["x".to_owned()], 1.0)]);
+/// assert!(res1.is_ok());
+///
+/// let res2 = register_gauge_func_vec!("test_macro_gauge_func_vec_2", "help", &["a"], Vec::new);
+/// assert!(res2.is_ok());
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! register_gauge_func_vec {
+    ($OPTS:expr, $LABELS_NAMES:expr, $FUNC:expr $(,)?) => {{
+        __register_func_vec!(GaugeFuncVec, $OPTS, $LABELS_NAMES, $FUNC)
+    }};
+
+    ($NAME:expr, $HELP:expr, $LABELS_NAMES:expr, $FUNC:expr $(,)?) => {{
+        register_gauge_func_vec!(opts!($NAME, $HELP), $LABELS_NAMES, $FUNC)
+    }};
+}
+
+/// Create a [`GaugeFuncVec`][crate::GaugeFuncVec] and registers to a custom registry.
+///
+/// View docs of `register_gauge_vec_with_registry` for examples.
+#[macro_export(local_inner_macros)]
+macro_rules! register_gauge_func_vec_with_registry {
+    ($OPTS:expr, $LABELS_NAMES:expr, $FUNC:expr, $REGISTRY:expr $(,)?) => {{
+        __register_func_vec!(GaugeFuncVec, $OPTS, $LABELS_NAMES, $FUNC, $REGISTRY)
+    }};
+
+    ($NAME:expr, $HELP:expr, $LABELS_NAMES:expr, $FUNC:expr, $REGISTRY:expr $(,)?) => {{
+        register_gauge_func_vec_with_registry!(opts!($NAME, $HELP), $LABELS_NAMES, $FUNC, $REGISTRY)
+    }};
+}
+
+/// Create a [`CounterFuncVec`][crate::CounterFuncVec] and registers to default registry.
+///
+/// View docs of `register_gauge_func_vec` for examples.
+#[macro_export(local_inner_macros)]
+macro_rules! register_counter_func_vec {
+    ($OPTS:expr, $LABELS_NAMES:expr, $FUNC:expr $(,)?) => {{
+        __register_func_vec!(CounterFuncVec, $OPTS, $LABELS_NAMES, $FUNC)
+    }};
+
+    ($NAME:expr, $HELP:expr, $LABELS_NAMES:expr, $FUNC:expr $(,)?) => {{
+        register_counter_func_vec!(opts!($NAME, $HELP), $LABELS_NAMES, $FUNC)
+    }};
+}
+
+/// Create a [`CounterFuncVec`][crate::CounterFuncVec] and registers to a custom registry.
+///
+/// View docs of `register_gauge_vec_with_registry` for examples.
+#[macro_export(local_inner_macros)]
+macro_rules! register_counter_func_vec_with_registry {
+    ($OPTS:expr, $LABELS_NAMES:expr, $FUNC:expr, $REGISTRY:expr $(,)?) => {{
+        __register_func_vec!(CounterFuncVec, $OPTS, $LABELS_NAMES, $FUNC, $REGISTRY)
+    }};
+
+    ($NAME:expr, $HELP:expr, $LABELS_NAMES:expr, $FUNC:expr, $REGISTRY:expr $(,)?) => {{
+        register_counter_func_vec_with_registry!(
+            opts!($NAME, $HELP),
+            $LABELS_NAMES,
+            $FUNC,
+            $REGISTRY
+        )
+    }};
+}
+
+#[test]
+fn test_register_func_vec() {
+    use crate::Registry;
+
+    let opts = opts!("test_macro_gauge_func_vec_1", "help");
+    let res = register_gauge_func_vec!(opts, &["a"], Vec::new);
+    assert!(res.is_ok());
+
+    let res = register_gauge_func_vec!("test_macro_gauge_func_vec_2", "help", &["a"], Vec::new,);
+    assert!(res.is_ok());
+
+    let opts = opts!("test_macro_counter_func_vec_1", "help");
+    let res = register_counter_func_vec!(opts, &["a"], Vec::new,);
+    assert!(res.is_ok());
+
+    let res = register_counter_func_vec!("test_macro_counter_func_vec_2", "help", &["a"], Vec::new);
+    assert!(res.is_ok());
+
+    let custom_registry = Registry::new();
+
+    let opts = opts!("test_macro_gauge_func_vec_1", "help");
+    let res = register_gauge_func_vec_with_registry!(opts, &["a"], Vec::new, custom_registry);
+    assert!(res.is_ok());
+
+    let res = register_gauge_func_vec_with_registry!(
+        "test_macro_gauge_func_vec_2",
+        "help",
+        &["a"],
+        Vec::new,
+        custom_registry,
+    );
+    assert!(res.is_ok());
+
+    let opts = opts!("test_macro_counter_func_vec_1", "help");
+    let res = register_counter_func_vec_with_registry!(opts, &["a"], Vec::new, custom_registry,);
+    assert!(res.is_ok());
+
+    let res = register_counter_func_vec_with_registry!(
+        "test_macro_counter_func_vec_2",
+        "help",
+        &["a"],
+        Vec::new,
+        custom_registry
+    );
+    assert!(res.is_ok());
+}
+
 /// Create a [`Histogram`][crate::Histogram] and registers to default registry.
 ///
 /// # Examples
@@ -1144,3 +1405,333 @@ fn test_register_histogram_vec_with_registry_trailing_comma() {
     );
     assert!(histogram_vec.is_ok());
 }
+
+/// Create a [`SummaryOpts`][crate::SummaryOpts].
+///
+/// # Examples
+///
+/// ```
+/// # use prometheus::summary_opts;
+/// # use prometheus::labels;
+/// # fn main() {
+/// let name = "test_summary_opts";
+/// let help = "test opts help";
+///
+/// let opts = summary_opts!(name, help);
+/// assert_eq!(opts.common_opts.name, name);
+/// assert_eq!(opts.common_opts.help, help);
+///
+/// let opts = summary_opts!(name, help, vec![(0.5, 0.05), (0.99, 0.001)]);
+/// assert_eq!(opts.common_opts.name, name);
+/// assert_eq!(opts.common_opts.help, help);
+/// assert_eq!(opts.objectives.len(), 2);
+///
+/// let opts = summary_opts!(name,
+///                          help,
+///                          vec![(0.5, 0.05)],
+///                          labels!{"key".to_string() => "value".to_string(),});
+/// assert_eq!(opts.common_opts.name, name);
+/// assert_eq!(opts.common_opts.help, help);
+/// assert_eq!(opts.objectives.len(), 1);
+/// assert!(opts.common_opts.const_labels.contains_key("key"));
+/// assert_eq!(opts.common_opts.const_labels.get("key").unwrap(), "value");
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! summary_opts {
+    ($NAME:expr, $HELP:expr $(,)?) => {{
+        $crate::SummaryOpts::new($NAME, $HELP)
+    }};
+
+    ($NAME:expr, $HELP:expr, $OBJECTIVES:expr $(,)?) => {{
+        let sopts = summary_opts!($NAME, $HELP);
+        sopts.objectives($OBJECTIVES)
+    }};
+
+    ($NAME:expr, $HELP:expr, $OBJECTIVES:expr, $CONST_LABELS:expr $(,)?) => {{
+        let sopts = summary_opts!($NAME, $HELP, $OBJECTIVES);
+        sopts.const_labels($CONST_LABELS)
+    }};
+}
+
+#[test]
+fn test_summary_opts_trailing_comma() {
+    let name = "test_summary_opts";
+    let help = "test opts help";
+
+    let opts = summary_opts!(name, help,);
+    assert_eq!(opts.common_opts.name, name);
+    assert_eq!(opts.common_opts.help, help);
+
+    let opts = summary_opts!(name, help, vec![(0.5, 0.05), (0.9, 0.01)],);
+    assert_eq!(opts.common_opts.name, name);
+    assert_eq!(opts.common_opts.help, help);
+    assert_eq!(opts.objectives.len(), 2);
+
+    let opts = summary_opts!(
+        name,
+        help,
+        vec![(0.5, 0.05)],
+        labels! {"key".to_string() => "value".to_string(),},
+    );
+    assert_eq!(opts.common_opts.name, name);
+    assert_eq!(opts.common_opts.help, help);
+    assert_eq!(opts.objectives.len(), 1);
+    assert!(opts.common_opts.const_labels.contains_key("key"));
+    assert_eq!(opts.common_opts.const_labels.get("key").unwrap(), "value");
+}
+
+/// Create a [`Summary`][crate::Summary] and registers to default registry.
+///
+/// # Examples
+///
+/// ```
+/// # use prometheus::{summary_opts, register_summary};
+/// # fn main() {
+/// let opts = summary_opts!("test_macro_summary", "help");
+/// let res1 = register_summary!(opts);
+/// assert!(res1.is_ok());
+///
+/// let res2 = register_summary!("test_macro_summary_2", "help");
+/// assert!(res2.is_ok());
+///
+/// let res3 = register_summary!("test_macro_summary_4",
+///                              "help",
+///                              vec![(0.5, 0.05), (0.99, 0.001)]);
+/// assert!(res3.is_ok());
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! register_summary {
+    ($NAME:expr, $HELP:expr $(,)?) => {
+        register_summary!(summary_opts!($NAME, $HELP))
+    };
+
+    ($NAME:expr, $HELP:expr, $OBJECTIVES:expr $(,)?) => {
+        register_summary!(summary_opts!($NAME, $HELP, $OBJECTIVES))
+    };
+
+    ($SOPTS:expr $(,)?) => {{
+        let summary = $crate::Summary::with_opts($SOPTS).unwrap();
+        $crate::register(Box::new(summary.clone())).map(|_| summary)
+    }};
+}
+
+#[test]
+fn test_register_summary_trailing_comma() {
+    let opts = summary_opts!("test_macro_summary", "help",);
+    let res1 = register_summary!(opts,);
+    assert!(res1.is_ok());
+
+    let res2 = register_summary!("test_macro_summary_2", "help",);
+    assert!(res2.is_ok());
+
+    let res3 = register_summary!("test_macro_summary_4", "help", vec![(0.5, 0.05)],);
+    assert!(res3.is_ok());
+}
+
+/// Create a [`Summary`][crate::Summary] and registers to a custom registry.
+///
+/// # Examples
+///
+/// ```
+/// # use prometheus::{register_summary_with_registry, summary_opts};
+/// # use prometheus::Registry;
+/// # use std::collections::HashMap;
+/// # fn main() {
+/// let mut labels = HashMap::new();
+/// labels.insert("mykey".to_string(), "myvalue".to_string());
+/// let custom_registry = Registry::new_custom(Some("myprefix".to_string()), Some(labels)).unwrap();
+///
+/// let opts = summary_opts!("test_macro_summary", "help");
+/// let res1 = register_summary_with_registry!(opts, custom_registry);
+/// assert!(res1.is_ok());
+///
+/// let res2 = register_summary_with_registry!("test_macro_summary_2", "help", custom_registry);
+/// assert!(res2.is_ok());
+///
+/// let res3 = register_summary_with_registry!("test_macro_summary_4",
+///                                 "help",
+///                                 vec![(0.5, 0.05)], custom_registry);
+/// assert!(res3.is_ok());
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! register_summary_with_registry {
+    ($NAME:expr, $HELP:expr, $REGISTRY:expr $(,)?) => {
+        register_summary_with_registry!(summary_opts!($NAME, $HELP), $REGISTRY)
+    };
+
+    ($NAME:expr, $HELP:expr, $OBJECTIVES:expr, $REGISTRY:expr $(,)?) => {
+        register_summary_with_registry!(summary_opts!($NAME, $HELP, $OBJECTIVES), $REGISTRY)
+    };
+
+    ($SOPTS:expr, $REGISTRY:expr $(,)?) => {{
+        let summary = $crate::Summary::with_opts($SOPTS).unwrap();
+        $REGISTRY
+            .register(Box::new(summary.clone()))
+            .map(|_| summary)
+    }};
+}
+
+#[test]
+fn test_register_summary_with_registry_trailing_comma() {
+    use crate::Registry;
+    use std::collections::HashMap;
+
+    let mut labels = HashMap::new();
+    labels.insert("mykey".to_string(), "myvalue".to_string());
+    let custom_registry = Registry::new_custom(Some("myprefix".to_string()), Some(labels)).unwrap();
+
+    let opts = summary_opts!("test_macro_summary", "help",);
+    let res1 = register_summary_with_registry!(opts, custom_registry,);
+    assert!(res1.is_ok());
+
+    let res2 = register_summary_with_registry!("test_macro_summary_2", "help", custom_registry,);
+    assert!(res2.is_ok());
+
+    let res3 = register_summary_with_registry!(
+        "test_macro_summary_4",
+        "help",
+        vec![(0.5, 0.05)],
+        custom_registry,
+    );
+    assert!(res3.is_ok());
+}
+
+/// Create a [`SummaryVec`][crate::SummaryVec] and registers to default registry.
+///
+/// # Examples
+///
+/// ```
+/// # use prometheus::{summary_opts, register_summary_vec};
+/// # fn main() {
+/// let opts = summary_opts!("test_macro_summary_vec_1", "help");
+/// let summary_vec = register_summary_vec!(opts, &["a", "b"]);
+/// assert!(summary_vec.is_ok());
+///
+/// let summary_vec =
+///     register_summary_vec!("test_macro_summary_vec_2", "help", &["a", "b"]);
+/// assert!(summary_vec.is_ok());
+///
+/// let summary_vec = register_summary_vec!("test_macro_summary_vec_3",
+///                                         "help",
+///                                         &["test_label"],
+///                                         vec![(0.5, 0.05), (0.9, 0.01)]);
+/// assert!(summary_vec.is_ok());
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! register_summary_vec {
+    ($SOPTS:expr, $LABELS_NAMES:expr $(,)?) => {{
+        let summary_vec = $crate::SummaryVec::new($SOPTS, $LABELS_NAMES).unwrap();
+        $crate::register(Box::new(summary_vec.clone())).map(|_| summary_vec)
+    }};
+
+    ($NAME:expr, $HELP:expr, $LABELS_NAMES:expr $(,)?) => {{
+        register_summary_vec!(summary_opts!($NAME, $HELP), $LABELS_NAMES)
+    }};
+
+    ($NAME:expr, $HELP:expr, $LABELS_NAMES:expr, $OBJECTIVES:expr $(,)?) => {{
+        register_summary_vec!(summary_opts!($NAME, $HELP, $OBJECTIVES), $LABELS_NAMES)
+    }};
+}
+
+#[test]
+fn test_register_summary_vec_trailing_comma() {
+    let opts = summary_opts!("test_macro_summary_vec_1", "help",);
+    let summary_vec = register_summary_vec!(opts, &["a", "b"],);
+    assert!(summary_vec.is_ok());
+
+    let summary_vec = register_summary_vec!("test_macro_summary_vec_2", "help", &["a", "b"],);
+    assert!(summary_vec.is_ok());
+
+    let summary_vec = register_summary_vec!(
+        "test_macro_summary_vec_3",
+        "help",
+        &["test_label"],
+        vec![(0.5, 0.05), (0.9, 0.01)],
+    );
+    assert!(summary_vec.is_ok());
+}
+
+/// Create a [`SummaryVec`][crate::SummaryVec] and registers to a custom registry.
+///
+/// # Examples
+///
+/// ```
+/// # use prometheus::{register_summary_vec_with_registry, summary_opts};
+/// # use prometheus::Registry;
+/// # use std::collections::HashMap;
+/// # fn main() {
+/// let mut labels = HashMap::new();
+/// labels.insert("mykey".to_string(), "myvalue".to_string());
+/// let custom_registry = Registry::new_custom(Some("myprefix".to_string()), Some(labels)).unwrap();
+///
+/// let opts = summary_opts!("test_macro_summary_vec_1", "help");
+/// let summary_vec = register_summary_vec_with_registry!(opts, &["a", "b"], custom_registry);
+/// assert!(summary_vec.is_ok());
+///
+/// let summary_vec =
+///     register_summary_vec_with_registry!("test_macro_summary_vec_2", "help", &["a", "b"], custom_registry);
+/// assert!(summary_vec.is_ok());
+///
+/// let summary_vec = register_summary_vec_with_registry!("test_macro_summary_vec_3",
+///                                             "help",
+///                                             &["test_label"],
+///                                             vec![(0.5, 0.05)], custom_registry);
+/// assert!(summary_vec.is_ok());
+/// # }
+/// ```
+#[macro_export(local_inner_macros)]
+macro_rules! register_summary_vec_with_registry {
+    ($SOPTS:expr, $LABELS_NAMES:expr, $REGISTRY:expr $(,)?) => {{
+        let summary_vec = $crate::SummaryVec::new($SOPTS, $LABELS_NAMES).unwrap();
+        $REGISTRY
+            .register(Box::new(summary_vec.clone()))
+            .map(|_| summary_vec)
+    }};
+
+    ($NAME:expr, $HELP:expr, $LABELS_NAMES:expr, $REGISTRY:expr $(,)?) => {{
+        register_summary_vec_with_registry!(summary_opts!($NAME, $HELP), $LABELS_NAMES, $REGISTRY)
+    }};
+
+    ($NAME:expr, $HELP:expr, $LABELS_NAMES:expr, $OBJECTIVES:expr, $REGISTRY:expr $(,)?) => {{
+        register_summary_vec_with_registry!(
+            summary_opts!($NAME, $HELP, $OBJECTIVES),
+            $LABELS_NAMES,
+            $REGISTRY
+        )
+    }};
+}
+
+#[test]
+fn test_register_summary_vec_with_registry_trailing_comma() {
+    use crate::Registry;
+    use std::collections::HashMap;
+
+    let mut labels = HashMap::new();
+    labels.insert("mykey".to_string(), "myvalue".to_string());
+    let custom_registry = Registry::new_custom(Some("myprefix".to_string()), Some(labels)).unwrap();
+
+    let opts = summary_opts!("test_macro_summary_vec_1", "help",);
+    let summary_vec = register_summary_vec_with_registry!(opts, &["a", "b"], custom_registry,);
+    assert!(summary_vec.is_ok());
+
+    let summary_vec = register_summary_vec_with_registry!(
+        "test_macro_summary_vec_2",
+        "help",
+        &["a", "b"],
+        custom_registry,
+    );
+    assert!(summary_vec.is_ok());
+
+    let summary_vec = register_summary_vec_with_registry!(
+        "test_macro_summary_vec_3",
+        "help",
+        &["test_label"],
+        vec![(0.5, 0.05)],
+        custom_registry,
+    );
+    assert!(summary_vec.is_ok());
+}
@@ -3,6 +3,7 @@
 
 use std::cmp::{Eq, Ord, Ordering, PartialOrd};
 use std::collections::HashMap;
+use std::time::Duration;
 
 use crate::desc::{Desc, Describer};
 use crate::errors::Result;
@@ -19,12 +20,30 @@ pub trait Collector: Sync + Send {
 
     /// Collect metrics.
     fn collect(&self) -> Vec<proto::MetricFamily>;
+
+    /// Collect metrics, returning an error if the metrics can not be
+    /// collected, e.g. because their source is unavailable.
+    ///
+    /// [`Registry`](crate::Registry) collects through this method and reports
+    /// the errors per collector. The default implementation never fails and
+    /// returns the result of [`collect`](Collector::collect).
+    fn try_collect(&self) -> Result<Vec<proto::MetricFamily>> {
+        Ok(self.collect())
+    }
 }
 
 /// An interface models a single sample value with its meta data being exported to Prometheus.
 pub trait Metric: Sync + Send + Clone {
     /// Return the protocol Metric.
     fn metric(&self) -> proto::Metric;
+
+    /// Return the coarse time in milliseconds of the last update, if the
+    /// metric was created with a ttl. Metric vectors keep children updated
+    /// within their ttl. The default implementation tracks no updates.
+    #[doc(hidden)]
+    fn last_touched(&self) -> Option<u64> {
+        None
+    }
 }
 
 /// An interface models a Metric only usable in single thread environment.
@@ -107,6 +126,23 @@ pub struct Opts {
     /// Note that variable_labels is used in `MetricVec`. To create a single
     /// metric must leave it empty.
     pub variable_labels: Vec<String>,
+
+    /// cardinality_limit bounds the number of children, i.e. distinct sets
+    /// of variable label values, a metric vector created with these Opts
+    /// may hold. It is ignored by single metrics. `None` means unbounded.
+    pub cardinality_limit: Option<CardinalityLimit>,
+
+    /// ttl makes a metric vector created with these Opts drop children whose
+    /// label values have been neither looked up nor updated for the given
+    /// duration. Updates through a kept handle, a flushed local metric or a
+    /// static metric count as well. Expired children are removed at collect
+    /// time. It is ignored by single metrics.
+    pub ttl: Option<Duration>,
+
+    /// unit is the unit of the metric values, e.g. `seconds`. The
+    /// fully-qualified name must end with it, see [`Desc::with_unit`]. Empty
+    /// means the metric has no unit.
+    pub unit: String,
 }
 
 impl Opts {
@@ -119,6 +155,9 @@ impl Opts {
             help: help.into(),
             const_labels: HashMap::new(),
             variable_labels: Vec::new(),
+            cardinality_limit: None,
+            ttl: None,
+            unit: String::new(),
         }
     }
 
@@ -158,20 +197,75 @@ impl Opts {
         self
     }
 
+    /// `cardinality_limit` limits the number of children of a metric vector
+    /// to `max_children`, applying `policy` once the limit is reached.
+    pub fn cardinality_limit(mut self, max_children: usize, policy: CardinalityPolicy) -> Self {
+        self.cardinality_limit = Some(CardinalityLimit {
+            max_children,
+            policy,
+        });
+        self
+    }
+
+    /// `ttl` sets the time after which untouched children of a metric vector
+    /// expire.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// `unit` sets the unit.
+    pub fn unit<S: Into<String>>(mut self, unit: S) -> Self {
+        self.unit = unit.into();
+        self
+    }
+
     /// `fq_name` returns the fq_name.
     pub fn fq_name(&self) -> String {
         build_fq_name(&self.namespace, &self.subsystem, &self.name)
     }
-}
 
-impl Describer for Opts {
-    fn describe(&self) -> Result<Desc> {
+    /// `describe_as` works as [`Describer::describe`], but checks the unit
+    /// against the name of a metric of the given type.
+    pub(crate) fn describe_as(&self, metric_type: proto::MetricType) -> Result<Desc> {
         Desc::new(
             self.fq_name(),
             self.help.clone(),
             self.variable_labels.clone(),
             self.const_labels.clone(),
-        )
+        )?
+        .with_unit(self.unit.clone(), metric_type)
+    }
+}
+
+/// What a metric vector does when a new set of label values would take it
+/// past its [`CardinalityLimit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CardinalityPolicy {
+    /// Return an error from `get_metric_with_label_values` and friends.
+    Error,
+    /// Return a single shared overflow child, whose variable labels all
+    /// have the given sentinel value. The overflow child is created on
+    /// demand and does not count against the limit.
+    Overflow(String),
+    /// Evict the least recently used child to make room for the new one.
+    /// Handles to an evicted child keep working but are no longer exported.
+    EvictLru,
+}
+
+/// A limit on the number of children of a metric vector. See
+/// [`Opts::cardinality_limit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardinalityLimit {
+    /// The maximum number of children, must be greater than zero.
+    pub max_children: usize,
+    /// The policy applied once `max_children` is reached.
+    pub policy: CardinalityPolicy,
+}
+
+impl Describer for Opts {
+    fn describe(&self) -> Result<Desc> {
+        self.describe_as(proto::MetricType::UNTYPED)
     }
 }
 
@@ -0,0 +1,612 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+
+use crate::desc::{Desc, Describer};
+use crate::errors::{Error, Result};
+use crate::metrics::{CardinalityPolicy, Collector, Metric, Opts};
+use crate::proto;
+use crate::value::{make_label_pairs, make_timestamp};
+use crate::vec::{ChildLimits, LastTouched, MetricVec, MetricVecBuilder};
+
+/// The default schema of a [`NativeHistogram`], with a bucket growth factor
+/// of 2^(2^-3), i.e. about 1.09.
+pub const DEFAULT_NATIVE_HISTOGRAM_SCHEMA: i32 = 3;
+
+/// The default breadth of the zero bucket of a [`NativeHistogram`], 2^-128.
+pub const DEFAULT_NATIVE_HISTOGRAM_ZERO_THRESHOLD: f64 = 2.938_735_877_055_719e-39;
+
+/// The default maximum number of buckets of a [`NativeHistogram`].
+pub const DEFAULT_NATIVE_HISTOGRAM_MAX_BUCKETS: usize = 160;
+
+const MIN_SCHEMA: i32 = -4;
+const MAX_SCHEMA: i32 = 8;
+
+lazy_static! {
+    /// The upper bounds of the buckets within one power of two for the
+    /// schemas 1 to 8, as fractions in `[0.5, 1)` like returned by `frexp`.
+    static ref BOUNDS: Vec<Vec<f64>> = (1..=MAX_SCHEMA)
+        .map(|schema| {
+            let n = 1 << schema;
+            (0..n).map(|j| (f64::from(j) / f64::from(n)).exp2() / 2.0).collect()
+        })
+        .collect();
+}
+
+/// A struct that bundles the options for creating a [`NativeHistogram`]
+/// metric. It is mandatory to set Name and Help to a non-empty string. All
+/// other fields are optional and can safely be left at their default value.
+#[derive(Clone, Debug)]
+pub struct NativeHistogramOpts {
+    /// A container holding various options.
+    pub common_opts: Opts,
+
+    /// Defines the resolution of the buckets. The upper bound of each bucket
+    /// is `2^(2^-schema)` times the one of the previous bucket. The schema
+    /// must be in `[-4, 8]`, the default is 3.
+    pub schema: i32,
+
+    /// Observations with an absolute value less than or equal to the zero
+    /// threshold are counted in the zero bucket. The default is 2^-128.
+    pub zero_threshold: f64,
+
+    /// The maximum number of populated buckets, not counting the zero bucket.
+    /// When exceeded, the resolution is halved by decreasing the schema until
+    /// the buckets fit or the lowest schema is reached. 0 disables the limit,
+    /// the default is 160.
+    pub max_buckets: usize,
+}
+
+impl NativeHistogramOpts {
+    /// Create a [`NativeHistogramOpts`] with the `name` and `help` arguments.
+    pub fn new<S1: Into<String>, S2: Into<String>>(name: S1, help: S2) -> NativeHistogramOpts {
+        NativeHistogramOpts::from(Opts::new(name, help))
+    }
+
+    /// `namespace` sets the namespace.
+    pub fn namespace<S: Into<String>>(mut self, namespace: S) -> Self {
+        self.common_opts.namespace = namespace.into();
+        self
+    }
+
+    /// `subsystem` sets the sub system.
+    pub fn subsystem<S: Into<String>>(mut self, subsystem: S) -> Self {
+        self.common_opts.subsystem = subsystem.into();
+        self
+    }
+
+    /// `const_labels` sets the const labels.
+    pub fn const_labels(mut self, const_labels: HashMap<String, String>) -> Self {
+        self.common_opts = self.common_opts.const_labels(const_labels);
+        self
+    }
+
+    /// `const_label` adds a const label.
+    pub fn const_label<S1: Into<String>, S2: Into<String>>(mut self, name: S1, value: S2) -> Self {
+        self.common_opts = self.common_opts.const_label(name, value);
+        self
+    }
+
+    /// `variable_labels` sets the variable labels.
+    pub fn variable_labels(mut self, variable_labels: Vec<String>) -> Self {
+        self.common_opts = self.common_opts.variable_labels(variable_labels);
+        self
+    }
+
+    /// `variable_label` adds a variable label.
+    pub fn variable_label<S: Into<String>>(mut self, name: S) -> Self {
+        self.common_opts = self.common_opts.variable_label(name);
+        self
+    }
+
+    /// `cardinality_limit` limits the number of children of a metric vector.
+    /// See [`Opts::cardinality_limit`].
+    pub fn cardinality_limit(mut self, max_children: usize, policy: CardinalityPolicy) -> Self {
+        self.common_opts = self.common_opts.cardinality_limit(max_children, policy);
+        self
+    }
+
+    /// `ttl` sets the time after which untouched children of a metric vector
+    /// expire. See [`Opts::ttl`].
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.common_opts = self.common_opts.ttl(ttl);
+        self
+    }
+
+    /// `unit` sets the unit. See [`Opts::unit`].
+    pub fn unit<S: Into<String>>(mut self, unit: S) -> Self {
+        self.common_opts = self.common_opts.unit(unit);
+        self
+    }
+
+    /// `fq_name` returns the fq_name.
+    pub fn fq_name(&self) -> String {
+        self.common_opts.fq_name()
+    }
+
+    /// `schema` sets the schema.
+    pub fn schema(mut self, schema: i32) -> Self {
+        self.schema = schema;
+        self
+    }
+
+    /// `zero_threshold` sets the zero threshold.
+    pub fn zero_threshold(mut self, zero_threshold: f64) -> Self {
+        self.zero_threshold = zero_threshold;
+        self
+    }
+
+    /// `max_buckets` sets the maximum number of buckets.
+    pub fn max_buckets(mut self, max_buckets: usize) -> Self {
+        self.max_buckets = max_buckets;
+        self
+    }
+}
+
+impl Describer for NativeHistogramOpts {
+    fn describe(&self) -> Result<Desc> {
+        self.common_opts.describe()
+    }
+}
+
+impl From<Opts> for NativeHistogramOpts {
+    fn from(opts: Opts) -> NativeHistogramOpts {
+        NativeHistogramOpts {
+            common_opts: opts,
+            schema: DEFAULT_NATIVE_HISTOGRAM_SCHEMA,
+            zero_threshold: DEFAULT_NATIVE_HISTOGRAM_ZERO_THRESHOLD,
+            max_buckets: DEFAULT_NATIVE_HISTOGRAM_MAX_BUCKETS,
+        }
+    }
+}
+
+/// Split the positive and finite `v` into a fraction in `[0.5, 1)` and an
+/// exponent, such that `v == frac * 2^exp`.
+fn frexp(v: f64) -> (f64, i32) {
+    const EXP_MASK: u64 = 0x7ff << 52;
+
+    let bits = v.to_bits();
+    let exp = ((bits & EXP_MASK) >> 52) as i32;
+    if exp == 0 {
+        // Subnormal, scale it into the normal range first.
+        let (frac, exp) = frexp(v * 2f64.powi(64));
+        return (frac, exp - 64);
+    }
+    let frac = f64::from_bits((bits & !EXP_MASK) | (1022 << 52));
+    (frac, exp - 1022)
+}
+
+/// Return the index of the bucket the positive `v` falls into at `schema`.
+/// Bucket `i` covers the interval `(base^(i-1), base^i]`, with the base being
+/// `2^(2^-schema)`.
+fn bucket_key(v: f64, schema: i32) -> i32 {
+    // Infinity shares the highest bucket, whose upper bound is infinite.
+    let (frac, exp) = frexp(v.min(f64::MAX));
+    if schema > 0 {
+        let bounds = &BOUNDS[(schema - 1) as usize];
+        let i = bounds.partition_point(|b| *b < frac) as i32;
+        i + (exp - 1) * bounds.len() as i32
+    } else {
+        let mut key = exp;
+        if frac == 0.5 {
+            key -= 1;
+        }
+        let offset = (1 << -schema) - 1;
+        (key + offset) >> -schema
+    }
+}
+
+/// Merge the buckets pairwise into the buckets of the next lower schema.
+fn halve_resolution(buckets: &mut BTreeMap<i32, u64>) {
+    let mut merged = BTreeMap::new();
+    for (key, count) in buckets.iter() {
+        *merged.entry((key + 1) >> 1).or_insert(0) += count;
+    }
+    *buckets = merged;
+}
+
+/// Encode the sparse `buckets` as spans of consecutive buckets and the count
+/// deltas between the buckets.
+fn make_spans(buckets: &BTreeMap<i32, u64>) -> (Vec<proto::BucketSpan>, Vec<i64>) {
+    let mut spans: Vec<proto::BucketSpan> = Vec::new();
+    let mut deltas = Vec::with_capacity(buckets.len());
+    let mut last: Option<(i32, u64)> = None;
+    for (&key, &count) in buckets {
+        match last {
+            Some((last_key, _)) if key == last_key + 1 => {
+                let span = spans.last_mut().unwrap();
+                span.set_length(span.get_length() + 1);
+            }
+            _ => {
+                let mut span = proto::BucketSpan::default();
+                span.set_offset(match last {
+                    Some((last_key, _)) => key - last_key - 1,
+                    None => key,
+                });
+                span.set_length(1);
+                spans.push(span);
+            }
+        }
+        let last_count = last.map_or(0, |(_, c)| c);
+        deltas.push(count as i64 - last_count as i64);
+        last = Some((key, count));
+    }
+    (spans, deltas)
+}
+
+#[derive(Debug)]
+struct NativeHistogramState {
+    schema: i32,
+    zero_count: u64,
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    count: u64,
+    sum: f64,
+}
+
+#[derive(Debug)]
+struct NativeHistogramCore {
+    desc: Desc,
+    label_pairs: Vec<proto::LabelPair>,
+    zero_threshold: f64,
+    max_buckets: usize,
+    created: SystemTime,
+    last_touched: LastTouched,
+    state: Mutex<NativeHistogramState>,
+}
+
+/// A [`Metric`] counting observations in sparse buckets of exponentially
+/// growing width, i.e. a Prometheus native histogram.
+///
+/// Unlike a [`Histogram`](crate::Histogram), a [`NativeHistogram`] does not
+/// need its buckets to be defined upfront: buckets are created as
+/// observations fall into them, with a resolution defined by the schema. If
+/// the number of buckets exceeds the configured maximum, the resolution is
+/// halved until they fit.
+///
+/// The buckets are only exposed by the protobuf format. The text formats
+/// expose the sum and count of the observations.
+///
+/// # Examples
+///
+/// ```
+/// use prometheus::{NativeHistogram, NativeHistogramOpts};
+///
+/// let opts = NativeHistogramOpts::new("request_duration_seconds", "Request latencies.");
+/// let histogram = NativeHistogram::with_opts(opts).unwrap();
+/// histogram.observe(0.042);
+/// assert_eq!(histogram.get_sample_count(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct NativeHistogram {
+    core: Arc<NativeHistogramCore>,
+}
+
+impl NativeHistogram {
+    /// `with_opts` creates a [`NativeHistogram`] with the `opts` options.
+    pub fn with_opts(opts: NativeHistogramOpts) -> Result<NativeHistogram> {
+        NativeHistogram::with_opts_and_label_values(&opts, &[])
+    }
+
+    fn with_opts_and_label_values(
+        opts: &NativeHistogramOpts,
+        label_values: &[&str],
+    ) -> Result<NativeHistogram> {
+        if !(MIN_SCHEMA..=MAX_SCHEMA).contains(&opts.schema) {
+            return Err(Error::Msg(format!(
+                "native histogram schema must be in [{}, {}], got {}",
+                MIN_SCHEMA, MAX_SCHEMA, opts.schema
+            )));
+        }
+        if opts.zero_threshold.is_nan() || opts.zero_threshold < 0.0 {
+            return Err(Error::Msg(format!(
+                "native histogram zero threshold must not be negative or NaN, got {}",
+                opts.zero_threshold
+            )));
+        }
+
+        let desc = opts.describe()?;
+        let label_pairs = make_label_pairs(&desc, label_values)?;
+
+        Ok(NativeHistogram {
+            core: Arc::new(NativeHistogramCore {
+                desc,
+                label_pairs,
+                zero_threshold: opts.zero_threshold,
+                max_buckets: opts.max_buckets,
+                created: SystemTime::now(),
+                last_touched: LastTouched::new(opts.common_opts.ttl),
+                state: Mutex::new(NativeHistogramState {
+                    schema: opts.schema,
+                    zero_count: 0,
+                    positive: BTreeMap::new(),
+                    negative: BTreeMap::new(),
+                    count: 0,
+                    sum: 0.0,
+                }),
+            }),
+        })
+    }
+
+    /// Add a single observation to the [`NativeHistogram`]. A NaN
+    /// observation is counted, but not put in any bucket.
+    pub fn observe(&self, v: f64) {
+        let core = &self.core;
+        core.last_touched.touch();
+        let mut state = core.state.lock();
+        state.count += 1;
+        state.sum += v;
+
+        let schema = state.schema;
+        if v.is_nan() {
+            return;
+        } else if v.abs() <= core.zero_threshold {
+            state.zero_count += 1;
+        } else if v > 0.0 {
+            *state.positive.entry(bucket_key(v, schema)).or_insert(0) += 1;
+        } else {
+            *state.negative.entry(bucket_key(-v, schema)).or_insert(0) += 1;
+        }
+
+        if core.max_buckets == 0 {
+            return;
+        }
+        while state.positive.len() + state.negative.len() > core.max_buckets
+            && state.schema > MIN_SCHEMA
+        {
+            state.schema -= 1;
+            halve_resolution(&mut state.positive);
+            halve_resolution(&mut state.negative);
+        }
+    }
+
+    /// Return accumulated sum of all samples.
+    pub fn get_sample_sum(&self) -> f64 {
+        self.core.state.lock().sum
+    }
+
+    /// Return count of all samples.
+    pub fn get_sample_count(&self) -> u64 {
+        self.core.state.lock().count
+    }
+
+    /// Return the current schema, which is lower than the configured one if
+    /// the resolution had to be reduced.
+    pub fn get_schema(&self) -> i32 {
+        self.core.state.lock().schema
+    }
+}
+
+impl Metric for NativeHistogram {
+    fn metric(&self) -> proto::Metric {
+        let mut h = proto::Histogram::default();
+        {
+            let state = self.core.state.lock();
+            h.set_sample_count(state.count);
+            h.set_sample_sum(state.sum);
+            h.set_schema(state.schema);
+            h.set_zero_threshold(self.core.zero_threshold);
+            h.set_zero_count(state.zero_count);
+            h.set_created_timestamp(make_timestamp(self.core.created));
+
+            let (mut positive_spans, positive_deltas) = make_spans(&state.positive);
+            let (negative_spans, negative_deltas) = make_spans(&state.negative);
+            if positive_spans.is_empty() && negative_spans.is_empty() {
+                // An empty span tells a native histogram without observations
+                // apart from a classic one.
+                positive_spans.push(proto::BucketSpan::default());
+            }
+            h.set_positive_span(from_vec!(positive_spans));
+            h.set_positive_delta(positive_deltas);
+            h.set_negative_span(from_vec!(negative_spans));
+            h.set_negative_delta(negative_deltas);
+        }
+
+        let mut m = proto::Metric::default();
+        m.set_label(from_vec!(self.core.label_pairs.clone()));
+        m.set_histogram(h);
+        m
+    }
+
+    fn last_touched(&self) -> Option<u64> {
+        self.core.last_touched.get()
+    }
+}
+
+impl Collector for NativeHistogram {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.core.desc]
+    }
+
+    fn collect(&self) -> Vec<proto::MetricFamily> {
+        let mut m = proto::MetricFamily::default();
+        m.set_name(self.core.desc.fq_name.clone());
+        m.set_help(self.core.desc.help.clone());
+        if !self.core.desc.unit.is_empty() {
+            m.set_unit(self.core.desc.unit.clone());
+        }
+        m.set_field_type(proto::MetricType::HISTOGRAM);
+        m.set_metric(from_vec!(vec![self.metric()]));
+
+        vec![m]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NativeHistogramVecBuilder {}
+
+impl MetricVecBuilder for NativeHistogramVecBuilder {
+    type M = NativeHistogram;
+    type P = NativeHistogramOpts;
+
+    fn build(&self, opts: &NativeHistogramOpts, vals: &[&str]) -> Result<NativeHistogram> {
+        NativeHistogram::with_opts_and_label_values(opts, vals)
+    }
+}
+
+/// A [`Collector`] that bundles a set of NativeHistograms that all share the
+/// same [`Desc`], but have different values for their variable labels.
+pub type NativeHistogramVec = MetricVec<NativeHistogramVecBuilder>;
+
+impl NativeHistogramVec {
+    /// Create a new [`NativeHistogramVec`] based on the provided
+    /// [`NativeHistogramOpts`] and partitioned by the given label names. At
+    /// least one label name must be provided.
+    pub fn new(opts: NativeHistogramOpts, label_names: &[&str]) -> Result<NativeHistogramVec> {
+        let variable_names = label_names.iter().map(|s| (*s).to_owned()).collect();
+        let opts = opts.variable_labels(variable_names);
+        let limits = ChildLimits::from_opts(&opts.common_opts);
+        let desc = opts.describe()?;
+        let metric_vec = MetricVec::create_with_limits(
+            proto::MetricType::HISTOGRAM,
+            NativeHistogramVecBuilder {},
+            opts,
+            desc,
+            limits,
+        )?;
+
+        Ok(metric_vec as NativeHistogramVec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_key() {
+        // Bucket i covers (2^(i-1), 2^i] at schema 0.
+        assert_eq!(bucket_key(1.0, 0), 0);
+        assert_eq!(bucket_key(1.5, 0), 1);
+        assert_eq!(bucket_key(2.0, 0), 1);
+        assert_eq!(bucket_key(0.25, 0), -2);
+        assert_eq!(bucket_key(0.3, 0), -1);
+
+        // Bucket i covers (4^(i-1), 4^i] at schema -1.
+        assert_eq!(bucket_key(4.0, -1), 1);
+        assert_eq!(bucket_key(5.0, -1), 2);
+
+        // Bucket i covers (sqrt(2)^(i-1), sqrt(2)^i] at schema 1.
+        assert_eq!(bucket_key(1.0, 1), 0);
+        assert_eq!(bucket_key(1.2, 1), 1);
+        assert_eq!(bucket_key(1.5, 1), 2);
+        assert_eq!(bucket_key(2.0, 1), 2);
+        assert_eq!(bucket_key(1024.0, 8), 10 * 256);
+
+        // Subnormal and infinite values.
+        assert_eq!(bucket_key(f64::MIN_POSITIVE / 4.0, 0), -1024);
+        assert_eq!(bucket_key(f64::INFINITY, 0), 1024);
+
+        // The bucket of a lower schema contains the buckets it was merged from.
+        for &v in &[0.001, 0.7, 1.0, 3.3, 1e6] {
+            for schema in MIN_SCHEMA + 1..=MAX_SCHEMA {
+                assert_eq!(
+                    (bucket_key(v, schema) + 1) >> 1,
+                    bucket_key(v, schema - 1),
+                    "v {} schema {}",
+                    v,
+                    schema
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_native_histogram() {
+        let opts = NativeHistogramOpts::new("test_native_histogram", "test help")
+            .const_label("a", "1")
+            .schema(0)
+            .zero_threshold(0.5);
+        let histogram = NativeHistogram::with_opts(opts).unwrap();
+
+        let h = histogram.metric().get_histogram().clone();
+        assert_eq!(h.get_positive_span().len(), 1);
+        assert_eq!(h.get_positive_span()[0].get_length(), 0);
+
+        for &v in &[0.1, -0.2, 1.0, 1.5, 2.0, 8.0, 16.0, -3.0] {
+            histogram.observe(v);
+        }
+        assert_eq!(histogram.get_sample_count(), 8);
+        assert!((histogram.get_sample_sum() - 25.4).abs() < 1e-9);
+
+        let mfs = histogram.collect();
+        assert_eq!(mfs[0].get_field_type(), proto::MetricType::HISTOGRAM);
+        let h = mfs[0].get_metric()[0].get_histogram();
+        assert_eq!(h.get_schema(), 0);
+        assert!((h.get_zero_threshold() - 0.5).abs() < f64::EPSILON);
+        assert_eq!(h.get_zero_count(), 2);
+
+        // Positive buckets 0: 1, 1: 2, 3: 1, 4: 1.
+        let spans: Vec<_> = h
+            .get_positive_span()
+            .iter()
+            .map(|s| (s.get_offset(), s.get_length()))
+            .collect();
+        assert_eq!(spans, vec![(0, 2), (1, 2)]);
+        assert_eq!(h.get_positive_delta(), &[1, 1, -1, 0]);
+        // Negative bucket 2: 1.
+        assert_eq!(h.get_negative_span()[0].get_offset(), 2);
+        assert_eq!(h.get_negative_delta(), &[1]);
+
+        #[cfg(feature = "protobuf")]
+        {
+            use crate::encoder::{Encoder, ProtobufDecoder, ProtobufEncoder};
+
+            let mut buf = Vec::new();
+            ProtobufEncoder::new().encode(&mfs, &mut buf).unwrap();
+            let decoded: Vec<_> = ProtobufDecoder::new()
+                .decode_iter(&mut &buf[..])
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(decoded, mfs);
+        }
+    }
+
+    #[test]
+    fn test_native_histogram_max_buckets() {
+        let opts = NativeHistogramOpts::new("test_native_histogram", "test help")
+            .schema(2)
+            .max_buckets(4);
+        let histogram = NativeHistogram::with_opts(opts).unwrap();
+        for i in 1..=16 {
+            histogram.observe(f64::from(i));
+        }
+
+        let schema = histogram.get_schema();
+        assert!(schema < 2);
+        let h = histogram.metric().get_histogram().clone();
+        assert_eq!(h.get_schema(), schema);
+        let num_buckets: u32 = h.get_positive_span().iter().map(|s| s.get_length()).sum();
+        assert!(num_buckets <= 4);
+        let total: i64 = h
+            .get_positive_delta()
+            .iter()
+            .scan(0, |count, delta| {
+                *count += delta;
+                Some(*count)
+            })
+            .sum();
+        assert_eq!(total, 16);
+    }
+
+    #[test]
+    fn test_native_histogram_invalid_opts() {
+        let opts = NativeHistogramOpts::new("test_native_histogram", "test help");
+        assert!(NativeHistogram::with_opts(opts.clone().schema(9)).is_err());
+        assert!(NativeHistogram::with_opts(opts.clone().schema(-5)).is_err());
+        assert!(NativeHistogram::with_opts(opts.zero_threshold(f64::NAN)).is_err());
+
+        let vec = NativeHistogramVec::new(
+            NativeHistogramOpts::new("test_native_histogram_vec", "test help"),
+            &["l"],
+        )
+        .unwrap();
+        vec.with_label_values(&["x"]).observe(1.0);
+        assert_eq!(vec.collect()[0].get_metric().len(), 1);
+    }
+}
@@ -65,6 +65,8 @@ impl Gauge {
 #[derive(PartialEq, Clone, Default, Debug)]
 pub struct Counter {
     value: f64,
+    exemplar: Option<Exemplar>,
+    created_timestamp: Option<Timestamp>,
 }
 
 impl Counter {
@@ -81,6 +83,32 @@ impl Counter {
     pub fn get_value(&self) -> f64 {
         self.value
     }
+
+    pub fn has_exemplar(&self) -> bool {
+        self.exemplar.is_some()
+    }
+
+    pub fn set_exemplar(&mut self, v: Exemplar) {
+        self.exemplar = Some(v);
+    }
+
+    pub fn get_exemplar(&self) -> &Exemplar {
+        self.exemplar.as_ref().unwrap_or(&DEFAULT_EXEMPLAR)
+    }
+
+    pub fn has_created_timestamp(&self) -> bool {
+        self.created_timestamp.is_some()
+    }
+
+    pub fn set_created_timestamp(&mut self, v: Timestamp) {
+        self.created_timestamp = Some(v);
+    }
+
+    pub fn get_created_timestamp(&self) -> &Timestamp {
+        self.created_timestamp
+            .as_ref()
+            .unwrap_or(&DEFAULT_TIMESTAMP)
+    }
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
@@ -117,6 +145,7 @@ pub struct Summary {
     sample_count: u64,
     sample_sum: f64,
     quantile: Vec<Quantile>,
+    created_timestamp: Option<Timestamp>,
 }
 
 impl Summary {
@@ -148,6 +177,20 @@ impl Summary {
     pub fn get_quantile(&self) -> &[Quantile] {
         &self.quantile
     }
+
+    pub fn has_created_timestamp(&self) -> bool {
+        self.created_timestamp.is_some()
+    }
+
+    pub fn set_created_timestamp(&mut self, v: Timestamp) {
+        self.created_timestamp = Some(v);
+    }
+
+    pub fn get_created_timestamp(&self) -> &Timestamp {
+        self.created_timestamp
+            .as_ref()
+            .unwrap_or(&DEFAULT_TIMESTAMP)
+    }
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
@@ -183,6 +226,14 @@ pub struct Histogram {
     sample_count: u64,
     sample_sum: f64,
     bucket: Vec<Bucket>,
+    schema: i32,
+    zero_threshold: f64,
+    zero_count: u64,
+    negative_span: Vec<BucketSpan>,
+    negative_delta: Vec<i64>,
+    positive_span: Vec<BucketSpan>,
+    positive_delta: Vec<i64>,
+    created_timestamp: Option<Timestamp>,
 }
 
 impl Histogram {
@@ -214,12 +265,83 @@ impl Histogram {
     pub fn get_bucket(&self) -> &[Bucket] {
         &self.bucket
     }
+
+    pub fn set_schema(&mut self, v: i32) {
+        self.schema = v;
+    }
+
+    pub fn get_schema(&self) -> i32 {
+        self.schema
+    }
+
+    pub fn set_zero_threshold(&mut self, v: f64) {
+        self.zero_threshold = v;
+    }
+
+    pub fn get_zero_threshold(&self) -> f64 {
+        self.zero_threshold
+    }
+
+    pub fn set_zero_count(&mut self, v: u64) {
+        self.zero_count = v;
+    }
+
+    pub fn get_zero_count(&self) -> u64 {
+        self.zero_count
+    }
+
+    pub fn set_negative_span(&mut self, v: Vec<BucketSpan>) {
+        self.negative_span = v;
+    }
+
+    pub fn get_negative_span(&self) -> &[BucketSpan] {
+        &self.negative_span
+    }
+
+    pub fn set_negative_delta(&mut self, v: Vec<i64>) {
+        self.negative_delta = v;
+    }
+
+    pub fn get_negative_delta(&self) -> &[i64] {
+        &self.negative_delta
+    }
+
+    pub fn set_positive_span(&mut self, v: Vec<BucketSpan>) {
+        self.positive_span = v;
+    }
+
+    pub fn get_positive_span(&self) -> &[BucketSpan] {
+        &self.positive_span
+    }
+
+    pub fn set_positive_delta(&mut self, v: Vec<i64>) {
+        self.positive_delta = v;
+    }
+
+    pub fn get_positive_delta(&self) -> &[i64] {
+        &self.positive_delta
+    }
+
+    pub fn has_created_timestamp(&self) -> bool {
+        self.created_timestamp.is_some()
+    }
+
+    pub fn set_created_timestamp(&mut self, v: Timestamp) {
+        self.created_timestamp = Some(v);
+    }
+
+    pub fn get_created_timestamp(&self) -> &Timestamp {
+        self.created_timestamp
+            .as_ref()
+            .unwrap_or(&DEFAULT_TIMESTAMP)
+    }
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
 pub struct Bucket {
     cumulative_count: u64,
     upper_bound: f64,
+    exemplar: Option<Exemplar>,
 }
 
 impl Bucket {
@@ -243,6 +365,115 @@ impl Bucket {
     pub fn get_upper_bound(&self) -> f64 {
         self.upper_bound
     }
+
+    pub fn has_exemplar(&self) -> bool {
+        self.exemplar.is_some()
+    }
+
+    pub fn set_exemplar(&mut self, v: Exemplar) {
+        self.exemplar = Some(v);
+    }
+
+    pub fn get_exemplar(&self) -> &Exemplar {
+        self.exemplar.as_ref().unwrap_or(&DEFAULT_EXEMPLAR)
+    }
+}
+
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct BucketSpan {
+    offset: i32,
+    length: u32,
+}
+
+impl BucketSpan {
+    pub fn set_offset(&mut self, v: i32) {
+        self.offset = v;
+    }
+
+    pub fn get_offset(&self) -> i32 {
+        self.offset
+    }
+
+    pub fn set_length(&mut self, v: u32) {
+        self.length = v;
+    }
+
+    pub fn get_length(&self) -> u32 {
+        self.length
+    }
+}
+
+static DEFAULT_EXEMPLAR: Exemplar = Exemplar {
+    label: Vec::new(),
+    value: 0.0,
+    timestamp: None,
+};
+
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct Exemplar {
+    label: Vec<LabelPair>,
+    value: f64,
+    timestamp: Option<Timestamp>,
+}
+
+impl Exemplar {
+    pub fn set_label(&mut self, v: Vec<LabelPair>) {
+        self.label = v;
+    }
+
+    pub fn get_label(&self) -> &[LabelPair] {
+        &self.label
+    }
+
+    pub fn set_value(&mut self, v: f64) {
+        self.value = v;
+    }
+
+    pub fn get_value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn has_timestamp(&self) -> bool {
+        self.timestamp.is_some()
+    }
+
+    pub fn set_timestamp(&mut self, v: Timestamp) {
+        self.timestamp = Some(v);
+    }
+
+    pub fn get_timestamp(&self) -> &Timestamp {
+        self.timestamp.as_ref().unwrap_or(&DEFAULT_TIMESTAMP)
+    }
+}
+
+static DEFAULT_TIMESTAMP: Timestamp = Timestamp {
+    seconds: 0,
+    nanos: 0,
+};
+
+/// Counterpart of the `google.protobuf.Timestamp` well-known type.
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct Timestamp {
+    seconds: i64,
+    nanos: i32,
+}
+
+impl Timestamp {
+    pub fn set_seconds(&mut self, v: i64) {
+        self.seconds = v;
+    }
+
+    pub fn get_seconds(&self) -> i64 {
+        self.seconds
+    }
+
+    pub fn set_nanos(&mut self, v: i32) {
+        self.nanos = v;
+    }
+
+    pub fn get_nanos(&self) -> i32 {
+        self.nanos
+    }
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
@@ -336,6 +567,7 @@ impl Metric {
     }
 }
 
+#[allow(non_camel_case_types)]
 #[derive(Clone, PartialEq, Eq, Debug, Hash, Copy)]
 pub enum MetricType {
     COUNTER,
@@ -343,6 +575,9 @@ pub enum MetricType {
     SUMMARY,
     UNTYPED,
     HISTOGRAM,
+    GAUGE_HISTOGRAM,
+    INFO,
+    STATESET,
 }
 
 impl Default for MetricType {
@@ -357,6 +592,7 @@ pub struct MetricFamily {
     help: String,
     field_type: MetricType,
     metric: Vec<Metric>,
+    unit: String,
 }
 
 impl MetricFamily {
@@ -416,4 +652,12 @@ impl MetricFamily {
     pub fn get_metric(&self) -> &[Metric] {
         &self.metric
     }
+
+    pub fn set_unit(&mut self, v: String) {
+        self.unit = v;
+    }
+
+    pub fn get_unit(&self) -> &str {
+        &self.unit
+    }
 }
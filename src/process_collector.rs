@@ -8,6 +8,7 @@ use lazy_static::lazy_static;
 
 use crate::counter::IntCounter;
 use crate::desc::Desc;
+use crate::errors::{Error, Result};
 use crate::gauge::IntGauge;
 use crate::metrics::{Collector, Opts};
 use crate::proto;
@@ -138,13 +139,17 @@ impl Collector for ProcessCollector {
     }
 
     fn collect(&self) -> Vec<proto::MetricFamily> {
-        let p = match procfs::process::Process::new(self.pid) {
-            Ok(p) => p,
-            Err(..) => {
-                // we can't construct a Process object, so there's no stats to gather
-                return Vec::new();
-            }
-        };
+        // we can't construct a Process object, so there's no stats to gather
+        self.try_collect().unwrap_or_default()
+    }
+
+    fn try_collect(&self) -> Result<Vec<proto::MetricFamily>> {
+        let p = procfs::process::Process::new(self.pid).map_err(|e| {
+            Error::Msg(format!(
+                "failed to read stats of process {}: {}",
+                self.pid, e
+            ))
+        })?;
 
         // file descriptors
         if let Ok(fd_count) = p.fd_count() {
@@ -181,7 +186,7 @@ impl Collector for ProcessCollector {
         mfs.extend(self.rss.collect());
         mfs.extend(self.start_time.collect());
         mfs.extend(self.threads.collect());
-        mfs
+        Ok(mfs)
     }
 }
 
@@ -222,4 +227,11 @@ mod tests {
         let res = r.register(Box::new(pc));
         assert!(res.is_ok());
     }
+
+    #[test]
+    fn test_process_collector_missing_process() {
+        let pc = ProcessCollector::new(pid_t::MAX, "missing");
+        assert!(pc.try_collect().is_err());
+        assert!(pc.collect().is_empty());
+    }
 }
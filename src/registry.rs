@@ -4,34 +4,239 @@
 use std::collections::btree_map::Entry as BEntry;
 use std::collections::hash_map::Entry as HEntry;
 use std::collections::{BTreeMap, HashMap, HashSet};
-use std::sync::Arc;
-
-use parking_lot::RwLock;
-
-use crate::errors::{Error, Result};
-use crate::metrics::Collector;
+use std::iter;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use parking_lot::{Mutex, RwLock};
+
+use crate::counter::IntCounterVec;
+use crate::desc::Desc;
+use crate::errors::{Error, GatherErrors, Result};
+use crate::metrics::{Collector, Opts};
 use crate::proto;
 
 use cfg_if::cfg_if;
 use lazy_static::lazy_static;
 
-#[derive(Default)]
+/// A hook run by [`Registry::gather`] on the gathered metric families, see
+/// [`Registry::add_gather_hook`].
+type GatherHook = Box<dyn Fn(&mut Vec<proto::MetricFamily>) + Send + Sync>;
+
+/// The name of the counter of failed collections, see [`Registry::gather`].
+const COLLECT_FAILURES_NAME: &str = "prometheus_collector_failures_total";
+
+/// The name of the counter of collections that missed the deadline of a
+/// parallel collection, see [`Registry::set_parallel_collection`].
+const COLLECT_TIMEOUTS_NAME: &str = "prometheus_collector_timeouts_total";
+
+/// The result of collecting a single collector.
+type CollectResult = Result<Vec<proto::MetricFamily>>;
+
+/// A job run by the workers of a [`CollectPool`].
+type CollectJob = Box<dyn FnOnce() + Send>;
+
+/// The progress of a collector on a [`CollectPool`], by index.
+enum CollectEvent {
+    Started(usize, Instant),
+    Done(usize, CollectResult),
+}
+
+/// A bounded pool of threads collecting collectors in parallel, each within a
+/// timeout.
+struct CollectPool {
+    jobs: Mutex<Sender<CollectJob>>,
+    threads: usize,
+    timeout: Duration,
+    /// The addresses of the collectors submitted to the pool which did not
+    /// complete yet, including the ones their gather gave up on.
+    in_flight: Arc<Mutex<HashSet<usize>>>,
+}
+
+impl CollectPool {
+    fn new(threads: usize, timeout: Duration) -> Result<CollectPool> {
+        let (tx, rx) = mpsc::channel::<CollectJob>();
+        let rx = Arc::new(Mutex::new(rx));
+        for i in 0..threads {
+            let rx = rx.clone();
+            // Workers exit once the pool, i.e. the sending half, is dropped.
+            thread::Builder::new()
+                .name(format!("prometheus-collect-{}", i))
+                .spawn(move || loop {
+                    let job = rx.lock().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => return,
+                    }
+                })?;
+        }
+
+        Ok(CollectPool {
+            jobs: Mutex::new(tx),
+            threads,
+            timeout,
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+        })
+    }
+
+    /// `collect` collects `collectors` on the pool. The result of a collector
+    /// is `None` if it did not complete in time, in which case it is left
+    /// running but its result is discarded.
+    ///
+    /// A collector has to complete within the timeout of the pool, counted
+    /// from when a thread of the pool started collecting it. The whole call
+    /// is bounded by the time the collectors would take if each used up its
+    /// timeout, i.e. the timeout times the number of collectors per thread,
+    /// counted from the call. Collectors still queued then are not run.
+    /// Collectors whose previous collection did not complete yet are not
+    /// submitted again.
+    fn collect(&self, collectors: &[Arc<dyn Collector>]) -> Vec<Option<CollectResult>> {
+        let rounds = collectors.len().div_ceil(self.threads);
+        let overall_deadline = Instant::now() + self.timeout * rounds as u32;
+        let expired = Arc::new(AtomicBool::new(false));
+        let (tx, rx): (Sender<CollectEvent>, Receiver<_>) = mpsc::channel();
+        let mut pending = 0;
+        {
+            let jobs = self.jobs.lock();
+            let mut in_flight = self.in_flight.lock();
+            for (i, c) in collectors.iter().enumerate() {
+                let key = Arc::as_ptr(c) as *const () as usize;
+                if !in_flight.insert(key) {
+                    continue;
+                }
+                pending += 1;
+
+                let c = c.clone();
+                let tx = tx.clone();
+                let expired = expired.clone();
+                let in_flight = self.in_flight.clone();
+                let job = Box::new(move || {
+                    if expired.load(Ordering::Relaxed) {
+                        in_flight.lock().remove(&key);
+                        return;
+                    }
+                    let _ = tx.send(CollectEvent::Started(i, Instant::now()));
+                    let res = panic::catch_unwind(AssertUnwindSafe(|| c.try_collect()))
+                        .unwrap_or_else(|_| Err(Error::Msg("collector panicked".to_owned())));
+                    in_flight.lock().remove(&key);
+                    // The gather may have given up on this result already.
+                    let _ = tx.send(CollectEvent::Done(i, res));
+                });
+                jobs.send(job).expect("collect pool workers exited");
+            }
+        }
+        drop(tx);
+
+        let mut results: Vec<_> = collectors.iter().map(|_| None).collect();
+        // The deadlines of the collectors being collected, unset once they
+        // completed or missed it.
+        let mut deadlines: Vec<Option<Instant>> = collectors.iter().map(|_| None).collect();
+        while pending > 0 {
+            let deadline = match deadlines.iter().flatten().min() {
+                Some(&deadline) => deadline.min(overall_deadline),
+                None => overall_deadline,
+            };
+            let event = match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => {
+                    let now = Instant::now();
+                    if now >= overall_deadline {
+                        break;
+                    }
+                    for deadline in &mut deadlines {
+                        if matches!(*deadline, Some(d) if d <= now) {
+                            *deadline = None;
+                            pending -= 1;
+                        }
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            match event {
+                CollectEvent::Started(i, start) => deadlines[i] = Some(start + self.timeout),
+                CollectEvent::Done(i, res) => {
+                    if deadlines[i].take().is_some() {
+                        results[i] = Some(res);
+                        pending -= 1;
+                    }
+                }
+            }
+        }
+        expired.store(true, Ordering::Relaxed);
+        results
+    }
+}
+
 struct RegistryCore {
-    pub collectors_by_id: HashMap<u64, Box<dyn Collector>>,
+    pub collectors_by_id: HashMap<u64, Arc<dyn Collector>>,
     pub dim_hashes_by_name: HashMap<String, u64>,
     pub desc_ids: HashSet<u64>,
     /// Optional common labels for all registered collectors.
     pub labels: Option<HashMap<String, String>>,
     /// Optional common namespace for all registered collectors.
     pub prefix: Option<String>,
+    /// Hooks run on the gathered metric families, in insertion order.
+    pub gather_hooks: Vec<GatherHook>,
+    /// The number of failed collections, by collector.
+    pub collect_failures: IntCounterVec,
+    /// The number of collections that missed the deadline, by collector.
+    pub collect_timeouts: IntCounterVec,
+    /// Optional pool collecting the collectors in parallel.
+    pub collect_pool: Option<CollectPool>,
+    /// Child registries gathered along with the registered collectors.
+    pub sub_registries: Vec<Registry>,
+    /// The registry this one is a sub-registry of, if any.
+    pub parent: Option<Weak<RwLock<RegistryCore>>>,
+}
+
+impl Default for RegistryCore {
+    fn default() -> RegistryCore {
+        let collect_failures = IntCounterVec::new(
+            Opts::new(
+                COLLECT_FAILURES_NAME,
+                "Total number of failed collections, by collector.",
+            ),
+            &["collector"],
+        )
+        .unwrap();
+        let collect_timeouts = IntCounterVec::new(
+            Opts::new(
+                COLLECT_TIMEOUTS_NAME,
+                "Total number of collections which missed the collection deadline, by collector.",
+            ),
+            &["collector"],
+        )
+        .unwrap();
+
+        RegistryCore {
+            collectors_by_id: HashMap::new(),
+            dim_hashes_by_name: HashMap::new(),
+            desc_ids: HashSet::new(),
+            labels: None,
+            prefix: None,
+            gather_hooks: Vec::new(),
+            collect_failures,
+            collect_timeouts,
+            collect_pool: None,
+            sub_registries: Vec::new(),
+            parent: None,
+        }
+    }
 }
 
 impl std::fmt::Debug for RegistryCore {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "RegistryCore ({} collectors)",
-            self.collectors_by_id.keys().len()
+            "RegistryCore ({} collectors, {} sub-registries, {} gather hooks)",
+            self.collectors_by_id.keys().len(),
+            self.sub_registries.len(),
+            self.gather_hooks.len()
         )
     }
 }
@@ -83,7 +288,7 @@ impl RegistryCore {
         match self.collectors_by_id.entry(collector_id) {
             HEntry::Vacant(vc) => {
                 self.desc_ids.extend(desc_id_set);
-                vc.insert(c);
+                vc.insert(Arc::from(c));
                 Ok(())
             }
             HEntry::Occupied(_) => Err(Error::AlreadyReg),
@@ -94,7 +299,7 @@ impl RegistryCore {
         let mut id_set = Vec::new();
         let mut collector_id: u64 = 0;
         for desc in c.desc() {
-            if !id_set.iter().any(|id| *id == desc.id) {
+            if !id_set.contains(&desc.id) {
                 id_set.push(desc.id);
                 collector_id = collector_id.wrapping_add(desc.id);
             }
@@ -116,37 +321,168 @@ impl RegistryCore {
         Ok(())
     }
 
-    fn gather(&self) -> Vec<proto::MetricFamily> {
-        let mut mf_by_name = BTreeMap::new();
+    /// `exposed_descs` returns the descriptors of the collectors of this
+    /// registry and of its sub-registries, the latter with the prefix and
+    /// labels of the sub-registries applied.
+    fn exposed_descs(&self) -> Result<Vec<Desc>> {
+        let mut descs: Vec<Desc> = self
+            .collectors_by_id
+            .values()
+            .flat_map(|c| c.desc().into_iter().cloned())
+            .collect();
+        for sub in &self.sub_registries {
+            let sub = sub.r.read();
+            for desc in sub.exposed_descs()? {
+                descs.push(sub.expose_desc(&desc)?);
+            }
+        }
+        Ok(descs)
+    }
 
-        for c in self.collectors_by_id.values() {
-            let mfs = c.collect();
-            for mut mf in mfs {
-                // Prune empty MetricFamilies.
-                if mf.get_metric().is_empty() {
-                    continue;
+    /// `expose_desc` applies the prefix and common labels of this registry to
+    /// `desc`, as gathering does to the metrics it describes.
+    fn expose_desc(&self, desc: &Desc) -> Result<Desc> {
+        if self.prefix.is_none() && self.labels.is_none() {
+            return Ok(desc.clone());
+        }
+
+        let fq_name = match self.prefix {
+            Some(ref namespace) => format!("{}_{}", namespace, desc.fq_name),
+            None => desc.fq_name.clone(),
+        };
+        let mut const_labels: HashMap<_, _> = desc
+            .const_label_pairs
+            .iter()
+            .map(|lp| (lp.get_name().to_owned(), lp.get_value().to_owned()))
+            .collect();
+        if let Some(ref labels) = self.labels {
+            for (name, value) in labels {
+                if const_labels.contains_key(name) || desc.variable_labels.contains(name) {
+                    return Err(Error::Msg(format!(
+                        "common label {:?} of the registry is also a label of {:?}",
+                        name, desc.fq_name
+                    )));
                 }
+                const_labels.insert(name.clone(), value.clone());
+            }
+        }
+        Desc::new(
+            fq_name,
+            desc.help.clone(),
+            desc.variable_labels.clone(),
+            const_labels,
+        )
+        // Prefixing the name keeps the unit it ends with.
+        .map(|d| d.set_unit(desc.unit.clone()))
+    }
 
-                let name = mf.get_name().to_owned();
-                match mf_by_name.entry(name) {
-                    BEntry::Vacant(entry) => {
-                        entry.insert(mf);
-                    }
-                    BEntry::Occupied(mut entry) => {
-                        let existent_mf = entry.get_mut();
-                        let existent_metrics = existent_mf.mut_metric();
-
-                        // TODO: check type.
-                        // TODO: check consistency.
-                        for metric in mf.take_metric().into_iter() {
-                            existent_metrics.push(metric);
-                        }
+    fn gather(&self) -> Vec<proto::MetricFamily> {
+        self.gather_impl(false, &|_| true).0
+    }
+
+    /// `gather_impl` gathers the metric families of all collectors. If `check`
+    /// is true, inconsistent families and metrics are reported as errors and
+    /// left out of the result, otherwise they are merged as is.
+    ///
+    /// Only families whose exposed name, i.e. including the registry prefix,
+    /// passes `filter` are gathered. Collectors without any descriptor passing
+    /// `filter` are not collected at all.
+    fn gather_impl(
+        &self,
+        check: bool,
+        filter: &dyn Fn(&str) -> bool,
+    ) -> (Vec<proto::MetricFamily>, Vec<Error>) {
+        let exposed = |name: &str| match self.prefix {
+            Some(ref namespace) => filter(&format!("{}_{}", namespace, name)),
+            None => filter(name),
+        };
+        let mut merger = MetricFamilyMerger::new(check);
+
+        let collectors: Vec<_> = self
+            .collectors_by_id
+            .values()
+            .filter(|c| {
+                // Collectors without descriptors may collect anything.
+                let descs = c.desc();
+                descs.is_empty() || descs.iter().any(|d| exposed(&d.fq_name))
+            })
+            .cloned()
+            .collect();
+        let results = match self.collect_pool {
+            Some(ref pool) => pool.collect(&collectors),
+            None => collectors.iter().map(|c| Some(c.try_collect())).collect(),
+        };
+
+        for (c, res) in collectors.iter().zip(results) {
+            // A collector is named by its first descriptor.
+            let name = || {
+                c.desc()
+                    .first()
+                    .map_or("", |d| d.fq_name.as_str())
+                    .to_owned()
+            };
+            match res {
+                Some(Ok(mfs)) => {
+                    for mf in mfs.into_iter().filter(|mf| exposed(mf.get_name())) {
+                        merger.merge(mf);
                     }
                 }
+                Some(Err(e)) => {
+                    let name = name();
+                    self.collect_failures.with_label_values(&[&name]).inc();
+                    merger.errors.push(Error::Msg(format!(
+                        "collector {:?} failed to collect metrics: {}",
+                        name, e
+                    )));
+                }
+                None => {
+                    let name = name();
+                    self.collect_timeouts.with_label_values(&[&name]).inc();
+                    merger.errors.push(Error::Msg(format!(
+                        "collector {:?} missed the collection deadline",
+                        name
+                    )));
+                }
+            }
+        }
+        // Sub-registries apply their own prefix and labels, then ours are
+        // applied on top below.
+        for sub in &self.sub_registries {
+            let (mfs, errors) = sub.r.read().gather_impl(check, &exposed);
+            merger.errors.extend(errors);
+            for mf in mfs {
+                merger.merge(mf);
             }
         }
 
-        // TODO: metric_family injection hook.
+        // The failure and timeout counters are pruned as empty families until
+        // a collector failed or timed out.
+        let mut mfs = self.collect_failures.collect();
+        mfs.extend(self.collect_timeouts.collect());
+        for mf in mfs.into_iter().filter(|mf| exposed(mf.get_name())) {
+            merger.merge(mf);
+        }
+
+        // Let the hooks add, rewrite or drop families, then merge the result
+        // again as hooks may have introduced duplicate names.
+        if !self.gather_hooks.is_empty() {
+            let mut mfs: Vec<_> = merger.mf_by_name.into_values().collect();
+            for hook in &self.gather_hooks {
+                hook(&mut mfs);
+            }
+
+            let errors = merger.errors;
+            merger = MetricFamilyMerger::new(check);
+            merger.errors = errors;
+            for mf in mfs.into_iter().filter(|mf| exposed(mf.get_name())) {
+                merger.merge(mf);
+            }
+        }
+        let MetricFamilyMerger {
+            mut mf_by_name,
+            errors,
+            ..
+        } = merger;
 
         // Now that MetricFamilies are all set, sort their Metrics
         // lexicographically by their label values.
@@ -182,9 +518,9 @@ impl RegistryCore {
         }
 
         // Write out MetricFamilies sorted by their name.
-        mf_by_name
-            .into_iter()
-            .map(|(_, mut m)| {
+        let mfs = mf_by_name
+            .into_values()
+            .map(|mut m| {
                 // Add registry namespace prefix, if any.
                 if let Some(ref namespace) = self.prefix {
                     let prefixed = format!("{}_{}", namespace, m.get_name());
@@ -211,7 +547,176 @@ impl RegistryCore {
                 }
                 m
             })
-            .collect()
+            .collect();
+
+        (mfs, errors)
+    }
+}
+
+/// `check_exposable` checks that `descs` are consistent with the `exposed`
+/// descriptors of a registry, like [`RegistryCore::register`] does for the
+/// descriptors of its own collectors.
+fn check_exposable(exposed: &[Desc], descs: &[Desc]) -> Result<()> {
+    let desc_ids: HashSet<_> = exposed.iter().map(|d| d.id).collect();
+    let dim_hashes_by_name: HashMap<_, _> = exposed
+        .iter()
+        .map(|d| (d.fq_name.as_str(), d.dim_hash))
+        .collect();
+    for desc in descs {
+        if desc_ids.contains(&desc.id) {
+            return Err(Error::AlreadyReg);
+        }
+        if let Some(hash) = dim_hashes_by_name.get(desc.fq_name.as_str()) {
+            if *hash != desc.dim_hash {
+                return Err(Error::Msg(format!(
+                    "a descriptor registered with a related registry with \
+                     the same fully-qualified name as {:?} has different \
+                     label names or a different help string",
+                    desc
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Merges collected metric families by name, optionally checking them for
+/// consistency.
+struct MetricFamilyMerger {
+    mf_by_name: BTreeMap<String, proto::MetricFamily>,
+    /// Consistency state per family name, `None` if checks are disabled.
+    checks: Option<HashMap<String, FamilyCheck>>,
+    errors: Vec<Error>,
+}
+
+impl MetricFamilyMerger {
+    fn new(check: bool) -> MetricFamilyMerger {
+        MetricFamilyMerger {
+            mf_by_name: BTreeMap::new(),
+            checks: if check { Some(HashMap::new()) } else { None },
+            errors: Vec::new(),
+        }
+    }
+
+    /// `merge` adds `mf` to the merged families, appending its metrics to any
+    /// family of the same name already present. Empty families are pruned.
+    fn merge(&mut self, mut mf: proto::MetricFamily) {
+        // Prune empty MetricFamilies.
+        if mf.get_metric().is_empty() {
+            return;
+        }
+
+        if let Some(checks) = self.checks.as_mut() {
+            let name = mf.get_name();
+            if let Some(existent_mf) = self.mf_by_name.get(name) {
+                if existent_mf.get_field_type() != mf.get_field_type() {
+                    self.errors.push(Error::Msg(format!(
+                        "collected metric family {:?} has type {:?} but should have {:?}",
+                        name,
+                        mf.get_field_type(),
+                        existent_mf.get_field_type()
+                    )));
+                    return;
+                }
+                if existent_mf.get_help() != mf.get_help() {
+                    self.errors.push(Error::Msg(format!(
+                        "collected metric family {:?} has help {:?} but should have {:?}",
+                        name,
+                        mf.get_help(),
+                        existent_mf.get_help()
+                    )));
+                    return;
+                }
+                if existent_mf.get_unit() != mf.get_unit() {
+                    self.errors.push(Error::Msg(format!(
+                        "collected metric family {:?} has unit {:?} but should have {:?}",
+                        name,
+                        mf.get_unit(),
+                        existent_mf.get_unit()
+                    )));
+                    return;
+                }
+            }
+
+            let check = checks.entry(name.to_owned()).or_default();
+            let errors = &mut self.errors;
+            let metrics: Vec<_> = mf
+                .take_metric()
+                .into_iter()
+                .filter(|m| match check.check_metric(mf.get_name(), m) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        errors.push(e);
+                        false
+                    }
+                })
+                .collect();
+            if metrics.is_empty() {
+                return;
+            }
+            mf.set_metric(from_vec!(metrics));
+        }
+
+        let name = mf.get_name().to_owned();
+        match self.mf_by_name.entry(name) {
+            BEntry::Vacant(entry) => {
+                entry.insert(mf);
+            }
+            BEntry::Occupied(mut entry) => {
+                let existent_mf = entry.get_mut();
+                let existent_metrics = existent_mf.mut_metric();
+
+                for metric in mf.take_metric().into_iter() {
+                    existent_metrics.push(metric);
+                }
+            }
+        }
+    }
+}
+
+/// The consistency state of a single metric family while merging.
+#[derive(Default)]
+struct FamilyCheck {
+    /// The sorted label names of the first metric of the family.
+    label_names: Option<Vec<String>>,
+    /// The label values, sorted by label name, of every metric seen so far.
+    label_values: HashSet<Vec<String>>,
+}
+
+impl FamilyCheck {
+    /// `check_metric` verifies that `m` has the same label names as the
+    /// previous metrics of the family and that its label values are unique.
+    fn check_metric(&mut self, name: &str, m: &proto::Metric) -> Result<()> {
+        let mut pairs: Vec<_> = m
+            .get_label()
+            .iter()
+            .map(|lp| (lp.get_name(), lp.get_value()))
+            .collect();
+        pairs.sort_unstable();
+
+        let names: Vec<String> = pairs.iter().map(|(n, _)| (*n).to_owned()).collect();
+        match self.label_names {
+            None => self.label_names = Some(names),
+            Some(ref expected) if *expected != names => {
+                return Err(Error::Msg(format!(
+                    "collected metric {:?} with labels {:?} has label names inconsistent \
+                     with previously collected metrics in the same metric family, \
+                     expected {:?}",
+                    name, pairs, expected
+                )));
+            }
+            Some(_) => {}
+        }
+
+        let values: Vec<String> = pairs.iter().map(|(_, v)| (*v).to_owned()).collect();
+        if !self.label_values.insert(values) {
+            return Err(Error::Msg(format!(
+                "collected metric {:?} with labels {:?} was collected before with the \
+                 same label values",
+                name, pairs
+            )));
+        }
+        Ok(())
     }
 }
 
@@ -257,10 +762,50 @@ impl Registry {
     /// If the provided [`Collector`] is equal to a [`Collector`] already registered
     /// (which includes the case of re-registering the same [`Collector`]), the
     /// AlreadyReg error returns.
+    ///
+    /// The descriptors are checked against all registries they are gathered
+    /// with, see [`sub_registry`](Registry::sub_registry).
     pub fn register(&self, c: Box<dyn Collector>) -> Result<()> {
+        let ancestors = self.ancestors();
+        let root = match ancestors.last() {
+            Some(root) => root,
+            None => {
+                let mut core = self.r.write();
+                if !core.sub_registries.is_empty() {
+                    let descs: Vec<_> = c.desc().into_iter().cloned().collect();
+                    check_exposable(&core.exposed_descs()?, &descs)?;
+                }
+                return core.register(c);
+            }
+        };
+
+        // Registries are locked from the root down, like gathering does. The
+        // root is locked throughout to serialize the registrations below it.
+        let root_core = root.r.write();
+        let mut descs: Vec<_> = c.desc().into_iter().cloned().collect();
+        for r in iter::once(self).chain(&ancestors[..ancestors.len() - 1]) {
+            let core = r.r.read();
+            descs = descs
+                .iter()
+                .map(|d| core.expose_desc(d))
+                .collect::<Result<_>>()?;
+        }
+        check_exposable(&root_core.exposed_descs()?, &descs)?;
         self.r.write().register(c)
     }
 
+    /// `ancestors` returns the registries this one is a sub-registry of, from
+    /// its parent up to the root.
+    fn ancestors(&self) -> Vec<Registry> {
+        let mut ancestors = Vec::new();
+        let mut parent = self.r.read().parent.as_ref().and_then(Weak::upgrade);
+        while let Some(r) = parent {
+            parent = r.read().parent.as_ref().and_then(Weak::upgrade);
+            ancestors.push(Registry { r });
+        }
+        ancestors
+    }
+
     /// `unregister` unregisters the [`Collector`] that equals the [`Collector`] passed
     /// in as an argument.  (Two Collectors are considered equal if their
     /// Describe method yields the same set of descriptors.) The function
@@ -269,12 +814,190 @@ impl Registry {
         self.r.write().unregister(c)
     }
 
+    /// `sub_registry` creates a child registry with an optional `prefix` and
+    /// common `labels`, e.g. for a library to register its collectors without
+    /// knowing about the registry of the application.
+    ///
+    /// The child is gathered along with this registry: the names and labels of
+    /// its metrics get the prefix and labels of the child first, then the ones
+    /// of this registry. Collectors registered with the child are checked, with
+    /// that prefix and labels applied, against the collectors of this registry
+    /// and of all its sub-registries. An error is returned if `labels` sets a
+    /// label already set by this registry or one of its parents.
+    ///
+    /// The child and all its collectors can be removed at once with
+    /// [`unregister_sub_registry`](Registry::unregister_sub_registry).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use prometheus::{Counter, Registry};
+    ///
+    /// let r = Registry::new_custom(Some("app".to_owned()), None).unwrap();
+    /// let mut labels = HashMap::new();
+    /// labels.insert("component".to_owned(), "cache".to_owned());
+    /// let sub = r.sub_registry(Some("cache".to_owned()), Some(labels)).unwrap();
+    /// sub.register(Box::new(Counter::new("hits", "help").unwrap())).unwrap();
+    ///
+    /// let mfs = r.gather();
+    /// assert_eq!(mfs[0].get_name(), "app_cache_hits");
+    /// assert_eq!(mfs[0].get_metric()[0].get_label()[0].get_value(), "cache");
+    /// ```
+    pub fn sub_registry(
+        &self,
+        prefix: Option<String>,
+        labels: Option<HashMap<String, String>>,
+    ) -> Result<Registry> {
+        if let Some(ref labels) = labels {
+            for r in iter::once(self).chain(&self.ancestors()) {
+                if let Some(ref common_labels) = r.r.read().labels {
+                    if let Some(name) = labels.keys().find(|n| common_labels.contains_key(*n)) {
+                        return Err(Error::Msg(format!(
+                            "label {:?} of the sub-registry is already set by a parent registry",
+                            name
+                        )));
+                    }
+                }
+            }
+        }
+
+        let sub = Registry::new_custom(prefix, labels)?;
+        sub.r.write().parent = Some(Arc::downgrade(&self.r));
+        self.r.write().sub_registries.push(sub.clone());
+        Ok(sub)
+    }
+
+    /// `unregister_sub_registry` removes a child registry created by
+    /// [`sub_registry`](Registry::sub_registry), so that none of its
+    /// collectors are gathered anymore. The function returns an error if `sub`
+    /// is not a child of this registry.
+    pub fn unregister_sub_registry(&self, sub: &Registry) -> Result<()> {
+        let mut core = self.r.write();
+        match core
+            .sub_registries
+            .iter()
+            .position(|r| Arc::ptr_eq(&r.r, &sub.r))
+        {
+            Some(i) => {
+                core.sub_registries.remove(i).r.write().parent = None;
+                Ok(())
+            }
+            None => Err(Error::Msg(
+                "sub-registry is not registered with this registry".to_owned(),
+            )),
+        }
+    }
+
+    /// `add_gather_hook` adds a hook which is run by [`gather`](Registry::gather)
+    /// on the collected metric families. Hooks may add, rewrite, relabel or drop
+    /// families and run in the order they were added.
+    ///
+    /// Hooks run before the families are sorted and before the registry prefix
+    /// and common labels are applied. Families sharing a name after the hooks
+    /// ran are merged and empty families are pruned.
+    pub fn add_gather_hook<F>(&self, hook: F)
+    where
+        F: Fn(&mut Vec<proto::MetricFamily>) + Send + Sync + 'static,
+    {
+        self.r.write().gather_hooks.push(Box::new(hook));
+    }
+
+    /// `set_parallel_collection` makes [`gather`](Registry::gather) and its
+    /// variants collect the registered collectors in parallel on a pool of
+    /// `threads` threads, instead of sequentially on the calling thread.
+    ///
+    /// Every collector has to complete within `timeout` of a thread starting
+    /// to collect it. The metrics of collectors missing their deadline are
+    /// left out, reported as errors by [`try_gather`](Registry::try_gather)
+    /// and counted by the `prometheus_collector_timeouts_total` counter,
+    /// labelled with the `collector` name. A collector missing its deadline
+    /// keeps running, and occupying its thread, until it completes. It is not
+    /// collected again before, but reported as timed out by the following
+    /// gathers right away.
+    ///
+    /// A gather waits at most `timeout` times the number of collectors per
+    /// thread, so that collectors queued behind busy threads still get their
+    /// turn. Collectors not started by then are reported as timed out as
+    /// well.
+    ///
+    /// Calling it again replaces the pool, an error is returned if `threads`
+    /// is 0 or the threads can not be spawned.
+    pub fn set_parallel_collection(&self, threads: usize, timeout: Duration) -> Result<()> {
+        if threads == 0 {
+            return Err(Error::Msg(
+                "parallel collection needs at least one thread".to_owned(),
+            ));
+        }
+        let pool = CollectPool::new(threads, timeout)?;
+        self.r.write().collect_pool = Some(pool);
+        Ok(())
+    }
+
     /// `gather` calls the Collect method of the registered Collectors and then
     /// gathers the collected metrics into a lexicographically sorted slice
     /// of MetricFamily protobufs.
+    ///
+    /// Families of the same name are merged without any checks, use
+    /// [`try_gather`](Registry::try_gather) to detect inconsistent metrics.
+    ///
+    /// Collectors are collected through [`Collector::try_collect`]. The
+    /// metrics of failing collectors are left out and each failure increments
+    /// the `prometheus_collector_failures_total` counter, labelled with the
+    /// `collector` name, i.e. the fully-qualified name of its first
+    /// descriptor. The counter is only exposed once a collection failed.
     pub fn gather(&self) -> Vec<proto::MetricFamily> {
         self.r.read().gather()
     }
+
+    /// `gather_filtered` works like [`gather`](Registry::gather) but only
+    /// gathers the metric families whose name passes `filter`, e.g. to serve
+    /// the `name[]` parameters of a federation scrape. The names passed to
+    /// `filter` include the registry prefix, if any.
+    ///
+    /// Collectors none of whose descriptors pass `filter` are skipped without
+    /// being collected, so gathering a single family doesn't pay for unrelated
+    /// expensive collectors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashSet;
+    /// use prometheus::{Counter, Registry};
+    ///
+    /// let r = Registry::new();
+    /// r.register(Box::new(Counter::new("requests", "help").unwrap())).unwrap();
+    /// r.register(Box::new(Counter::new("errors", "help").unwrap())).unwrap();
+    ///
+    /// let names: HashSet<_> = vec!["requests"].into_iter().collect();
+    /// let mfs = r.gather_filtered(|name| names.contains(name));
+    /// assert_eq!(mfs.len(), 1);
+    /// assert_eq!(mfs[0].get_name(), "requests");
+    /// ```
+    pub fn gather_filtered<F>(&self, filter: F) -> Vec<proto::MetricFamily>
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.r.read().gather_impl(false, &filter).0
+    }
+
+    /// `try_gather` works like [`gather`](Registry::gather) but additionally
+    /// checks the collected metrics for consistency: families of the same name
+    /// must have the same type and help string, all metrics of a family must
+    /// have the same label names and no two metrics of a family may have the
+    /// same label values.
+    ///
+    /// If any check or collector fails, all violations and collection errors
+    /// are returned as [`GatherErrors`], together with the metric families
+    /// gathered without the offending metrics and failed collectors.
+    pub fn try_gather(&self) -> std::result::Result<Vec<proto::MetricFamily>, GatherErrors> {
+        let (mfs, errors) = self.r.read().gather_impl(true, &|_| true);
+        if errors.is_empty() {
+            Ok(mfs)
+        } else {
+            Err(GatherErrors::new(errors, mfs))
+        }
+    }
 }
 
 cfg_if! {
@@ -332,14 +1055,31 @@ pub fn gather() -> Vec<proto::MetricFamily> {
     DEFAULT_REGISTRY.gather()
 }
 
+/// Return the `MetricFamily` of `DEFAULT_REGISTRY` whose name passes `filter`.
+/// See [`Registry::gather_filtered`].
+pub fn gather_filtered<F>(filter: F) -> Vec<proto::MetricFamily>
+where
+    F: Fn(&str) -> bool,
+{
+    DEFAULT_REGISTRY.gather_filtered(filter)
+}
+
+/// Return all `MetricFamily` of `DEFAULT_REGISTRY`, checked for consistency.
+/// See [`Registry::try_gather`].
+pub fn try_gather() -> std::result::Result<Vec<proto::MetricFamily>, GatherErrors> {
+    DEFAULT_REGISTRY.try_gather()
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::thread;
 
     use super::*;
     use crate::counter::{Counter, CounterVec};
     use crate::desc::Desc;
+    use crate::gauge::Gauge;
     use crate::metrics::{Collector, Opts};
     use crate::proto;
 
@@ -562,4 +1302,425 @@ mod tests {
         counter_vec.with_label_values(&["1", "2"]).inc();
         assert!(!r.gather().is_empty());
     }
+
+    #[test]
+    fn test_gather_hooks() {
+        let r = Registry::new_custom(Some("common".to_string()), None).unwrap();
+        let counter_a = Counter::new("test_a_counter", "test help").unwrap();
+        let counter_b = Counter::new("test_b_counter", "test help").unwrap();
+        r.register(Box::new(counter_a.clone())).unwrap();
+        r.register(Box::new(counter_b.clone())).unwrap();
+        counter_a.inc();
+        counter_b.inc();
+
+        // Drop a family.
+        r.add_gather_hook(|mfs| mfs.retain(|mf| mf.get_name() != "test_a_counter"));
+        // Inject a family whose name sorts first, plus a duplicate of an
+        // existing one which gets merged.
+        let injected = Counter::new("test_0_info", "test help").unwrap();
+        let duplicate = CounterVec::new(Opts::new("test_b_counter", "test help"), &["x"]).unwrap();
+        duplicate.with_label_values(&["y"]).inc();
+        r.add_gather_hook(move |mfs| {
+            mfs.extend(injected.collect());
+            mfs.extend(duplicate.collect());
+        });
+        // Hooks run in order, so this one sees the injected families.
+        r.add_gather_hook(|mfs| {
+            for mf in mfs.iter_mut() {
+                if mf.get_name() == "test_0_info" {
+                    mf.set_help("rewritten".to_string());
+                }
+            }
+        });
+
+        let mfs = r.gather();
+        assert_eq!(mfs.len(), 2);
+        assert_eq!(mfs[0].get_name(), "common_test_0_info");
+        assert_eq!(mfs[0].get_help(), "rewritten");
+        assert_eq!(mfs[1].get_name(), "common_test_b_counter");
+        assert_eq!(mfs[1].get_metric().len(), 2);
+        assert!(mfs[1].get_metric()[0].get_label().is_empty());
+    }
+
+    #[test]
+    fn test_register_unit_mismatch() {
+        let r = Registry::new();
+        let opts = Opts::new("test_duration_seconds", "test help").const_label("a", "1");
+        let counter = Counter::with_opts(opts.clone().unit("seconds")).unwrap();
+        r.register(Box::new(counter)).unwrap();
+
+        let counter = Counter::with_opts(opts.const_label("a", "2")).unwrap();
+        assert!(r.register(Box::new(counter)).is_err());
+
+        let mfs = r.gather();
+        assert_eq!(mfs[0].get_unit(), "seconds");
+    }
+
+    #[test]
+    fn test_register_unit_with_prefix() {
+        let r = Registry::new_custom(Some("common".to_owned()), None).unwrap();
+        let opts = Opts::new("test_duration_seconds_total", "test help").unit("seconds");
+        let counter = Counter::with_opts(opts).unwrap();
+        r.register(Box::new(counter)).unwrap();
+
+        let mfs = r.gather();
+        assert_eq!(mfs[0].get_name(), "common_test_duration_seconds_total");
+        assert_eq!(mfs[0].get_unit(), "seconds");
+    }
+
+    #[test]
+    fn test_try_gather() {
+        let r = Registry::new();
+        let counter_vec = CounterVec::new(Opts::new("test_vec", "test help"), &["a"]).unwrap();
+        r.register(Box::new(counter_vec.clone())).unwrap();
+        counter_vec.with_label_values(&["1"]).inc();
+        counter_vec.with_label_values(&["2"]).inc();
+
+        let gauge = Gauge::new("test_gauge", "test help").unwrap();
+        r.register(Box::new(gauge.clone())).unwrap();
+        assert_eq!(r.try_gather().unwrap().len(), 2);
+
+        // Families injected by a hook are checked as well.
+        let wrong_type = Gauge::new("test_vec", "test help").unwrap();
+        let wrong_help = CounterVec::new(Opts::new("test_vec", "other help"), &["a"]).unwrap();
+        wrong_help.with_label_values(&["3"]).inc();
+        let wrong_names = CounterVec::new(Opts::new("test_vec", "test help"), &["b"]).unwrap();
+        wrong_names.with_label_values(&["3"]).inc();
+        let duplicate = CounterVec::new(Opts::new("test_vec", "test help"), &["a"]).unwrap();
+        duplicate.with_label_values(&["1"]).inc();
+        duplicate.with_label_values(&["3"]).inc();
+        r.add_gather_hook(move |mfs| {
+            mfs.extend(wrong_type.collect());
+            mfs.extend(wrong_help.collect());
+            mfs.extend(wrong_names.collect());
+            mfs.extend(duplicate.collect());
+        });
+
+        let errs = r.try_gather().unwrap_err();
+        assert_eq!(errs.errors().len(), 4, "{}", errs);
+        let msg = errs.to_string();
+        assert!(msg.starts_with("4 error(s) occurred during gathering:"));
+        assert!(msg.contains("has type GAUGE but should have COUNTER"));
+        assert!(msg.contains("has help \"other help\""));
+        assert!(msg.contains("label names inconsistent"));
+        assert!(msg.contains("was collected before with the same label values"));
+
+        // Only the consistent metrics are kept.
+        let mfs = errs.into_metric_families();
+        assert_eq!(mfs.len(), 2);
+        assert_eq!(mfs[1].get_name(), "test_vec");
+        let values: Vec<_> = mfs[1]
+            .get_metric()
+            .iter()
+            .map(|m| m.get_label()[0].get_value())
+            .collect();
+        assert_eq!(values, vec!["1", "2", "3"]);
+
+        // `gather` keeps merging without any checks.
+        let mfs = r.gather();
+        assert_eq!(mfs[1].get_metric().len(), 7);
+    }
+
+    struct FailingCollector {
+        desc: Desc,
+    }
+
+    impl Collector for FailingCollector {
+        fn desc(&self) -> Vec<&Desc> {
+            vec![&self.desc]
+        }
+
+        fn collect(&self) -> Vec<proto::MetricFamily> {
+            Vec::new()
+        }
+
+        fn try_collect(&self) -> Result<Vec<proto::MetricFamily>> {
+            Err(Error::Msg("source unavailable".to_owned()))
+        }
+    }
+
+    #[test]
+    fn test_gather_collector_failure() {
+        let r = Registry::new();
+        let counter = Counter::new("test_counter", "test help").unwrap();
+        r.register(Box::new(counter)).unwrap();
+        assert_eq!(r.gather().len(), 1);
+
+        let desc = Desc::new(
+            "test_failing".to_owned(),
+            "test help".to_owned(),
+            vec![],
+            HashMap::new(),
+        )
+        .unwrap();
+        r.register(Box::new(FailingCollector { desc })).unwrap();
+
+        let errs = r.try_gather().unwrap_err();
+        assert_eq!(errs.errors().len(), 1);
+        assert!(errs.to_string().contains(
+            "collector \"test_failing\" failed to collect metrics: Error: source unavailable"
+        ));
+
+        // The healthy families are still gathered, along with the failures.
+        let mfs = r.gather();
+        assert_eq!(mfs.len(), 2);
+        assert_eq!(mfs[0].get_name(), COLLECT_FAILURES_NAME);
+        let m = &mfs[0].get_metric()[0];
+        assert_eq!(m.get_label()[0].get_value(), "test_failing");
+        assert_eq!(m.get_counter().get_value() as u64, 2);
+        assert_eq!(mfs[1].get_name(), "test_counter");
+    }
+
+    struct CountingCollector {
+        counter: Counter,
+        collected: Arc<AtomicUsize>,
+    }
+
+    impl Collector for CountingCollector {
+        fn desc(&self) -> Vec<&Desc> {
+            self.counter.desc()
+        }
+
+        fn collect(&self) -> Vec<proto::MetricFamily> {
+            self.collected.fetch_add(1, Ordering::SeqCst);
+            self.counter.collect()
+        }
+    }
+
+    #[test]
+    fn test_gather_filtered() {
+        let r = Registry::new_custom(Some("ns".to_owned()), None).unwrap();
+        let collected = Arc::new(AtomicUsize::new(0));
+        let expensive = CountingCollector {
+            counter: Counter::new("expensive", "test help").unwrap(),
+            collected: collected.clone(),
+        };
+        r.register(Box::new(expensive)).unwrap();
+        let counter_vec = CounterVec::new(Opts::new("cheap", "test help"), &["a"]).unwrap();
+        counter_vec.with_label_values(&["1"]).inc();
+        r.register(Box::new(counter_vec)).unwrap();
+
+        let mfs = r.gather_filtered(|name| name == "ns_cheap");
+        assert_eq!(mfs.len(), 1);
+        assert_eq!(mfs[0].get_name(), "ns_cheap");
+        assert_eq!(collected.load(Ordering::SeqCst), 0);
+
+        // Families injected by hooks are filtered as well.
+        r.add_gather_hook(|mfs| {
+            mfs.extend(Gauge::new("injected", "test help").unwrap().collect());
+        });
+        assert!(r.gather_filtered(|name| name == "cheap").is_empty());
+        let mfs = r.gather_filtered(|name| name != "ns_cheap");
+        let names: Vec<_> = mfs.iter().map(|mf| mf.get_name()).collect();
+        assert_eq!(names, vec!["ns_expensive", "ns_injected"]);
+        assert_eq!(collected.load(Ordering::SeqCst), 1);
+    }
+
+    struct SlowCollector {
+        counter: Counter,
+        delay: Duration,
+    }
+
+    impl Collector for SlowCollector {
+        fn desc(&self) -> Vec<&Desc> {
+            self.counter.desc()
+        }
+
+        fn collect(&self) -> Vec<proto::MetricFamily> {
+            thread::sleep(self.delay);
+            self.counter.collect()
+        }
+    }
+
+    #[test]
+    fn test_parallel_collection() {
+        let r = Registry::new();
+        assert!(r
+            .set_parallel_collection(0, Duration::from_secs(1))
+            .is_err());
+        r.set_parallel_collection(2, Duration::from_millis(200))
+            .unwrap();
+
+        for i in 0..4 {
+            let counter = Counter::new(format!("test_counter_{}", i), "test help").unwrap();
+            r.register(Box::new(counter)).unwrap();
+        }
+        assert_eq!(r.try_gather().unwrap().len(), 4);
+
+        let slow = SlowCollector {
+            counter: Counter::new("test_slow", "test help").unwrap(),
+            delay: Duration::from_secs(2),
+        };
+        r.register(Box::new(slow)).unwrap();
+
+        let start = Instant::now();
+        let errs = r.try_gather().unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(errs.errors().len(), 1);
+        assert!(errs
+            .to_string()
+            .contains("collector \"test_slow\" missed the collection deadline"));
+
+        // The other collectors are still gathered, along with the timeouts.
+        let mfs = errs.into_metric_families();
+        assert_eq!(mfs.len(), 5);
+        assert_eq!(mfs[0].get_name(), COLLECT_TIMEOUTS_NAME);
+        let m = &mfs[0].get_metric()[0];
+        assert_eq!(m.get_label()[0].get_value(), "test_slow");
+        assert_eq!(m.get_counter().get_value() as u64, 1);
+        assert!(mfs.iter().all(|mf| mf.get_name() != "test_slow"));
+
+        // The timeout of a collector only starts once a thread is free.
+        let r = Registry::new();
+        r.set_parallel_collection(1, Duration::from_millis(300))
+            .unwrap();
+        for i in 0..4 {
+            let slow = SlowCollector {
+                counter: Counter::new(format!("test_slow_{}", i), "test help").unwrap(),
+                delay: Duration::from_millis(100),
+            };
+            r.register(Box::new(slow)).unwrap();
+        }
+        assert_eq!(r.try_gather().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_parallel_collection_stuck_collector() {
+        let r = Registry::new();
+        r.set_parallel_collection(1, Duration::from_millis(100))
+            .unwrap();
+        let stuck = SlowCollector {
+            counter: Counter::new("test_stuck", "test help").unwrap(),
+            delay: Duration::from_secs(3600),
+        };
+        r.register(Box::new(stuck)).unwrap();
+        let counter = Counter::new("test_counter", "test help").unwrap();
+        r.register(Box::new(counter)).unwrap();
+
+        // Gathers neither wait for the stuck collector nor for the collectors
+        // queued behind it, and do not queue it again.
+        let mut mfs = vec![];
+        for _ in 0..3 {
+            let start = Instant::now();
+            let errs = r.try_gather().unwrap_err();
+            assert!(start.elapsed() < Duration::from_secs(1));
+            assert!(errs
+                .to_string()
+                .contains("collector \"test_stuck\" missed the collection deadline"));
+            mfs = errs.into_metric_families();
+        }
+        let timeouts = mfs
+            .iter()
+            .find(|mf| mf.get_name() == COLLECT_TIMEOUTS_NAME)
+            .unwrap();
+        let m = timeouts
+            .get_metric()
+            .iter()
+            .find(|m| m.get_label()[0].get_value() == "test_stuck")
+            .unwrap();
+        assert_eq!(m.get_counter().get_value() as u64, 3);
+
+        let gauge = Gauge::new("test_gauge", "test help").unwrap();
+        r.register(Box::new(gauge)).unwrap();
+    }
+
+    #[test]
+    fn test_sub_registry() {
+        let r = Registry::new_custom(Some("app".to_owned()), None).unwrap();
+        let counter = Counter::new("requests", "test help").unwrap();
+        r.register(Box::new(counter)).unwrap();
+
+        let mut labels = HashMap::new();
+        labels.insert("component".to_owned(), "a".to_owned());
+        let sub = r
+            .sub_registry(Some("lib".to_owned()), Some(labels))
+            .unwrap();
+        let counter = Counter::new("requests", "test help").unwrap();
+        sub.register(Box::new(counter)).unwrap();
+        let nested = sub.sub_registry(Some("inner".to_owned()), None).unwrap();
+        let gauge = Gauge::new("size", "test help").unwrap();
+        nested.register(Box::new(gauge)).unwrap();
+        assert!(r.sub_registry(Some("".to_owned()), None).is_err());
+
+        let mfs = r.try_gather().unwrap();
+        let names: Vec<_> = mfs.iter().map(|mf| mf.get_name()).collect();
+        assert_eq!(
+            names,
+            vec!["app_lib_inner_size", "app_lib_requests", "app_requests"]
+        );
+        for mf in &mfs[..2] {
+            let label = &mf.get_metric()[0].get_label()[0];
+            assert_eq!(label.get_name(), "component");
+            assert_eq!(label.get_value(), "a");
+        }
+        assert!(mfs[2].get_metric()[0].get_label().is_empty());
+
+        // The sub-registry is gathered on its own without the parent's prefix.
+        let mfs = sub.gather();
+        assert_eq!(mfs[1].get_name(), "lib_requests");
+
+        let mfs = r.gather_filtered(|name| name == "app_lib_requests");
+        assert_eq!(mfs.len(), 1);
+
+        r.unregister_sub_registry(&sub).unwrap();
+        assert!(r.unregister_sub_registry(&sub).is_err());
+        assert!(r.unregister_sub_registry(&nested).is_err());
+        let mfs = r.gather();
+        assert_eq!(mfs.len(), 1);
+        assert_eq!(mfs[0].get_name(), "app_requests");
+    }
+
+    #[test]
+    fn test_sub_registry_conflicts() {
+        let mut labels = HashMap::new();
+        labels.insert("env".to_owned(), "prod".to_owned());
+        let r = Registry::new_custom(None, Some(labels.clone())).unwrap();
+        let counter = Counter::new("lib_requests", "test help").unwrap();
+        r.register(Box::new(counter)).unwrap();
+
+        // Labels set by a parent can not be set again.
+        assert!(r.sub_registry(None, Some(labels.clone())).is_err());
+        let labels_of = |value: &str| {
+            let mut labels = HashMap::new();
+            labels.insert("component".to_owned(), value.to_owned());
+            Some(labels)
+        };
+        let sub = r
+            .sub_registry(Some("lib".to_owned()), labels_of("a"))
+            .unwrap();
+        assert!(sub.sub_registry(None, Some(labels)).is_err());
+
+        // Prefixed descriptors are checked against the parent.
+        let counter = Counter::new("requests", "test help").unwrap();
+        assert!(sub.register(Box::new(counter)).is_err());
+        let vec = CounterVec::new(Opts::new("hits", "test help"), &["component"]).unwrap();
+        assert!(sub.register(Box::new(vec)).is_err());
+        let gauge = Gauge::new("size", "test help").unwrap();
+        sub.register(Box::new(gauge)).unwrap();
+
+        // And against sibling sub-registries, the parent against its children.
+        let sibling = r
+            .sub_registry(Some("lib".to_owned()), labels_of("a"))
+            .unwrap();
+        let gauge = Gauge::new("size", "test help").unwrap();
+        match sibling.register(Box::new(gauge)) {
+            Err(Error::AlreadyReg) => {}
+            res => panic!("expected AlreadyReg, got {:?}", res),
+        }
+        let sibling = r
+            .sub_registry(Some("lib".to_owned()), labels_of("b"))
+            .unwrap();
+        let gauge = Gauge::new("size", "test help").unwrap();
+        sibling.register(Box::new(gauge)).unwrap();
+        let gauge =
+            Gauge::with_opts(Opts::new("lib_size", "test help").const_label("component", "a"))
+                .unwrap();
+        assert!(r.register(Box::new(gauge)).is_err());
+        assert!(r.try_gather().is_ok());
+
+        // A removed sub-registry is on its own.
+        r.unregister_sub_registry(&sub).unwrap();
+        let counter = Counter::new("requests", "test help").unwrap();
+        sub.register(Box::new(counter)).unwrap();
+    }
 }
@@ -0,0 +1,280 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::desc::{is_valid_label_name, Desc, Describer};
+use crate::errors::{Error, Result};
+use crate::metrics::{Collector, Opts};
+use crate::proto::{self, LabelPair, MetricFamily, MetricType};
+use crate::value::make_label_pairs;
+
+/// An enumeration of the states of a [`StateSet`].
+///
+/// # Examples
+///
+/// ```
+/// use prometheus::core::State;
+///
+/// #[derive(Clone, Copy, PartialEq)]
+/// enum Health {
+///     Up,
+///     Degraded,
+///     Down,
+/// }
+///
+/// impl State for Health {
+///     fn states() -> &'static [Health] {
+///         &[Health::Up, Health::Degraded, Health::Down]
+///     }
+///
+///     fn name(&self) -> &'static str {
+///         match self {
+///             Health::Up => "up",
+///             Health::Degraded => "degraded",
+///             Health::Down => "down",
+///         }
+///     }
+/// }
+/// ```
+pub trait State: Copy + PartialEq + Send + Sync + 'static {
+    /// Return all states, in the order they are exposed.
+    fn states() -> &'static [Self];
+
+    /// Return the label value exposing this state.
+    fn name(&self) -> &'static str;
+}
+
+/// A [`Collector`] exposing which one of a fixed set of states is currently
+/// active, e.g. the health of a component.
+///
+/// Every state is exposed as a sample labelled with the state name under a
+/// label named like the metric, with the value 1 for the active state and 0
+/// for all others. OpenMetrics output exposes it with the `stateset` type,
+/// the other formats as a gauge.
+#[derive(Clone)]
+pub struct StateSet<E: State> {
+    core: Arc<StateSetCore>,
+    _phantom: PhantomData<E>,
+}
+
+struct StateSetCore {
+    desc: Desc,
+    /// The label pairs of each state, in the order of `State::states`.
+    label_pairs: Vec<Vec<LabelPair>>,
+    /// The index of the active state.
+    active: AtomicUsize,
+}
+
+impl<E: State> StateSet<E> {
+    /// Create a [`StateSet`] with the `name` and `help` arguments and the
+    /// `initial` state active.
+    pub fn new<S1: Into<String>, S2: Into<String>>(
+        name: S1,
+        help: S2,
+        initial: E,
+    ) -> Result<StateSet<E>> {
+        StateSet::with_opts(Opts::new(name, help), initial)
+    }
+
+    /// Create a [`StateSet`] with the `opts` options and the `initial` state
+    /// active. The fully-qualified name must be a valid label name, as it
+    /// names the label carrying the states.
+    pub fn with_opts(opts: Opts, initial: E) -> Result<StateSet<E>> {
+        if !opts.variable_labels.is_empty() {
+            return Err(Error::Msg(format!(
+                "state set {} must not have variable labels",
+                opts.fq_name()
+            )));
+        }
+
+        if !opts.unit.is_empty() {
+            return Err(Error::Msg(format!(
+                "state set {} must not have a unit",
+                opts.fq_name()
+            )));
+        }
+
+        let fq_name = opts.fq_name();
+        if !is_valid_label_name(&fq_name) {
+            return Err(Error::Msg(format!(
+                "'{}' is not a valid label name for the states",
+                fq_name
+            )));
+        }
+        if opts.const_labels.contains_key(&fq_name) {
+            return Err(Error::Msg(format!(
+                "state set {} has a const label of the same name",
+                fq_name
+            )));
+        }
+
+        let mut names = HashSet::new();
+        if let Some(state) = E::states().iter().find(|s| !names.insert(s.name())) {
+            return Err(Error::Msg(format!(
+                "state set {} has duplicate state {}",
+                fq_name,
+                state.name()
+            )));
+        }
+
+        let desc = opts.variable_label(fq_name).describe()?;
+        let label_pairs = E::states()
+            .iter()
+            .map(|s| make_label_pairs(&desc, &[s.name()]))
+            .collect::<Result<_>>()?;
+
+        Ok(StateSet {
+            core: Arc::new(StateSetCore {
+                desc,
+                label_pairs,
+                active: AtomicUsize::new(Self::index(initial)),
+            }),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Make `state` the active state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `state` is not listed by [`State::states`].
+    pub fn set(&self, state: E) {
+        self.core
+            .active
+            .store(Self::index(state), Ordering::Relaxed);
+    }
+
+    /// Return the active state.
+    pub fn get(&self) -> E {
+        E::states()[self.core.active.load(Ordering::Relaxed)]
+    }
+
+    fn index(state: E) -> usize {
+        E::states()
+            .iter()
+            .position(|s| *s == state)
+            .expect("state not listed by State::states")
+    }
+}
+
+impl<E: State> Collector for StateSet<E> {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.core.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let active = self.core.active.load(Ordering::Relaxed);
+        let metrics = self
+            .core
+            .label_pairs
+            .iter()
+            .enumerate()
+            .map(|(i, label_pairs)| {
+                let mut m = proto::Metric::default();
+                m.set_label(from_vec!(label_pairs.clone()));
+                let mut gauge = proto::Gauge::default();
+                gauge.set_value(if i == active { 1.0 } else { 0.0 });
+                m.set_gauge(gauge);
+                m
+            })
+            .collect();
+
+        let mut mf = MetricFamily::default();
+        mf.set_name(self.core.desc.fq_name.clone());
+        mf.set_help(self.core.desc.help.clone());
+        mf.set_field_type(MetricType::STATESET);
+        mf.set_metric(from_vec!(metrics));
+        vec![mf]
+    }
+}
+
+impl<E: State> std::fmt::Debug for StateSet<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "StateSet {{ {}: {} }}",
+            self.core.desc.fq_name,
+            self.get().name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{OpenMetricsEncoder, TextEncoder};
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Health {
+        Up,
+        Down,
+    }
+
+    impl State for Health {
+        fn states() -> &'static [Health] {
+            &[Health::Up, Health::Down]
+        }
+
+        fn name(&self) -> &'static str {
+            match self {
+                Health::Up => "up",
+                Health::Down => "down",
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    struct Duplicate;
+
+    impl State for Duplicate {
+        fn states() -> &'static [Duplicate] {
+            &[Duplicate, Duplicate]
+        }
+
+        fn name(&self) -> &'static str {
+            "same"
+        }
+    }
+
+    #[test]
+    fn test_state_set() {
+        let opts = Opts::new("health", "Health of the component.").const_label("c", "1");
+        let state_set = StateSet::with_opts(opts, Health::Up).unwrap();
+        assert_eq!(state_set.get(), Health::Up);
+        state_set.set(Health::Down);
+        assert_eq!(state_set.get(), Health::Down);
+
+        let mfs = state_set.collect();
+        let expected = r#"# HELP health Health of the component.
+# TYPE health gauge
+health{c="1",health="up"} 0
+health{c="1",health="down"} 1
+"#;
+        assert_eq!(TextEncoder::new().encode_to_string(&mfs).unwrap(), expected);
+
+        let expected = r#"# TYPE health stateset
+# HELP health Health of the component.
+health{c="1",health="up"} 0
+health{c="1",health="down"} 1
+# EOF
+"#;
+        assert_eq!(
+            OpenMetricsEncoder::new().encode_to_string(&mfs).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_state_set_invalid() {
+        assert!(StateSet::new("health", "help", Duplicate).is_err());
+        assert!(StateSet::new("a:b", "help", Health::Up).is_err());
+
+        let opts = Opts::new("health", "help").const_label("health", "1");
+        assert!(StateSet::with_opts(opts, Health::Up).is_err());
+        let opts = Opts::new("health", "help").variable_label("a");
+        assert!(StateSet::with_opts(opts, Health::Up).is_err());
+    }
+}
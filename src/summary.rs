@@ -0,0 +1,1445 @@
+// Copyright 2014 The Prometheus Authors
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::From;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant as StdInstant, SystemTime};
+
+use crate::atomic64::{Atomic, AtomicF64, AtomicU64};
+use crate::desc::{Desc, Describer};
+use crate::errors::{Error, Result};
+use crate::histogram::Instant;
+use crate::metrics::{CardinalityPolicy, Collector, LocalMetric, Metric, Opts};
+use crate::proto;
+use crate::value::{make_label_pairs, make_timestamp};
+use crate::vec::{ChildLimits, LabelValuesMap, LastTouched, MetricVec, MetricVecBuilder};
+
+/// The default duration for which observations stay relevant in a
+/// [`Summary`].
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// The default number of buckets used to calculate the age of observations
+/// in a [`Summary`].
+pub const DEFAULT_AGE_BUCKETS: u32 = 5;
+
+/// The default number of observations a [`Summary`] buffers before they are
+/// merged into its quantile streams.
+pub const DEFAULT_BUF_CAP: usize = 500;
+
+/// Used for the label that defines the quantile in a summary.
+pub const QUANTILE_LABEL: &str = "quantile";
+
+#[inline]
+fn check_quantile_label(label: &str) -> Result<()> {
+    if label == QUANTILE_LABEL {
+        return Err(Error::Msg(
+            "`quantile` is not allowed as label name in summaries".to_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn check_and_sort_objectives(mut objectives: Vec<(f64, f64)>) -> Result<Vec<(f64, f64)>> {
+    for &(quantile, error) in &objectives {
+        if !(0.0..=1.0).contains(&quantile) {
+            return Err(Error::Msg(format!(
+                "summary objectives must be in [0, 1], quantile: {}",
+                quantile
+            )));
+        }
+        if !(0.0..=1.0).contains(&error) {
+            return Err(Error::Msg(format!(
+                "summary allowed errors must be in [0, 1], error: {}",
+                error
+            )));
+        }
+    }
+
+    objectives.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    for pair in objectives.windows(2) {
+        if pair[0].0 == pair[1].0 {
+            return Err(Error::Msg(format!(
+                "duplicate summary objective: {}",
+                pair[0].0
+            )));
+        }
+    }
+
+    Ok(objectives)
+}
+
+/// A struct that bundles the options for creating a [`Summary`] metric. It is
+/// mandatory to set Name and Help to a non-empty string. All other fields are
+/// optional and can safely be left at their default value.
+#[derive(Clone, Debug)]
+pub struct SummaryOpts {
+    /// A container holding various options.
+    pub common_opts: Opts,
+
+    /// Defines the quantile rank estimates with their respective absolute
+    /// error, as `(quantile, error)` pairs. For example `(0.5, 0.05)` exposes
+    /// a value between the 45th and the 55th percentile as the median. The
+    /// default is empty, in which case the summary only exposes the sum and
+    /// the count of observations.
+    pub objectives: Vec<(f64, f64)>,
+
+    /// Defines the duration for which an observation stays relevant for the
+    /// summary. Must be positive. The default value is [`DEFAULT_MAX_AGE`].
+    pub max_age: Duration,
+
+    /// The number of buckets used to exclude observations that are older than
+    /// `max_age` from the summary. A higher number has a resource penalty, so
+    /// only increase it if the higher resolution is really required. The
+    /// default value is [`DEFAULT_AGE_BUCKETS`].
+    pub age_buckets: u32,
+
+    /// The size of the buffer used to collect observations before merging
+    /// them into the quantile streams. The default value is
+    /// [`DEFAULT_BUF_CAP`].
+    pub buf_cap: usize,
+}
+
+impl SummaryOpts {
+    /// Create a [`SummaryOpts`] with the `name` and `help` arguments.
+    pub fn new<S1: Into<String>, S2: Into<String>>(name: S1, help: S2) -> SummaryOpts {
+        SummaryOpts::from(Opts::new(name, help))
+    }
+
+    /// `namespace` sets the namespace.
+    pub fn namespace<S: Into<String>>(mut self, namespace: S) -> Self {
+        self.common_opts.namespace = namespace.into();
+        self
+    }
+
+    /// `subsystem` sets the sub system.
+    pub fn subsystem<S: Into<String>>(mut self, subsystem: S) -> Self {
+        self.common_opts.subsystem = subsystem.into();
+        self
+    }
+
+    /// `const_labels` sets the const labels.
+    pub fn const_labels(mut self, const_labels: HashMap<String, String>) -> Self {
+        self.common_opts = self.common_opts.const_labels(const_labels);
+        self
+    }
+
+    /// `const_label` adds a const label.
+    pub fn const_label<S1: Into<String>, S2: Into<String>>(mut self, name: S1, value: S2) -> Self {
+        self.common_opts = self.common_opts.const_label(name, value);
+        self
+    }
+
+    /// `variable_labels` sets the variable labels.
+    pub fn variable_labels(mut self, variable_labels: Vec<String>) -> Self {
+        self.common_opts = self.common_opts.variable_labels(variable_labels);
+        self
+    }
+
+    /// `variable_label` adds a variable label.
+    pub fn variable_label<S: Into<String>>(mut self, name: S) -> Self {
+        self.common_opts = self.common_opts.variable_label(name);
+        self
+    }
+
+    /// `cardinality_limit` limits the number of children of a metric vector.
+    /// See [`Opts::cardinality_limit`].
+    pub fn cardinality_limit(mut self, max_children: usize, policy: CardinalityPolicy) -> Self {
+        self.common_opts = self.common_opts.cardinality_limit(max_children, policy);
+        self
+    }
+
+    /// `ttl` sets the time after which untouched children of a metric vector
+    /// expire. See [`Opts::ttl`].
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.common_opts = self.common_opts.ttl(ttl);
+        self
+    }
+
+    /// `unit` sets the unit. See [`Opts::unit`].
+    pub fn unit<S: Into<String>>(mut self, unit: S) -> Self {
+        self.common_opts = self.common_opts.unit(unit);
+        self
+    }
+
+    /// `fq_name` returns the fq_name.
+    pub fn fq_name(&self) -> String {
+        self.common_opts.fq_name()
+    }
+
+    /// `objectives` sets the quantile objectives.
+    pub fn objectives(mut self, objectives: Vec<(f64, f64)>) -> Self {
+        self.objectives = objectives;
+        self
+    }
+
+    /// `objective` adds a quantile objective with its allowed error.
+    pub fn objective(mut self, quantile: f64, error: f64) -> Self {
+        self.objectives.push((quantile, error));
+        self
+    }
+
+    /// `max_age` sets the max age of observations.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// `age_buckets` sets the number of age buckets.
+    pub fn age_buckets(mut self, age_buckets: u32) -> Self {
+        self.age_buckets = age_buckets;
+        self
+    }
+
+    /// `buf_cap` sets the buffer capacity.
+    pub fn buf_cap(mut self, buf_cap: usize) -> Self {
+        self.buf_cap = buf_cap;
+        self
+    }
+}
+
+impl Describer for SummaryOpts {
+    fn describe(&self) -> Result<Desc> {
+        self.common_opts.describe()
+    }
+}
+
+impl From<Opts> for SummaryOpts {
+    fn from(opts: Opts) -> SummaryOpts {
+        SummaryOpts {
+            common_opts: opts,
+            objectives: Vec::new(),
+            max_age: DEFAULT_MAX_AGE,
+            age_buckets: DEFAULT_AGE_BUCKETS,
+            buf_cap: DEFAULT_BUF_CAP,
+        }
+    }
+}
+
+/// A sample of a [`QuantileStream`]. `width` is the number of observations
+/// merged into this sample and `delta` the uncertainty of its rank.
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    value: f64,
+    width: f64,
+    delta: f64,
+}
+
+/// A streaming quantile estimator for a set of targeted quantiles, as
+/// described in "Effective Computation of Biased Quantiles over Data Streams"
+/// by Cormode, Korn, Muthukrishnan and Srivastava.
+///
+/// Observations are buffered and merged into the compressed sample list in
+/// sorted batches.
+#[derive(Debug)]
+struct QuantileStream {
+    /// The targeted `(quantile, error)` pairs.
+    targets: Arc<[(f64, f64)]>,
+    /// Number of observations merged into `samples`.
+    n: f64,
+    samples: Vec<Sample>,
+    buffer: Vec<f64>,
+    buffer_cap: usize,
+}
+
+impl QuantileStream {
+    fn new(targets: Arc<[(f64, f64)]>, buffer_cap: usize) -> QuantileStream {
+        QuantileStream {
+            targets,
+            n: 0.0,
+            samples: Vec::new(),
+            buffer: Vec::with_capacity(buffer_cap),
+            buffer_cap,
+        }
+    }
+
+    /// The allowed rank error at rank `r`, i.e. the invariant of the
+    /// algorithm.
+    fn invariant(&self, r: f64) -> f64 {
+        let mut m = f64::MAX;
+        for &(quantile, error) in self.targets.iter() {
+            let f = if quantile * self.n <= r {
+                (2.0 * error * r) / quantile
+            } else {
+                (2.0 * error * (self.n - r)) / (1.0 - quantile)
+            };
+            if f < m {
+                m = f;
+            }
+        }
+        m
+    }
+
+    fn insert(&mut self, v: f64) {
+        self.buffer.push(v);
+        if self.buffer.len() >= self.buffer_cap {
+            self.flush();
+        }
+    }
+
+    fn count(&self) -> u64 {
+        self.buffer.len() as u64 + self.n as u64
+    }
+
+    fn reset(&mut self) {
+        self.n = 0.0;
+        self.samples.clear();
+        self.buffer.clear();
+    }
+
+    fn query(&mut self, q: f64) -> f64 {
+        if self.samples.is_empty() {
+            // Fast path when there hasn't been enough data for a flush, this
+            // also yields better accuracy for small sets of data.
+            if self.buffer.is_empty() {
+                return 0.0;
+            }
+            self.sort_buffer();
+            let i = ((self.buffer.len() as f64 * q).ceil() as usize).saturating_sub(1);
+            return self.buffer[i.min(self.buffer.len() - 1)];
+        }
+
+        self.flush();
+
+        let mut t = (q * self.n).ceil();
+        t += (self.invariant(t) / 2.0).ceil();
+        let mut p = self.samples[0];
+        let mut r = 0.0;
+        for c in &self.samples[1..] {
+            r += p.width;
+            if r + c.width + c.delta > t {
+                return p.value;
+            }
+            p = *c;
+        }
+        p.value
+    }
+
+    fn sort_buffer(&mut self) {
+        self.buffer
+            .sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    }
+
+    fn flush(&mut self) {
+        self.sort_buffer();
+        let buffer = std::mem::take(&mut self.buffer);
+        self.merge(&buffer);
+        self.buffer = buffer;
+        self.buffer.clear();
+    }
+
+    fn merge(&mut self, values: &[f64]) {
+        let mut r = 0.0;
+        let mut i = 0;
+        for &v in values {
+            let mut inserted = false;
+            while i < self.samples.len() {
+                let c = self.samples[i];
+                if c.value > v {
+                    let delta = (self.invariant(r).floor() - 1.0).max(0.0);
+                    self.samples.insert(
+                        i,
+                        Sample {
+                            value: v,
+                            width: 1.0,
+                            delta,
+                        },
+                    );
+                    i += 1;
+                    inserted = true;
+                    break;
+                }
+                r += c.width;
+                i += 1;
+            }
+            if !inserted {
+                self.samples.push(Sample {
+                    value: v,
+                    width: 1.0,
+                    delta: 0.0,
+                });
+                i += 1;
+            }
+            self.n += 1.0;
+            r += 1.0;
+        }
+        self.compress();
+    }
+
+    fn compress(&mut self) {
+        if self.samples.len() < 2 {
+            return;
+        }
+
+        let mut xi = self.samples.len() - 1;
+        let mut x = self.samples[xi];
+        let mut r = self.n - 1.0 - x.width;
+
+        for i in (0..self.samples.len() - 1).rev() {
+            let c = self.samples[i];
+            if c.width + x.width + x.delta <= self.invariant(r) {
+                x.width += c.width;
+                self.samples[xi] = x;
+                self.samples.remove(i);
+                xi -= 1;
+            } else {
+                x = c;
+                xi = i;
+            }
+            r -= c.width;
+        }
+    }
+}
+
+/// The buffer observations are recorded into before they are merged into the
+/// quantile streams.
+#[derive(Debug)]
+struct HotBuffer {
+    values: Vec<f64>,
+    /// Observations recorded after this instant belong to the next age bucket.
+    exp_time: StdInstant,
+}
+
+/// The quantile streams of a summary, one per age bucket.
+#[derive(Debug)]
+struct SummaryStreams {
+    streams: Vec<QuantileStream>,
+    /// Index of the oldest stream, i.e. the one covering the whole `max_age`
+    /// window, which is used to answer queries.
+    head_idx: usize,
+    head_exp_time: StdInstant,
+    /// Observations swapped out of the hot buffer, waiting to be merged.
+    cold_buf: Vec<f64>,
+}
+
+/// Core datastructure of a Prometheus summary.
+///
+/// The sum and the count of observations are tracked with atomics. If the
+/// summary has objectives, observations are additionally appended to a hot
+/// buffer. Once the buffer is full, or the current age bucket has expired, it
+/// is swapped with the cold buffer, which is then merged into every quantile
+/// stream. Streams are rotated every `max_age / age_buckets` so that old
+/// observations eventually drop out of the head stream.
+#[derive(Debug)]
+pub struct SummaryCore {
+    desc: Desc,
+    label_pairs: Vec<proto::LabelPair>,
+
+    sum: AtomicF64,
+    count: AtomicU64,
+    created: SystemTime,
+    last_touched: LastTouched,
+
+    /// Sorted by quantile.
+    objectives: Arc<[(f64, f64)]>,
+    stream_duration: Duration,
+    buf_cap: usize,
+
+    /// Lock order: `hot_buf` before `streams`.
+    hot_buf: Mutex<HotBuffer>,
+    streams: Mutex<SummaryStreams>,
+}
+
+impl SummaryCore {
+    pub fn new(opts: &SummaryOpts, label_values: &[&str]) -> Result<SummaryCore> {
+        let desc = opts.describe()?;
+
+        for name in &desc.variable_labels {
+            check_quantile_label(name)?;
+        }
+        for pair in &desc.const_label_pairs {
+            check_quantile_label(pair.get_name())?;
+        }
+
+        let label_pairs = make_label_pairs(&desc, label_values)?;
+
+        let objectives = check_and_sort_objectives(opts.objectives.clone())?;
+
+        if opts.max_age == Duration::from_secs(0) {
+            return Err(Error::Msg(format!(
+                "illegal max age MaxAge={:?}",
+                opts.max_age
+            )));
+        }
+        if opts.age_buckets == 0 {
+            return Err(Error::Msg(
+                "summary needs a positive number of age buckets".to_owned(),
+            ));
+        }
+        let buf_cap = if opts.buf_cap == 0 {
+            DEFAULT_BUF_CAP
+        } else {
+            opts.buf_cap
+        };
+
+        let objectives: Arc<[(f64, f64)]> = objectives.into();
+        let stream_duration = opts.max_age / opts.age_buckets;
+        let now = StdInstant::now();
+
+        let streams = if objectives.is_empty() {
+            Vec::new()
+        } else {
+            (0..opts.age_buckets)
+                .map(|_| QuantileStream::new(objectives.clone(), buf_cap))
+                .collect()
+        };
+
+        Ok(SummaryCore {
+            desc,
+            label_pairs,
+
+            sum: AtomicF64::new(0.0),
+            count: AtomicU64::new(0),
+            created: SystemTime::now(),
+            last_touched: LastTouched::new(opts.common_opts.ttl),
+
+            objectives,
+            stream_duration,
+            buf_cap,
+
+            hot_buf: Mutex::new(HotBuffer {
+                values: Vec::with_capacity(buf_cap),
+                exp_time: now + stream_duration,
+            }),
+            streams: Mutex::new(SummaryStreams {
+                streams,
+                head_idx: 0,
+                head_exp_time: now + stream_duration,
+                cold_buf: Vec::with_capacity(buf_cap),
+            }),
+        })
+    }
+
+    /// Record a given observation (f64) in the summary.
+    pub fn observe(&self, v: f64) {
+        if !self.objectives.is_empty() {
+            let mut hot_buf = self.hot_buf.lock().expect("Lock poisoned");
+            let now = StdInstant::now();
+            if now > hot_buf.exp_time {
+                self.flush(hot_buf, now);
+                hot_buf = self.hot_buf.lock().expect("Lock poisoned");
+            }
+            hot_buf.values.push(v);
+            if hot_buf.values.len() >= self.buf_cap {
+                self.flush(hot_buf, now);
+            }
+        }
+
+        self.sum.inc_by(v);
+        self.count.inc_by(1);
+        self.last_touched.touch();
+    }
+
+    /// Record a batch of observations with a precomputed sum.
+    fn observe_many(&self, values: &[f64], sum: f64) {
+        if !self.objectives.is_empty() {
+            let mut hot_buf = self.hot_buf.lock().expect("Lock poisoned");
+            let now = StdInstant::now();
+            if now > hot_buf.exp_time {
+                self.flush(hot_buf, now);
+                hot_buf = self.hot_buf.lock().expect("Lock poisoned");
+            }
+            hot_buf.values.extend_from_slice(values);
+            if hot_buf.values.len() >= self.buf_cap {
+                self.flush(hot_buf, now);
+            }
+        }
+
+        self.sum.inc_by(sum);
+        self.count.inc_by(values.len() as u64);
+        self.last_touched.touch();
+    }
+
+    /// Swap the hot and the cold buffer and merge the cold buffer into the
+    /// quantile streams.
+    //
+    // The hot buffer lock is released as soon as the buffers are swapped, so
+    // that observations can continue while the streams are updated.
+    fn flush(&self, mut hot_buf: std::sync::MutexGuard<'_, HotBuffer>, now: StdInstant) {
+        let mut streams = self.streams.lock().expect("Lock poisoned");
+        self.swap_bufs(&mut hot_buf, &mut streams, now);
+        let hot_exp_time = hot_buf.exp_time;
+        drop(hot_buf);
+
+        self.flush_cold_buf(&mut streams, hot_exp_time);
+    }
+
+    fn swap_bufs(&self, hot_buf: &mut HotBuffer, streams: &mut SummaryStreams, now: StdInstant) {
+        debug_assert!(streams.cold_buf.is_empty());
+        std::mem::swap(&mut hot_buf.values, &mut streams.cold_buf);
+        // The hot buffer is now empty and gets a new expiration time.
+        while now > hot_buf.exp_time {
+            hot_buf.exp_time += self.stream_duration;
+        }
+    }
+
+    fn flush_cold_buf(&self, streams: &mut SummaryStreams, hot_exp_time: StdInstant) {
+        let SummaryStreams {
+            streams: ss,
+            cold_buf,
+            ..
+        } = streams;
+        for v in cold_buf.drain(..) {
+            for stream in ss.iter_mut() {
+                stream.insert(v);
+            }
+        }
+
+        // Rotate the streams, resetting the ones whose age bucket has expired.
+        while streams.head_exp_time < hot_exp_time {
+            let head_idx = streams.head_idx;
+            streams.streams[head_idx].reset();
+            streams.head_idx = (head_idx + 1) % streams.streams.len();
+            streams.head_exp_time += self.stream_duration;
+        }
+    }
+
+    /// Make a snapshot of the current summary state exposed as a Protobuf
+    /// struct.
+    pub fn proto(&self) -> proto::Summary {
+        let mut quantiles = Vec::with_capacity(self.objectives.len());
+
+        if !self.objectives.is_empty() {
+            let mut hot_buf = self.hot_buf.lock().expect("Lock poisoned");
+            let mut streams = self.streams.lock().expect("Lock poisoned");
+            // Swap buffers even if the hot buffer is empty to set a new
+            // expiration time.
+            self.swap_bufs(&mut hot_buf, &mut streams, StdInstant::now());
+            let hot_exp_time = hot_buf.exp_time;
+            drop(hot_buf);
+            self.flush_cold_buf(&mut streams, hot_exp_time);
+
+            let head_idx = streams.head_idx;
+            let head = &mut streams.streams[head_idx];
+            for &(rank, _) in self.objectives.iter() {
+                let value = if head.count() == 0 {
+                    f64::NAN
+                } else {
+                    head.query(rank)
+                };
+
+                let mut q = proto::Quantile::default();
+                q.set_quantile(rank);
+                q.set_value(value);
+                quantiles.push(q);
+            }
+        }
+
+        let mut s = proto::Summary::default();
+        s.set_sample_sum(self.sum.get());
+        s.set_sample_count(self.count.get());
+        s.set_quantile(from_vec!(quantiles));
+        s.set_created_timestamp(make_timestamp(self.created));
+
+        s
+    }
+
+    fn sample_sum(&self) -> f64 {
+        self.sum.get()
+    }
+
+    fn sample_count(&self) -> u64 {
+        self.count.get()
+    }
+}
+
+/// Timer to measure and record the duration of an event.
+///
+/// This timer can be stopped and observed at most once, either automatically (when it
+/// goes out of scope) or manually.
+/// Alternatively, it can be manually stopped and discarded in order to not record its value.
+#[must_use = "Timer should be kept in a variable otherwise it cannot observe duration"]
+#[derive(Debug)]
+pub struct SummaryTimer {
+    /// A summary for automatic recording of observations.
+    summary: Summary,
+    /// Whether the timer has already been observed once.
+    observed: bool,
+    /// Starting instant for the timer.
+    start: Instant,
+}
+
+impl SummaryTimer {
+    fn new(summary: Summary) -> Self {
+        Self {
+            summary,
+            observed: false,
+            start: Instant::now(),
+        }
+    }
+
+    #[cfg(feature = "nightly")]
+    fn new_coarse(summary: Summary) -> Self {
+        SummaryTimer {
+            summary,
+            observed: false,
+            start: Instant::now_coarse(),
+        }
+    }
+
+    /// Observe and record timer duration (in seconds).
+    ///
+    /// It observes the floating-point number of seconds elapsed since the timer
+    /// started, and it records that value to the attached summary.
+    pub fn observe_duration(self) {
+        self.stop_and_record();
+    }
+
+    /// Observe, record and return timer duration (in seconds).
+    ///
+    /// It observes and returns a floating-point number for seconds elapsed since
+    /// the timer started, recording that value to the attached summary.
+    pub fn stop_and_record(self) -> f64 {
+        let mut timer = self;
+        timer.observe(true)
+    }
+
+    /// Observe and return timer duration (in seconds).
+    ///
+    /// It returns a floating-point number of seconds elapsed since the timer started,
+    /// without recording to any summary.
+    pub fn stop_and_discard(self) -> f64 {
+        let mut timer = self;
+        timer.observe(false)
+    }
+
+    fn observe(&mut self, record: bool) -> f64 {
+        let v = self.start.elapsed_sec();
+        self.observed = true;
+        if record {
+            self.summary.observe(v);
+        }
+        v
+    }
+}
+
+impl Drop for SummaryTimer {
+    fn drop(&mut self) {
+        if !self.observed {
+            self.observe(true);
+        }
+    }
+}
+
+/// A [`Metric`] captures individual observations from an event or sample
+/// stream and summarizes them in a manner similar to traditional summary
+/// statistics: sum of observations, observation count, and rank estimations.
+///
+/// Quantiles are calculated over a sliding time window of `max_age`, using
+/// the streaming algorithm described in [Effective Computation of Biased
+/// Quantiles over Data Streams][1]. Each objective is given with its allowed
+/// absolute error, which bounds the memory used by the estimation.
+///
+/// Note that Summaries, in contrast to [`Histogram`](crate::Histogram)s,
+/// cannot be aggregated with the Prometheus query language (see [the
+/// prometheus documentation][2] for detailed procedures). Their Observe method
+/// is also considerably more expensive, consider a
+/// [`LocalSummary`](crate::local::LocalSummary) on hot paths.
+///
+/// [1]: https://ieeexplore.ieee.org/document/1410103
+/// [2]: https://prometheus.io/docs/practices/histograms/
+#[derive(Clone, Debug)]
+pub struct Summary {
+    core: Arc<SummaryCore>,
+}
+
+impl Summary {
+    /// `with_opts` creates a [`Summary`] with the `opts` options.
+    pub fn with_opts(opts: SummaryOpts) -> Result<Summary> {
+        Summary::with_opts_and_label_values(&opts, &[])
+    }
+
+    fn with_opts_and_label_values(opts: &SummaryOpts, label_values: &[&str]) -> Result<Summary> {
+        let core = SummaryCore::new(opts, label_values)?;
+
+        Ok(Summary {
+            core: Arc::new(core),
+        })
+    }
+}
+
+impl Summary {
+    /// Add a single observation to the [`Summary`].
+    pub fn observe(&self, v: f64) {
+        self.core.observe(v)
+    }
+
+    /// Return a [`SummaryTimer`] to track a duration.
+    pub fn start_timer(&self) -> SummaryTimer {
+        SummaryTimer::new(self.clone())
+    }
+
+    /// Return a [`SummaryTimer`] to track a duration.
+    /// It is faster but less precise.
+    #[cfg(feature = "nightly")]
+    pub fn start_coarse_timer(&self) -> SummaryTimer {
+        SummaryTimer::new_coarse(self.clone())
+    }
+
+    /// Observe execution time of a closure, in second.
+    pub fn observe_closure_duration<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let instant = Instant::now();
+        let res = f();
+        let elapsed = instant.elapsed_sec();
+        self.observe(elapsed);
+        res
+    }
+
+    /// Observe execution time of a closure, in second.
+    #[cfg(feature = "nightly")]
+    pub fn observe_closure_duration_coarse<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let instant = Instant::now_coarse();
+        let res = f();
+        let elapsed = instant.elapsed_sec();
+        self.observe(elapsed);
+        res
+    }
+
+    /// Return a [`LocalSummary`] for single thread usage.
+    pub fn local(&self) -> LocalSummary {
+        LocalSummary::new(self.clone())
+    }
+
+    /// Return accumulated sum of all samples.
+    pub fn get_sample_sum(&self) -> f64 {
+        self.core.sample_sum()
+    }
+
+    /// Return count of all samples.
+    pub fn get_sample_count(&self) -> u64 {
+        self.core.sample_count()
+    }
+}
+
+impl Metric for Summary {
+    fn metric(&self) -> proto::Metric {
+        let mut m = proto::Metric::default();
+        m.set_label(from_vec!(self.core.label_pairs.clone()));
+
+        let s = self.core.proto();
+        m.set_summary(s);
+
+        m
+    }
+
+    fn last_touched(&self) -> Option<u64> {
+        self.core.last_touched.get()
+    }
+}
+
+impl Collector for Summary {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.core.desc]
+    }
+
+    fn collect(&self) -> Vec<proto::MetricFamily> {
+        let mut m = proto::MetricFamily::default();
+        m.set_name(self.core.desc.fq_name.clone());
+        m.set_help(self.core.desc.help.clone());
+        if !self.core.desc.unit.is_empty() {
+            m.set_unit(self.core.desc.unit.clone());
+        }
+        m.set_field_type(proto::MetricType::SUMMARY);
+        m.set_metric(from_vec!(vec![self.metric()]));
+
+        vec![m]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SummaryVecBuilder {}
+
+impl MetricVecBuilder for SummaryVecBuilder {
+    type M = Summary;
+    type P = SummaryOpts;
+
+    fn build(&self, opts: &SummaryOpts, vals: &[&str]) -> Result<Summary> {
+        Summary::with_opts_and_label_values(opts, vals)
+    }
+}
+
+/// A [`Collector`] that bundles a set of Summaries that all share the
+/// same [`Desc`], but have different values for their variable labels. This is used
+/// if you want to count the same thing partitioned by various dimensions
+/// (e.g. HTTP request latencies, partitioned by status code and method).
+pub type SummaryVec = MetricVec<SummaryVecBuilder>;
+
+impl SummaryVec {
+    /// Create a new [`SummaryVec`] based on the provided
+    /// [`SummaryOpts`] and partitioned by the given label names. At least
+    /// one label name must be provided.
+    pub fn new(opts: SummaryOpts, label_names: &[&str]) -> Result<SummaryVec> {
+        let variable_names = label_names.iter().map(|s| (*s).to_owned()).collect();
+        let opts = opts.variable_labels(variable_names);
+        let limits = ChildLimits::from_opts(&opts.common_opts);
+        let desc = opts.describe()?;
+        let metric_vec = MetricVec::create_with_limits(
+            proto::MetricType::SUMMARY,
+            SummaryVecBuilder {},
+            opts,
+            desc,
+            limits,
+        )?;
+
+        Ok(metric_vec as SummaryVec)
+    }
+
+    /// Return a `LocalSummaryVec` for single thread usage.
+    pub fn local(&self) -> LocalSummaryVec {
+        let vec = self.clone();
+        LocalSummaryVec::new(vec)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LocalSummaryCore {
+    summary: Summary,
+    values: Vec<f64>,
+    sum: f64,
+}
+
+/// An unsync [`Summary`].
+#[derive(Debug)]
+pub struct LocalSummary {
+    core: RefCell<LocalSummaryCore>,
+}
+
+impl Clone for LocalSummary {
+    fn clone(&self) -> LocalSummary {
+        let core = self.core.clone();
+        let ls = LocalSummary { core };
+        ls.clear();
+        ls
+    }
+}
+
+/// An unsync [`SummaryTimer`].
+#[must_use = "Timer should be kept in a variable otherwise it cannot observe duration"]
+#[derive(Debug)]
+pub struct LocalSummaryTimer {
+    /// A local summary for automatic recording of observations.
+    local: LocalSummary,
+    /// Whether the timer has already been observed once.
+    observed: bool,
+    /// Starting instant for the timer.
+    start: Instant,
+}
+
+impl LocalSummaryTimer {
+    fn new(summary: LocalSummary) -> Self {
+        Self {
+            local: summary,
+            observed: false,
+            start: Instant::now(),
+        }
+    }
+
+    #[cfg(feature = "nightly")]
+    fn new_coarse(summary: LocalSummary) -> Self {
+        Self {
+            local: summary,
+            observed: false,
+            start: Instant::now_coarse(),
+        }
+    }
+
+    /// Observe and record timer duration (in seconds).
+    ///
+    /// It observes the floating-point number of seconds elapsed since the timer
+    /// started, and it records that value to the attached summary.
+    pub fn observe_duration(self) {
+        self.stop_and_record();
+    }
+
+    /// Observe, record and return timer duration (in seconds).
+    ///
+    /// It observes and returns a floating-point number for seconds elapsed since
+    /// the timer started, recording that value to the attached summary.
+    pub fn stop_and_record(self) -> f64 {
+        let mut timer = self;
+        timer.observe(true)
+    }
+
+    /// Observe and return timer duration (in seconds).
+    ///
+    /// It returns a floating-point number of seconds elapsed since the timer started,
+    /// without recording to any summary.
+    pub fn stop_and_discard(self) -> f64 {
+        let mut timer = self;
+        timer.observe(false)
+    }
+
+    fn observe(&mut self, record: bool) -> f64 {
+        let v = self.start.elapsed_sec();
+        self.observed = true;
+        if record {
+            self.local.observe(v);
+        }
+        v
+    }
+}
+
+impl Drop for LocalSummaryTimer {
+    fn drop(&mut self) {
+        if !self.observed {
+            self.observe(true);
+        }
+    }
+}
+
+impl LocalSummaryCore {
+    fn new(summary: Summary) -> LocalSummaryCore {
+        LocalSummaryCore {
+            summary,
+            values: Vec::new(),
+            sum: 0.0,
+        }
+    }
+
+    pub fn observe(&mut self, v: f64) {
+        self.values.push(v);
+        self.sum += v;
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.sum = 0.0;
+    }
+
+    pub fn flush(&mut self) {
+        // No cached metric, return.
+        if self.values.is_empty() {
+            return;
+        }
+
+        self.summary.core.observe_many(&self.values, self.sum);
+
+        self.clear()
+    }
+
+    fn sample_sum(&self) -> f64 {
+        self.sum
+    }
+
+    fn sample_count(&self) -> u64 {
+        self.values.len() as u64
+    }
+}
+
+impl LocalSummary {
+    fn new(summary: Summary) -> LocalSummary {
+        let core = LocalSummaryCore::new(summary);
+        LocalSummary {
+            core: RefCell::new(core),
+        }
+    }
+
+    /// Add a single observation to the [`Summary`].
+    pub fn observe(&self, v: f64) {
+        self.core.borrow_mut().observe(v);
+    }
+
+    /// Return a `LocalSummaryTimer` to track a duration.
+    pub fn start_timer(&self) -> LocalSummaryTimer {
+        LocalSummaryTimer::new(self.clone())
+    }
+
+    /// Return a `LocalSummaryTimer` to track a duration.
+    /// It is faster but less precise.
+    #[cfg(feature = "nightly")]
+    pub fn start_coarse_timer(&self) -> LocalSummaryTimer {
+        LocalSummaryTimer::new_coarse(self.clone())
+    }
+
+    /// Observe execution time of a closure, in second.
+    pub fn observe_closure_duration<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let instant = Instant::now();
+        let res = f();
+        let elapsed = instant.elapsed_sec();
+        self.observe(elapsed);
+        res
+    }
+
+    /// Observe execution time of a closure, in second.
+    #[cfg(feature = "nightly")]
+    pub fn observe_closure_duration_coarse<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let instant = Instant::now_coarse();
+        let res = f();
+        let elapsed = instant.elapsed_sec();
+        self.observe(elapsed);
+        res
+    }
+
+    /// Clear the local metric.
+    pub fn clear(&self) {
+        self.core.borrow_mut().clear();
+    }
+
+    /// Flush the local metrics to the [`Summary`] metric.
+    pub fn flush(&self) {
+        self.core.borrow_mut().flush();
+    }
+
+    /// Return accumulated sum of local samples.
+    pub fn get_sample_sum(&self) -> f64 {
+        self.core.borrow().sample_sum()
+    }
+
+    /// Return count of local samples.
+    pub fn get_sample_count(&self) -> u64 {
+        self.core.borrow().sample_count()
+    }
+}
+
+impl LocalMetric for LocalSummary {
+    /// Flush the local metrics to the [`Summary`] metric.
+    fn flush(&self) {
+        LocalSummary::flush(self);
+    }
+}
+
+impl Drop for LocalSummary {
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+/// An unsync [`SummaryVec`].
+#[derive(Debug)]
+pub struct LocalSummaryVec {
+    vec: SummaryVec,
+    local: LabelValuesMap<LocalSummary>,
+}
+
+impl LocalSummaryVec {
+    fn new(vec: SummaryVec) -> LocalSummaryVec {
+        let local = LabelValuesMap::with_capacity(vec.v.children.read().len());
+        LocalSummaryVec { vec, local }
+    }
+
+    /// Get a [`LocalSummary`] by label values.
+    /// See more [`MetricVec::with_label_values`].
+    pub fn with_label_values<'a>(&'a mut self, vals: &[&str]) -> &'a LocalSummary {
+        let hash = self.vec.hash_label_values(vals).unwrap();
+        let vec = &self.vec;
+        self.local
+            .get_or_insert_with(hash, vals, || vec.with_label_values(vals).local())
+    }
+
+    /// Remove a [`LocalSummary`] by label values.
+    /// See more [`MetricVec::remove_label_values`].
+    pub fn remove_label_values(&mut self, vals: &[&str]) -> Result<()> {
+        let hash = self.vec.hash_label_values(vals)?;
+        self.local.remove(hash, vals);
+        self.vec.remove_label_values(vals)
+    }
+
+    /// Flush the local metrics to the [`SummaryVec`] metric.
+    pub fn flush(&self) {
+        for s in self.local.values() {
+            s.flush();
+        }
+    }
+}
+
+impl LocalMetric for LocalSummaryVec {
+    /// Flush the local metrics to the [`SummaryVec`] metric.
+    fn flush(&self) {
+        LocalSummaryVec::flush(self)
+    }
+}
+
+impl Clone for LocalSummaryVec {
+    fn clone(&self) -> LocalSummaryVec {
+        LocalSummaryVec::new(self.vec.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::metrics::{Collector, Metric};
+
+    fn get_quantiles(m: &proto::Metric) -> Vec<(f64, f64)> {
+        m.get_summary()
+            .get_quantile()
+            .iter()
+            .map(|q| (q.get_quantile(), q.get_value()))
+            .collect()
+    }
+
+    #[test]
+    fn test_summary() {
+        let opts = SummaryOpts::new("test1", "test help")
+            .const_label("a", "1")
+            .const_label("b", "2")
+            .objective(0.5, 0.05)
+            .objective(0.9, 0.01);
+        let summary = Summary::with_opts(opts).unwrap();
+        summary.observe(1.0);
+
+        let timer = summary.start_timer();
+        thread::sleep(Duration::from_millis(100));
+        timer.observe_duration();
+
+        let timer = summary.start_timer();
+        let handler = thread::spawn(move || {
+            let _timer = timer;
+            thread::sleep(Duration::from_millis(400));
+        });
+        assert!(handler.join().is_ok());
+
+        let mut mfs = summary.collect();
+        assert_eq!(mfs.len(), 1);
+
+        let mf = mfs.pop().unwrap();
+        assert_eq!(mf.get_field_type(), proto::MetricType::SUMMARY);
+        let m = mf.get_metric().first().unwrap();
+        assert_eq!(m.get_label().len(), 2);
+        let proto_summary = m.get_summary();
+        assert_eq!(proto_summary.get_sample_count(), 3);
+        assert!(proto_summary.get_sample_sum() >= 1.5);
+        assert_eq!(proto_summary.get_quantile().len(), 2);
+        assert_eq!(proto_summary.get_quantile()[0].get_quantile(), 0.5);
+        assert_eq!(proto_summary.get_quantile()[1].get_quantile(), 0.9);
+        assert!(proto_summary.get_quantile()[1].get_value() >= 1.0);
+
+        let summary = Summary::with_opts(SummaryOpts::new("test2", "test help")).unwrap();
+        summary.observe(2.0);
+        let m = summary.metric();
+        assert_eq!(m.get_summary().get_sample_count(), 1);
+        assert!((m.get_summary().get_sample_sum() - 2.0).abs() < f64::EPSILON);
+        assert!(m.get_summary().get_quantile().is_empty());
+    }
+
+    #[test]
+    fn test_summary_invalid_opts() {
+        let table = vec![
+            SummaryOpts::new("test", "help").objective(1.5, 0.01),
+            SummaryOpts::new("test", "help").objective(0.5, -0.01),
+            SummaryOpts::new("test", "help")
+                .objective(0.5, 0.05)
+                .objective(0.5, 0.01),
+            SummaryOpts::new("test", "help").max_age(Duration::from_secs(0)),
+            SummaryOpts::new("test", "help").age_buckets(0),
+            SummaryOpts::new("test", "help").const_label(QUANTILE_LABEL, "1"),
+        ];
+
+        for opts in table {
+            assert!(Summary::with_opts(opts).is_err());
+        }
+
+        let vec = SummaryVec::new(SummaryOpts::new("test", "help"), &[QUANTILE_LABEL]).unwrap();
+        assert!(vec.get_metric_with_label_values(&["0.5"]).is_err());
+    }
+
+    #[test]
+    fn test_summary_empty_quantiles() {
+        let summary = Summary::with_opts(
+            SummaryOpts::new("test_summary_empty", "test help").objective(0.5, 0.05),
+        )
+        .unwrap();
+
+        let quantiles = get_quantiles(&summary.metric());
+        assert_eq!(quantiles.len(), 1);
+        assert!(quantiles[0].1.is_nan());
+    }
+
+    #[test]
+    fn test_summary_quantiles() {
+        let objectives = vec![(0.5, 0.05), (0.9, 0.01), (0.99, 0.001)];
+        let summary = Summary::with_opts(
+            SummaryOpts::new("test_summary_quantiles", "test help")
+                .objectives(objectives.clone())
+                .buf_cap(100),
+        )
+        .unwrap();
+
+        // Observe 1..=10_000 in a scrambled order.
+        let n = 10_000;
+        for i in 0..n {
+            summary.observe(((i * 7_919) % n + 1) as f64);
+        }
+
+        let m = summary.metric();
+        assert_eq!(m.get_summary().get_sample_count(), n as u64);
+
+        for (rank, value) in get_quantiles(&m) {
+            let error = objectives.iter().find(|o| o.0 == rank).unwrap().1;
+            let min = ((rank - error) * n as f64).floor();
+            let max = ((rank + error) * n as f64).ceil();
+            assert!(
+                value >= min && value <= max,
+                "quantile {}: got {}, want in [{}, {}]",
+                rank,
+                value,
+                min,
+                max
+            );
+        }
+    }
+
+    #[test]
+    fn test_summary_max_age() {
+        let summary = Summary::with_opts(
+            SummaryOpts::new("test_summary_max_age", "test help")
+                .objective(0.5, 0.05)
+                .max_age(Duration::from_millis(200))
+                .age_buckets(2),
+        )
+        .unwrap();
+
+        summary.observe(1.0);
+        let quantiles = get_quantiles(&summary.metric());
+        assert!((quantiles[0].1 - 1.0).abs() < f64::EPSILON);
+
+        // Wait for all age buckets to expire.
+        thread::sleep(Duration::from_millis(400));
+
+        let m = summary.metric();
+        assert!(get_quantiles(&m)[0].1.is_nan());
+        // Sum and count are never reset.
+        assert_eq!(m.get_summary().get_sample_count(), 1);
+    }
+
+    #[test]
+    fn test_summary_vec_with_label_values() {
+        let vec = SummaryVec::new(
+            SummaryOpts::new("test_summary_vec", "test summary vec help"),
+            &["l1", "l2"],
+        )
+        .unwrap();
+
+        assert!(vec.remove_label_values(&["v1", "v2"]).is_err());
+        vec.with_label_values(&["v1", "v2"]).observe(1.0);
+        assert!(vec.remove_label_values(&["v1", "v2"]).is_ok());
+
+        assert!(vec.remove_label_values(&["v1"]).is_err());
+        assert!(vec.remove_label_values(&["v1", "v3"]).is_err());
+    }
+
+    #[test]
+    fn test_summary_local() {
+        let opts =
+            SummaryOpts::new("test_summary_local", "test summary local help").objective(0.5, 0.05);
+        let summary = Summary::with_opts(opts).unwrap();
+        let local = summary.local();
+
+        let check = |count, sum: f64| {
+            let m = summary.metric();
+            let proto_summary = m.get_summary();
+            assert_eq!(proto_summary.get_sample_count(), count);
+            assert!((proto_summary.get_sample_sum() - sum).abs() < f64::EPSILON);
+        };
+
+        local.observe(1.0);
+        local.observe(4.0);
+        check(0, 0.0);
+
+        local.flush();
+        check(2, 5.0);
+
+        local.observe(2.0);
+        local.clear();
+        check(2, 5.0);
+
+        local.observe(2.0);
+        drop(local);
+        check(3, 7.0);
+
+        let quantiles = get_quantiles(&summary.metric());
+        assert!((quantiles[0].1 - 2.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_summary_vec_local() {
+        let vec = SummaryVec::new(
+            SummaryOpts::new("test_summary_vec_local", "test summary vec help"),
+            &["l1", "l2"],
+        )
+        .unwrap();
+        let mut local_vec = vec.local();
+
+        vec.remove_label_values(&["v1", "v2"]).unwrap_err();
+        local_vec.remove_label_values(&["v1", "v2"]).unwrap_err();
+
+        let check = |count, sum: f64| {
+            let ms = vec.collect()[0].take_metric();
+            let proto_summary = ms[0].get_summary();
+            assert_eq!(proto_summary.get_sample_count(), count);
+            assert!((proto_summary.get_sample_sum() - sum).abs() < f64::EPSILON);
+        };
+
+        {
+            // Flush LocalSummary
+            let s = local_vec.with_label_values(&["v1", "v2"]);
+            s.observe(1.0);
+            s.flush();
+            check(1, 1.0);
+        }
+
+        {
+            // Flush LocalSummaryVec
+            local_vec.with_label_values(&["v1", "v2"]).observe(4.0);
+            local_vec.flush();
+            check(2, 5.0);
+        }
+        {
+            // Reset ["v1", "v2"]
+            local_vec.remove_label_values(&["v1", "v2"]).unwrap();
+
+            // Flush on drop
+            local_vec.with_label_values(&["v1", "v2"]).observe(2.0);
+            drop(local_vec);
+            check(1, 2.0);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_observe_and_collect() {
+        let summary = Summary::with_opts(
+            SummaryOpts::new("test_summary_concurrent", "test help")
+                .objective(0.5, 0.05)
+                .buf_cap(10),
+        )
+        .unwrap();
+
+        let handlers: Vec<_> = (0..4)
+            .map(|_| {
+                let summary = summary.clone();
+                thread::spawn(move || {
+                    for _ in 0..1_000 {
+                        summary.observe(1.0);
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..100 {
+            summary.collect();
+        }
+        for handler in handlers {
+            handler.join().unwrap();
+        }
+
+        let m = summary.metric();
+        assert_eq!(m.get_summary().get_sample_count(), 4_000);
+        assert!((get_quantiles(&m)[0].1 - 1.0).abs() < f64::EPSILON);
+    }
+}
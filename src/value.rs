@@ -1,16 +1,33 @@
 // Copyright 2014 The Prometheus Authors
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use parking_lot::Mutex;
+#[cfg(feature = "protobuf")]
+use protobuf::well_known_types::Timestamp;
+
 use crate::atomic64::{Atomic, Number};
-use crate::desc::{Desc, Describer};
+use crate::desc::{is_valid_label_name, Desc};
 use crate::errors::{Error, Result};
-use crate::proto::{Counter, Gauge, LabelPair, Metric, MetricFamily, MetricType};
+use crate::metrics::Opts;
+#[cfg(not(feature = "protobuf"))]
+use crate::proto::Timestamp;
+use crate::proto::{Counter, Exemplar, Gauge, LabelPair, Metric, MetricFamily, MetricType};
+use crate::vec::LastTouched;
+
+/// The maximum combined length, in UTF-8 characters, of the label names and
+/// values of an exemplar, as mandated by OpenMetrics.
+pub const MAX_EXEMPLAR_LABEL_LENGTH: usize = 128;
 
 /// `ValueType` is an enumeration of metric types that represent a simple value
 /// for [`Counter`] and [`Gauge`].
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum ValueType {
+    /// A monotonically increasing value.
     Counter,
+    /// A value that can arbitrarily go up and down.
     Gauge,
 }
 
@@ -34,16 +51,15 @@ pub struct Value<P: Atomic> {
     pub val: P,
     pub val_type: ValueType,
     pub label_pairs: Vec<LabelPair>,
+    pub exemplar: Mutex<Option<Exemplar>>,
+    /// The time the value was created or last reset, exposed by counters.
+    pub created: Mutex<SystemTime>,
+    pub last_touched: LastTouched,
 }
 
 impl<P: Atomic> Value<P> {
-    pub fn new<D: Describer>(
-        describer: &D,
-        val_type: ValueType,
-        val: P::T,
-        label_values: &[&str],
-    ) -> Result<Self> {
-        let desc = describer.describe()?;
+    pub fn new(opts: &Opts, val_type: ValueType, val: P::T, label_values: &[&str]) -> Result<Self> {
+        let desc = opts.describe_as(val_type.metric_type())?;
         let label_pairs = make_label_pairs(&desc, label_values)?;
 
         Ok(Self {
@@ -51,6 +67,9 @@ impl<P: Atomic> Value<P> {
             val: P::new(val),
             val_type,
             label_pairs,
+            exemplar: Mutex::new(None),
+            created: Mutex::new(SystemTime::now()),
+            last_touched: LastTouched::new(opts.ttl),
         })
     }
 
@@ -62,11 +81,28 @@ impl<P: Atomic> Value<P> {
     #[inline]
     pub fn set(&self, val: P::T) {
         self.val.set(val);
+        self.last_touched.touch();
+    }
+
+    /// `reset` sets the value back to 0 and restarts the creation time.
+    pub fn reset(&self) {
+        self.val.set(P::T::from_i64(0));
+        *self.created.lock() = SystemTime::now();
+        self.last_touched.touch();
     }
 
     #[inline]
     pub fn inc_by(&self, val: P::T) {
         self.val.inc_by(val);
+        self.last_touched.touch();
+    }
+
+    /// `inc_by_with_exemplar` increases the value by `val` and records
+    /// `exemplar` as the latest one, replacing any previous exemplar.
+    pub fn inc_by_with_exemplar(&self, val: P::T, exemplar: Exemplar) {
+        self.val.inc_by(val);
+        *self.exemplar.lock() = Some(exemplar);
+        self.last_touched.touch();
     }
 
     #[inline]
@@ -81,7 +117,8 @@ impl<P: Atomic> Value<P> {
 
     #[inline]
     pub fn dec_by(&self, val: P::T) {
-        self.val.dec_by(val)
+        self.val.dec_by(val);
+        self.last_touched.touch();
     }
 
     pub fn metric(&self) -> Metric {
@@ -93,6 +130,10 @@ impl<P: Atomic> Value<P> {
             ValueType::Counter => {
                 let mut counter = Counter::default();
                 counter.set_value(val.into_f64());
+                if let Some(exemplar) = self.exemplar.lock().clone() {
+                    counter.set_exemplar(exemplar);
+                }
+                counter.set_created_timestamp(make_timestamp(*self.created.lock()));
                 m.set_counter(counter);
             }
             ValueType::Gauge => {
@@ -109,6 +150,9 @@ impl<P: Atomic> Value<P> {
         let mut m = MetricFamily::default();
         m.set_name(self.desc.fq_name.clone());
         m.set_help(self.desc.help.clone());
+        if !self.desc.unit.is_empty() {
+            m.set_unit(self.desc.unit.clone());
+        }
         m.set_field_type(self.val_type.metric_type());
         m.set_metric(from_vec!(vec![self.metric()]));
         m
@@ -146,3 +190,54 @@ pub fn make_label_pairs(desc: &Desc, label_values: &[&str]) -> Result<Vec<LabelP
     label_pairs.sort();
     Ok(label_pairs)
 }
+
+/// `make_exemplar` builds an exemplar for the observed `value`, labelled with
+/// `labels` and stamped with the current wall clock time. It fails if a label
+/// name is invalid or if the labels exceed [`MAX_EXEMPLAR_LABEL_LENGTH`].
+pub fn make_exemplar(value: f64, labels: &HashMap<&str, &str>) -> Result<Exemplar> {
+    let mut total_len = 0;
+    let mut label_pairs = Vec::with_capacity(labels.len());
+    for (name, value) in labels {
+        if !is_valid_label_name(name) {
+            return Err(Error::Msg(format!(
+                "'{}' is not a valid exemplar label name",
+                name
+            )));
+        }
+        total_len += name.chars().count() + value.chars().count();
+
+        let mut label_pair = LabelPair::default();
+        label_pair.set_name((*name).to_owned());
+        label_pair.set_value((*value).to_owned());
+        label_pairs.push(label_pair);
+    }
+    if total_len > MAX_EXEMPLAR_LABEL_LENGTH {
+        return Err(Error::Msg(format!(
+            "exemplar labels have {} characters, exceeding the limit of {}",
+            total_len, MAX_EXEMPLAR_LABEL_LENGTH
+        )));
+    }
+    label_pairs.sort();
+
+    let mut exemplar = Exemplar::default();
+    exemplar.set_label(from_vec!(label_pairs));
+    exemplar.set_value(value);
+    exemplar.set_timestamp(make_timestamp(SystemTime::now()));
+    Ok(exemplar)
+}
+
+/// `timestamp_millis` converts the wall clock `time` into milliseconds since
+/// the Unix epoch, as used by the `timestamp_ms` field of a metric.
+pub fn timestamp_millis(time: SystemTime) -> i64 {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_millis() as i64
+}
+
+/// `make_timestamp` converts the wall clock `time` into a proto timestamp.
+pub fn make_timestamp(time: SystemTime) -> Timestamp {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut timestamp = Timestamp::default();
+    timestamp.set_seconds(since_epoch.as_secs() as i64);
+    timestamp.set_nanos(since_epoch.subsec_nanos() as i32);
+    timestamp
+}
@@ -1,17 +1,22 @@
 // Copyright 2014 The Prometheus Authors
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::hash::Hasher;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use fnv::FnvHasher;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 
 use crate::desc::{Desc, Describer};
 use crate::errors::{Error, Result};
-use crate::metrics::{Collector, Metric};
-use crate::proto::{MetricFamily, MetricType};
+use crate::metrics::{
+    CardinalityLimit, CardinalityPolicy, Collector, Metric, Opts, SEPARATOR_BYTE,
+};
+use crate::proto::{self, MetricFamily, MetricType};
+use crate::timer;
 
 /// An interface for building a metric vector.
 pub trait MetricVecBuilder: Send + Sync + Clone {
@@ -24,13 +29,197 @@ pub trait MetricVecBuilder: Send + Sync + Clone {
     fn build(&self, _: &Self::P, _: &[&str]) -> Result<Self::M>;
 }
 
+/// A map from label values to children, keyed by the hash of the label values
+/// as computed by [`MetricVecCore::hash_label_values`].
+///
+/// The label values are stored next to each child and compared on every
+/// lookup, so that hash collisions never merge two distinct children.
+#[derive(Debug)]
+pub(crate) struct LabelValuesMap<M> {
+    map: HashMap<u64, Vec<(Vec<String>, M)>>,
+    /// The number of children, across all hashes.
+    len: usize,
+}
+
+impl<M> Default for LabelValuesMap<M> {
+    fn default() -> Self {
+        LabelValuesMap {
+            map: HashMap::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<M> LabelValuesMap<M> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        LabelValuesMap {
+            map: HashMap::with_capacity(capacity),
+            len: 0,
+        }
+    }
+
+    pub fn get(&self, hash: u64, vals: &[&str]) -> Option<&M> {
+        self.map
+            .get(&hash)?
+            .iter()
+            .find(|(stored, _)| label_values_eq(stored, vals))
+            .map(|(_, m)| m)
+    }
+
+    pub fn get_or_insert_with<F: FnOnce() -> M>(
+        &mut self,
+        hash: u64,
+        vals: &[&str],
+        f: F,
+    ) -> &mut M {
+        let children = self.map.entry(hash).or_default();
+        let pos = match children
+            .iter()
+            .position(|(stored, _)| label_values_eq(stored, vals))
+        {
+            Some(pos) => pos,
+            None => {
+                let stored = vals.iter().map(|v| (*v).to_owned()).collect();
+                children.push((stored, f()));
+                self.len += 1;
+                children.len() - 1
+            }
+        };
+        &mut children[pos].1
+    }
+
+    pub fn remove(&mut self, hash: u64, vals: &[&str]) -> Option<M> {
+        let children = self.map.get_mut(&hash)?;
+        let pos = children
+            .iter()
+            .position(|(stored, _)| label_values_eq(stored, vals))?;
+        let (_, m) = children.swap_remove(pos);
+        if children.is_empty() {
+            self.map.remove(&hash);
+        }
+        self.len -= 1;
+        Some(m)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &M> {
+        self.map.values().flatten().map(|(_, m)| m)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &[String], &M)> {
+        self.map
+            .iter()
+            .flat_map(|(h, children)| children.iter().map(move |(v, m)| (*h, &v[..], m)))
+    }
+
+    pub fn retain<F: FnMut(&[String], &M) -> bool>(&mut self, mut f: F) {
+        let len = &mut self.len;
+        self.map.retain(|_, children| {
+            let before = children.len();
+            children.retain(|(vals, m)| f(vals, m));
+            *len -= before - children.len();
+            !children.is_empty()
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.len = 0;
+    }
+}
+
+/// `hash_values` hashes `vals`, terminating each value with [`SEPARATOR_BYTE`].
+fn hash_values(vals: &[&str]) -> u64 {
+    let mut h = FnvHasher::default();
+    for val in vals {
+        h.write(val.as_bytes());
+        h.write_u8(SEPARATOR_BYTE);
+    }
+    h.finish()
+}
+
+fn label_values_eq(stored: &[String], vals: &[&str]) -> bool {
+    stored.len() == vals.len() && stored.iter().zip(vals).all(|(s, v)| s == v)
+}
+
+/// The coarse time in milliseconds a metric was last touched, only
+/// maintained when a ttl is set.
+#[derive(Debug)]
+pub(crate) struct LastTouched(Option<AtomicU64>);
+
+impl LastTouched {
+    pub fn new(ttl: Option<Duration>) -> LastTouched {
+        LastTouched(ttl.map(|_| AtomicU64::new(timer::now_millis())))
+    }
+
+    #[inline]
+    pub fn touch(&self) {
+        if let Some(ref last_touched) = self.0 {
+            // Only write when the coarse clock moved, to keep the cache line
+            // shared between concurrent users of the metric.
+            let now = timer::recent_millis();
+            if last_touched.load(Ordering::Relaxed) < now {
+                last_touched.store(now, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn get(&self) -> Option<u64> {
+        self.0.as_ref().map(|t| t.load(Ordering::Relaxed))
+    }
+}
+
+/// A child of a [`MetricVecCore`] along with its bookkeeping.
+#[derive(Debug)]
+pub(crate) struct Child<M> {
+    pub metric: M,
+    /// The tick of the last lookup, only maintained for
+    /// [`CardinalityPolicy::EvictLru`].
+    last_access: AtomicU64,
+    /// The last lookup of the child. Updates are recorded by the metric
+    /// itself, see [`Metric::last_touched`].
+    last_touched: LastTouched,
+}
+
+/// The bounds on the children of a [`MetricVecCore`], taken from [`Opts`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ChildLimits {
+    pub cardinality_limit: Option<CardinalityLimit>,
+    pub ttl: Option<Duration>,
+}
+
+impl ChildLimits {
+    pub fn from_opts(opts: &Opts) -> ChildLimits {
+        ChildLimits {
+            cardinality_limit: opts.cardinality_limit.clone(),
+            ttl: opts.ttl,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct MetricVecCore<T: MetricVecBuilder> {
-    pub children: RwLock<HashMap<u64, T::M>>,
+    pub children: RwLock<LabelValuesMap<Child<T::M>>>,
     pub desc: Desc,
     pub metric_type: MetricType,
     pub new_metric: T,
     pub opts: T::P,
+    pub cardinality_limit: Option<CardinalityLimit>,
+    /// The ttl of untouched children in milliseconds.
+    pub ttl_millis: Option<u64>,
+    /// A logical clock ordering child lookups for LRU eviction.
+    access_tick: AtomicU64,
+    /// The hash and label values of the children by the tick of their last
+    /// lookup when indexed, for LRU eviction. Entries are refreshed lazily on
+    /// eviction, so lookups only need to bump the tick of the child.
+    lru: Mutex<BTreeMap<u64, (u64, Vec<String>)>>,
+    /// The number of label sets rejected or evicted due to the limit.
+    rejected: AtomicU64,
+    /// The descriptor of the counter exposing `rejected`, if there is a limit.
+    rejected_desc: Option<Desc>,
 }
 
 impl<T: MetricVecBuilder> MetricVecCore<T> {
@@ -38,43 +227,81 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
         let mut m = MetricFamily::default();
         m.set_name(self.desc.fq_name.clone());
         m.set_help(self.desc.help.clone());
+        if !self.desc.unit.is_empty() {
+            m.set_unit(self.desc.unit.clone());
+        }
         m.set_field_type(self.metric_type);
 
+        if let Some(ttl) = self.ttl_millis {
+            self.expire_children(timer::now_millis(), ttl);
+        }
+
         let children = self.children.read();
         let mut metrics = Vec::with_capacity(children.len());
         for child in children.values() {
-            metrics.push(child.metric());
+            metrics.push(child.metric.metric());
         }
         m.set_metric(from_vec!(metrics));
         m
     }
 
+    /// `collect_rejected` returns the counter of label sets rejected due to the
+    /// cardinality limit, if there is one.
+    fn collect_rejected(&self) -> Option<MetricFamily> {
+        let desc = self.rejected_desc.as_ref()?;
+
+        let mut counter = proto::Counter::default();
+        counter.set_value(self.rejected.load(Ordering::Relaxed) as f64);
+        let mut m = proto::Metric::default();
+        m.set_label(from_vec!(desc.const_label_pairs.clone()));
+        m.set_counter(counter);
+
+        let mut mf = MetricFamily::default();
+        mf.set_name(desc.fq_name.clone());
+        mf.set_help(desc.help.clone());
+        mf.set_field_type(MetricType::COUNTER);
+        mf.set_metric(from_vec!(vec![m]));
+        Some(mf)
+    }
+
+    /// `expire_children` drops the children neither looked up nor updated
+    /// within `ttl` milliseconds before `now`.
+    fn expire_children(&self, now: u64, ttl: u64) {
+        let is_live = |child: &Child<T::M>| {
+            let looked_up = child.last_touched.get().unwrap_or(0);
+            let touched = child
+                .metric
+                .last_touched()
+                .map_or(looked_up, |t| t.max(looked_up));
+            now.saturating_sub(touched) < ttl
+        };
+        if self.children.read().values().all(is_live) {
+            return;
+        }
+        self.children.write().retain(|_, child| is_live(child));
+    }
+
     pub fn get_metric_with_label_values(&self, vals: &[&str]) -> Result<T::M> {
         let h = self.hash_label_values(vals)?;
 
-        if let Some(metric) = self.children.read().get(&h).cloned() {
-            return Ok(metric);
+        if let Some(child) = self.children.read().get(h, vals) {
+            self.touch(child);
+            return Ok(child.metric.clone());
         }
 
         self.get_or_create_metric(h, vals)
     }
 
     pub fn get_metric_with(&self, labels: &HashMap<&str, &str>) -> Result<T::M> {
-        let h = self.hash_labels(labels)?;
-
-        if let Some(metric) = self.children.read().get(&h).cloned() {
-            return Ok(metric);
-        }
-
         let vals = self.get_label_values(labels)?;
-        self.get_or_create_metric(h, &vals)
+        self.get_metric_with_label_values(&vals)
     }
 
     pub fn delete_label_values(&self, vals: &[&str]) -> Result<()> {
         let h = self.hash_label_values(vals)?;
 
         let mut children = self.children.write();
-        if children.remove(&h).is_none() {
+        if children.remove(h, vals).is_none() {
             return Err(Error::Msg(format!("missing label values {:?}", vals)));
         }
 
@@ -82,10 +309,11 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
     }
 
     pub fn delete(&self, labels: &HashMap<&str, &str>) -> Result<()> {
-        let h = self.hash_labels(labels)?;
+        let vals = self.get_label_values(labels)?;
+        let h = self.hash_label_values(&vals)?;
 
         let mut children = self.children.write();
-        if children.remove(&h).is_none() {
+        if children.remove(h, &vals).is_none() {
             return Err(Error::Msg(format!("missing labels {:?}", labels)));
         }
 
@@ -97,6 +325,17 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
         self.children.write().clear();
     }
 
+    /// `reset_curried` deletes the metrics whose label values match `curry`.
+    fn reset_curried(&self, curry: &[(usize, String)]) {
+        self.children.write().retain(|vals, _| {
+            curry
+                .iter()
+                .any(|(i, curried)| vals.get(*i) != Some(curried))
+        });
+    }
+
+    /// `hash_label_values` hashes `vals`, terminating each value with
+    /// [`SEPARATOR_BYTE`] so that e.g. `["ab", "c"]` and `["a", "bc"]` differ.
     pub(crate) fn hash_label_values(&self, vals: &[&str]) -> Result<u64> {
         if vals.len() != self.desc.variable_labels.len() {
             return Err(Error::InconsistentCardinality {
@@ -105,15 +344,10 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
             });
         }
 
-        let mut h = FnvHasher::default();
-        for val in vals {
-            h.write(val.as_bytes());
-        }
-
-        Ok(h.finish())
+        Ok(hash_values(vals))
     }
 
-    fn hash_labels(&self, labels: &HashMap<&str, &str>) -> Result<u64> {
+    fn get_label_values<'a>(&self, labels: &'a HashMap<&str, &str>) -> Result<Vec<&'a str>> {
         if labels.len() != self.desc.variable_labels.len() {
             return Err(Error::InconsistentCardinality {
                 expect: self.desc.variable_labels.len(),
@@ -121,24 +355,7 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
             });
         }
 
-        let mut h = FnvHasher::default();
-        for name in &self.desc.variable_labels {
-            match labels.get(&name.as_ref()) {
-                Some(val) => h.write(val.as_bytes()),
-                None => {
-                    return Err(Error::Msg(format!(
-                        "label name {} missing in label map",
-                        name
-                    )));
-                }
-            }
-        }
-
-        Ok(h.finish())
-    }
-
-    fn get_label_values<'a>(&self, labels: &'a HashMap<&str, &str>) -> Result<Vec<&'a str>> {
-        let mut values = Vec::new();
+        let mut values = Vec::with_capacity(labels.len());
         for name in &self.desc.variable_labels {
             match labels.get(&name.as_ref()) {
                 Some(val) => values.push(*val),
@@ -156,14 +373,122 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
     fn get_or_create_metric(&self, hash: u64, label_values: &[&str]) -> Result<T::M> {
         let mut children = self.children.write();
         // Check exist first.
-        if let Some(metric) = children.get(&hash).cloned() {
-            return Ok(metric);
+        if let Some(child) = children.get(hash, label_values) {
+            self.touch(child);
+            return Ok(child.metric.clone());
         }
 
+        if let Some(limit) = &self.cardinality_limit {
+            let mut len = children.len();
+            let mut overflow = None;
+            if let CardinalityPolicy::Overflow(sentinel) = &limit.policy {
+                // The overflow child does not count against the limit.
+                let vals = vec![sentinel.as_str(); label_values.len()];
+                let h = self.hash_label_values(&vals)?;
+                if children.get(h, &vals).is_some() {
+                    len -= 1;
+                }
+                overflow = Some((h, vals));
+            }
+
+            if len >= limit.max_children {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                match &limit.policy {
+                    CardinalityPolicy::Error => {
+                        return Err(Error::Msg(format!(
+                            "cardinality limit of {} reached, rejecting label values {:?}",
+                            limit.max_children, label_values
+                        )));
+                    }
+                    CardinalityPolicy::Overflow(_) => {
+                        let (h, vals) = overflow.unwrap();
+                        if let Some(child) = children.get(h, &vals) {
+                            return Ok(child.metric.clone());
+                        }
+                        return self.insert_child(&mut children, h, &vals);
+                    }
+                    CardinalityPolicy::EvictLru => self.evict_lru(&mut children),
+                }
+            }
+        }
+
+        self.insert_child(&mut children, hash, label_values)
+    }
+
+    fn insert_child(
+        &self,
+        children: &mut LabelValuesMap<Child<T::M>>,
+        hash: u64,
+        label_values: &[&str],
+    ) -> Result<T::M> {
         let metric = self.new_metric.build(&self.opts, label_values)?;
-        children.insert(hash, metric.clone());
+        let child = children.get_or_insert_with(hash, label_values, || Child {
+            metric: metric.clone(),
+            last_access: AtomicU64::new(0),
+            last_touched: LastTouched::new(self.ttl_millis.map(Duration::from_millis)),
+        });
+        self.touch(child);
+
+        if let Some(CardinalityLimit {
+            policy: CardinalityPolicy::EvictLru,
+            ..
+        }) = self.cardinality_limit
+        {
+            let tick = child.last_access.load(Ordering::Relaxed);
+            let mut lru = self.lru.lock();
+            lru.insert(
+                tick,
+                (hash, label_values.iter().map(|v| (*v).to_owned()).collect()),
+            );
+            // Children removed otherwise leave stale entries behind, rebuild
+            // the index once they make up the majority.
+            if lru.len() > 2 * children.len() {
+                *lru = children
+                    .iter()
+                    .map(|(h, vals, child)| {
+                        let tick = child.last_access.load(Ordering::Relaxed);
+                        (tick, (h, vals.to_vec()))
+                    })
+                    .collect();
+            }
+        }
         Ok(metric)
     }
+
+    /// `evict_lru` removes the least recently used child. Index entries of
+    /// children looked up since they were indexed are moved to their latest
+    /// tick on the way, entries of removed children are dropped.
+    fn evict_lru(&self, children: &mut LabelValuesMap<Child<T::M>>) {
+        let mut lru = self.lru.lock();
+        while let Some(tick) = lru.keys().next().copied() {
+            let (h, vals) = lru.remove(&tick).unwrap();
+            let vals_ref: Vec<&str> = vals.iter().map(String::as_str).collect();
+            let last_access = match children.get(h, &vals_ref) {
+                Some(child) => child.last_access.load(Ordering::Relaxed),
+                None => continue,
+            };
+            if last_access > tick {
+                lru.insert(last_access, (h, vals));
+                continue;
+            }
+            children.remove(h, &vals_ref);
+            return;
+        }
+    }
+
+    /// `touch` records a lookup of `child`, if the limit policy or the ttl
+    /// needs it.
+    fn touch(&self, child: &Child<T::M>) {
+        if let Some(CardinalityLimit {
+            policy: CardinalityPolicy::EvictLru,
+            ..
+        }) = self.cardinality_limit
+        {
+            let tick = self.access_tick.fetch_add(1, Ordering::Relaxed);
+            child.last_access.store(tick, Ordering::Relaxed);
+        }
+        child.last_touched.touch();
+    }
 }
 
 /// A [`Collector`] to bundle metrics of the same name that
@@ -174,6 +499,9 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
 #[derive(Clone)]
 pub struct MetricVec<T: MetricVecBuilder> {
     pub(crate) v: Arc<MetricVecCore<T>>,
+    /// The label values bound by `curry_with`, as indices into the variable
+    /// labels of the Desc, sorted by index.
+    curry: Vec<(usize, String)>,
 }
 
 impl<T: MetricVecBuilder> std::fmt::Debug for MetricVec<T> {
@@ -187,15 +515,75 @@ impl<T: MetricVecBuilder> MetricVec<T> {
     /// a MetricVecBuilder `new_metric`.
     pub fn create(metric_type: MetricType, new_metric: T, opts: T::P) -> Result<MetricVec<T>> {
         let desc = opts.describe()?;
+        Self::create_with_limits(metric_type, new_metric, opts, desc, ChildLimits::default())
+    }
+
+    /// `create_with_limits` works as `create`, but takes the `desc` described
+    /// by `opts` and bounds the number and the lifetime of children according
+    /// to `limits`.
+    pub(crate) fn create_with_limits(
+        metric_type: MetricType,
+        new_metric: T,
+        opts: T::P,
+        desc: Desc,
+        limits: ChildLimits,
+    ) -> Result<MetricVec<T>> {
+        let ChildLimits {
+            cardinality_limit,
+            ttl,
+        } = limits;
+        if let Some(limit) = &cardinality_limit {
+            if limit.max_children == 0 {
+                return Err(Error::Msg(
+                    "cardinality limit must allow at least one child".to_owned(),
+                ));
+            }
+        }
+
+        let ttl_millis = ttl.map(timer::duration_to_millis);
+        if ttl_millis == Some(0) {
+            return Err(Error::Msg(
+                "ttl must be at least one millisecond".to_owned(),
+            ));
+        }
+        if ttl_millis.is_some() {
+            timer::ensure_updater();
+        }
+
+        let rejected_desc = match cardinality_limit {
+            Some(_) => Some(Desc::new(
+                format!("{}_rejected_label_sets_total", desc.fq_name),
+                format!(
+                    "Total number of label sets of {} rejected, routed to the overflow \
+                     child or evicted due to the cardinality limit.",
+                    desc.fq_name
+                ),
+                vec![],
+                desc.const_label_pairs
+                    .iter()
+                    .map(|lp| (lp.get_name().to_owned(), lp.get_value().to_owned()))
+                    .collect(),
+            )?),
+            None => None,
+        };
         let v = MetricVecCore {
-            children: RwLock::new(HashMap::new()),
+            children: RwLock::new(LabelValuesMap::default()),
             desc,
             metric_type,
             new_metric,
             opts,
+            cardinality_limit,
+            ttl_millis,
+            access_tick: AtomicU64::new(0),
+            lru: Mutex::new(BTreeMap::new()),
+            rejected: AtomicU64::new(0),
+            rejected_desc,
         };
 
-        Ok(MetricVec { v: Arc::new(v) })
+        Ok(MetricVec {
+            v: Arc::new(v),
+            curry: Vec::new(),
+        })
     }
 
     /// `get_metric_with_label_values` returns the [`Metric`] for the given slice
@@ -222,7 +610,11 @@ impl<T: MetricVecBuilder> MetricVec<T> {
     /// latter has a much more readable (albeit more verbose) syntax, but it comes
     /// with a performance overhead (for creating and processing the Labels map).
     pub fn get_metric_with_label_values(&self, vals: &[&str]) -> Result<T::M> {
-        self.v.get_metric_with_label_values(vals)
+        if self.curry.is_empty() {
+            return self.v.get_metric_with_label_values(vals);
+        }
+        let vals = self.uncurry_label_values(vals)?;
+        self.v.get_metric_with_label_values(&vals)
     }
 
     /// `get_metric_with` returns the [`Metric`] for the given Labels map (the
@@ -238,7 +630,11 @@ impl<T: MetricVecBuilder> MetricVec<T> {
     /// `get_metric_with_label_values`. See there for pros and cons of the two
     /// methods.
     pub fn get_metric_with(&self, labels: &HashMap<&str, &str>) -> Result<T::M> {
-        self.v.get_metric_with(labels)
+        if self.curry.is_empty() {
+            return self.v.get_metric_with(labels);
+        }
+        let labels = self.uncurry_labels(labels)?;
+        self.v.get_metric_with(&labels)
     }
 
     /// `with_label_values` works as `get_metric_with_label_values`, but panics if an error
@@ -278,7 +674,11 @@ impl<T: MetricVecBuilder> MetricVec<T> {
     /// latter has a much more readable (albeit more verbose) syntax, but it comes
     /// with a performance overhead (for creating and processing the Labels map).
     pub fn remove_label_values(&self, vals: &[&str]) -> Result<()> {
-        self.v.delete_label_values(vals)
+        if self.curry.is_empty() {
+            return self.v.delete_label_values(vals);
+        }
+        let vals = self.uncurry_label_values(vals)?;
+        self.v.delete_label_values(&vals)
     }
 
     /// `remove` removes the metric where the variable labels are the same as those
@@ -290,32 +690,205 @@ impl<T: MetricVecBuilder> MetricVec<T> {
     /// This method is used for the same purpose as `delete_label_values`. See
     /// there for pros and cons of the two methods.
     pub fn remove(&self, labels: &HashMap<&str, &str>) -> Result<()> {
-        self.v.delete(labels)
+        if self.curry.is_empty() {
+            return self.v.delete(labels);
+        }
+        let labels = self.uncurry_labels(labels)?;
+        self.v.delete(&labels)
     }
 
-    /// `reset` deletes all metrics in this vector.
+    /// `reset` deletes all metrics in this vector. On a curried vector, only
+    /// the metrics matching the curried label values are deleted.
     pub fn reset(&self) {
-        self.v.reset()
+        if self.curry.is_empty() {
+            self.v.reset()
+        } else {
+            self.v.reset_curried(&self.curry)
+        }
+    }
+
+    /// `curry_with` returns a vector with the given labels bound to the given
+    /// values. The returned vector only takes the remaining labels in
+    /// `with_label_values`, `with` and friends, but shares its metrics with
+    /// this vector, so that collecting either of them yields the same single
+    /// family. Hence only one of them should be registered.
+    ///
+    /// An error is returned if a label name is not a variable label of this
+    /// vector or has already been curried.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use prometheus::{CounterVec, Opts};
+    /// let vec = CounterVec::new(
+    ///     Opts::new("requests_total", "Number of requests."),
+    ///     &["service", "code"]
+    /// ).unwrap();
+    ///
+    /// let mut labels = HashMap::new();
+    /// labels.insert("service", "auth");
+    /// let auth = vec.curry_with(&labels).unwrap();
+    /// auth.with_label_values(&["200"]).inc();
+    /// assert_eq!(vec.with_label_values(&["auth", "200"]).get() as u64, 1);
+    /// ```
+    pub fn curry_with(&self, labels: &HashMap<&str, &str>) -> Result<MetricVec<T>> {
+        let mut curry = self.curry.clone();
+        for (name, val) in labels {
+            let index = self.label_index(name)?;
+            curry.push((index, (*val).to_owned()));
+        }
+        curry.sort_by_key(|(i, _)| *i);
+
+        Ok(MetricVec {
+            v: self.v.clone(),
+            curry,
+        })
+    }
+
+    /// `curry_with_label_values` works as `curry_with`, but binds the leading
+    /// labels not curried yet to `vals`, in the order of the VariableLabels in
+    /// Desc.
+    pub fn curry_with_label_values(&self, vals: &[&str]) -> Result<MetricVec<T>> {
+        let remaining = self.v.desc.variable_labels.len() - self.curry.len();
+        if vals.len() > remaining {
+            return Err(Error::InconsistentCardinality {
+                expect: remaining,
+                got: vals.len(),
+            });
+        }
+
+        let mut curry = self.curry.clone();
+        let free = (0..self.v.desc.variable_labels.len())
+            .filter(|i| self.curry.iter().all(|(curried, _)| curried != i));
+        for (index, val) in free.zip(vals) {
+            curry.push((index, (*val).to_owned()));
+        }
+        curry.sort_by_key(|(i, _)| *i);
+
+        Ok(MetricVec {
+            v: self.v.clone(),
+            curry,
+        })
+    }
+
+    /// `hash_label_values` hashes the label values not curried yet, as taken
+    /// by `with_label_values`.
+    pub(crate) fn hash_label_values(&self, vals: &[&str]) -> Result<u64> {
+        if self.curry.is_empty() {
+            return self.v.hash_label_values(vals);
+        }
+        self.check_label_values(vals)?;
+        Ok(hash_values(vals))
+    }
+
+    fn label_index(&self, name: &str) -> Result<usize> {
+        let index = self
+            .v
+            .desc
+            .variable_labels
+            .iter()
+            .position(|l| l == name)
+            .ok_or_else(|| Error::Msg(format!("label name {} is not a variable label", name)))?;
+        if self.curry.iter().any(|(i, _)| *i == index) {
+            return Err(Error::Msg(format!(
+                "label name {} is already curried",
+                name
+            )));
+        }
+        Ok(index)
+    }
+
+    fn check_label_values(&self, vals: &[&str]) -> Result<()> {
+        let expect = self.v.desc.variable_labels.len() - self.curry.len();
+        if vals.len() != expect {
+            return Err(Error::InconsistentCardinality {
+                expect,
+                got: vals.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// `uncurry_label_values` merges the curried label values into `vals`.
+    fn uncurry_label_values<'a>(&'a self, vals: &[&'a str]) -> Result<Vec<&'a str>> {
+        self.check_label_values(vals)?;
+
+        let mut curry = self.curry.iter().peekable();
+        let mut vals = vals.iter();
+        let mut res = Vec::with_capacity(self.v.desc.variable_labels.len());
+        for i in 0..self.v.desc.variable_labels.len() {
+            match curry.next_if(|(curried, _)| *curried == i) {
+                Some((_, val)) => res.push(val.as_str()),
+                None => res.push(*vals.next().unwrap()),
+            }
+        }
+        Ok(res)
+    }
+
+    /// `uncurry_labels` merges the curried labels into `labels`.
+    fn uncurry_labels<'a>(
+        &'a self,
+        labels: &HashMap<&'a str, &'a str>,
+    ) -> Result<HashMap<&'a str, &'a str>> {
+        let expect = self.v.desc.variable_labels.len() - self.curry.len();
+        if labels.len() != expect {
+            return Err(Error::InconsistentCardinality {
+                expect,
+                got: labels.len(),
+            });
+        }
+
+        let mut res = labels.clone();
+        for (i, val) in &self.curry {
+            let name = self.v.desc.variable_labels[*i].as_str();
+            if res.insert(name, val.as_str()).is_some() {
+                return Err(Error::Msg(format!(
+                    "label name {} is already curried",
+                    name
+                )));
+            }
+        }
+        Ok(res)
+    }
+
+    /// `rejected_label_sets` returns how many times a new set of label values
+    /// hit the cardinality limit of this vector, i.e. was rejected, routed to
+    /// the overflow child or caused an eviction. It is always zero for
+    /// vectors without a limit.
+    ///
+    /// Vectors with a limit also expose it as a counter named like the
+    /// vector with a `_rejected_label_sets_total` suffix.
+    pub fn rejected_label_sets(&self) -> u64 {
+        self.v.rejected.load(Ordering::Relaxed)
     }
 }
 
 impl<T: MetricVecBuilder> Collector for MetricVec<T> {
     fn desc(&self) -> Vec<&Desc> {
-        vec![&self.v.desc]
+        let mut descs = vec![&self.v.desc];
+        descs.extend(self.v.rejected_desc.as_ref());
+        descs
     }
 
     fn collect(&self) -> Vec<MetricFamily> {
-        vec![self.v.collect()]
+        let mut mfs = vec![self.v.collect()];
+        mfs.extend(self.v.collect_rejected());
+        mfs
     }
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
+    use std::time::Duration;
 
+    use super::LabelValuesMap;
     use crate::counter::CounterVec;
     use crate::gauge::GaugeVec;
-    use crate::metrics::{Metric, Opts};
+    use crate::histogram::{HistogramOpts, HistogramVec};
+    use crate::metrics::{CardinalityPolicy, Collector, Metric, Opts};
+    use crate::proto::MetricType;
 
     #[test]
     fn test_counter_vec_with_labels() {
@@ -431,4 +1004,245 @@ mod tests {
             assert_eq!(lp.get_value(), labels[lp.get_name()]);
         }
     }
+
+    #[test]
+    fn test_vec_label_values_separator() {
+        let vec = CounterVec::new(
+            Opts::new("test_counter_vec", "test counter vec help"),
+            &["l1", "l2"],
+        )
+        .unwrap();
+
+        vec.with_label_values(&["ab", "c"]).inc();
+        vec.with_label_values(&["a", "bc"]).inc_by(2.0);
+        assert!((vec.with_label_values(&["ab", "c"]).get() - 1.0).abs() < f64::EPSILON);
+        assert!((vec.with_label_values(&["a", "bc"]).get() - 2.0).abs() < f64::EPSILON);
+        assert_eq!(vec.collect()[0].get_metric().len(), 2);
+
+        assert!(vec.remove_label_values(&["abc", ""]).is_err());
+        assert!(vec.remove_label_values(&["ab", "c"]).is_ok());
+        assert!((vec.with_label_values(&["a", "bc"]).get() - 2.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_label_values_map_collision() {
+        let mut map = LabelValuesMap::default();
+        // Force a collision by using the same hash for different label values.
+        *map.get_or_insert_with(42, &["a"], || 1) += 10;
+        map.get_or_insert_with(42, &["b"], || 2);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(42, &["a"]), Some(&11));
+        assert_eq!(map.get(42, &["b"]), Some(&2));
+        assert_eq!(map.get(42, &["c"]), None);
+        assert_eq!(map.get(7, &["a"]), None);
+
+        assert_eq!(map.remove(42, &["a"]), Some(11));
+        assert_eq!(map.remove(42, &["a"]), None);
+        assert_eq!(map.get(42, &["b"]), Some(&2));
+        assert_eq!(map.values().collect::<Vec<_>>(), vec![&2]);
+
+        map.clear();
+        assert_eq!(map.len(), 0);
+    }
+
+    #[test]
+    fn test_vec_cardinality_limit_error() {
+        let vec = CounterVec::new(
+            Opts::new("test_counter_vec", "test counter vec help")
+                .cardinality_limit(2, CardinalityPolicy::Error),
+            &["l1"],
+        )
+        .unwrap();
+
+        vec.with_label_values(&["v1"]).inc();
+        vec.with_label_values(&["v2"]).inc();
+        assert!(vec.get_metric_with_label_values(&["v3"]).is_err());
+        assert_eq!(vec.rejected_label_sets(), 1);
+
+        // Existing children are still served.
+        vec.with_label_values(&["v1"]).inc();
+        assert_eq!(vec.rejected_label_sets(), 1);
+
+        assert!(vec.remove_label_values(&["v2"]).is_ok());
+        assert!(vec.get_metric_with_label_values(&["v3"]).is_ok());
+        assert_eq!(vec.collect()[0].get_metric().len(), 2);
+    }
+
+    #[test]
+    fn test_vec_cardinality_limit_overflow() {
+        let vec = CounterVec::new(
+            Opts::new("test_counter_vec", "test counter vec help")
+                .cardinality_limit(1, CardinalityPolicy::Overflow("__overflow__".to_owned())),
+            &["l1", "l2"],
+        )
+        .unwrap();
+
+        vec.with_label_values(&["a", "b"]).inc();
+        vec.with_label_values(&["c", "d"]).inc();
+        vec.with_label_values(&["e", "f"]).inc_by(2.0);
+        assert_eq!(vec.rejected_label_sets(), 2);
+
+        let overflow = vec.with_label_values(&["__overflow__", "__overflow__"]);
+        assert!((overflow.get() - 3.0).abs() < f64::EPSILON);
+        assert_eq!(vec.collect()[0].get_metric().len(), 2);
+
+        // The overflow child does not take up a slot.
+        assert!(vec.remove_label_values(&["a", "b"]).is_ok());
+        vec.with_label_values(&["g", "h"]).inc();
+        assert!((vec.with_label_values(&["g", "h"]).get() - 1.0).abs() < f64::EPSILON);
+        assert_eq!(vec.rejected_label_sets(), 2);
+    }
+
+    #[test]
+    fn test_vec_cardinality_limit_evict_lru() {
+        let vec = HistogramVec::new(
+            HistogramOpts::new("test_histogram_vec", "test histogram vec help")
+                .cardinality_limit(2, CardinalityPolicy::EvictLru),
+            &["l1"],
+        )
+        .unwrap();
+
+        vec.with_label_values(&["v1"]).observe(1.0);
+        vec.with_label_values(&["v2"]).observe(1.0);
+        // Touch v1, so that v2 becomes the least recently used child.
+        vec.with_label_values(&["v1"]).observe(1.0);
+        vec.with_label_values(&["v3"]).observe(1.0);
+        assert_eq!(vec.rejected_label_sets(), 1);
+
+        let mfs = vec.collect();
+        let values: Vec<_> = mfs[0]
+            .get_metric()
+            .iter()
+            .map(|m| m.get_label()[0].get_value().to_owned())
+            .collect();
+        assert_eq!(values.len(), 2);
+        assert!(values.contains(&"v1".to_owned()));
+        assert!(values.contains(&"v3".to_owned()));
+        assert_eq!(vec.with_label_values(&["v1"]).get_sample_count(), 2);
+
+        // A removed child leaves a stale index entry, v1 is evicted next.
+        assert!(vec.remove_label_values(&["v3"]).is_ok());
+        vec.with_label_values(&["v4"]).observe(1.0);
+        vec.with_label_values(&["v5"]).observe(1.0);
+        assert_eq!(vec.rejected_label_sets(), 2);
+        assert!(vec.remove_label_values(&["v1"]).is_err());
+        assert!(vec.remove_label_values(&["v4"]).is_ok());
+        assert!(vec.remove_label_values(&["v5"]).is_ok());
+    }
+
+    #[test]
+    fn test_vec_rejected_label_sets_counter() {
+        let vec = CounterVec::new(
+            Opts::new("test_counter_vec", "test counter vec help")
+                .const_label("a", "1")
+                .cardinality_limit(1, CardinalityPolicy::Error),
+            &["l1"],
+        )
+        .unwrap();
+        assert_eq!(vec.desc().len(), 2);
+        assert_eq!(
+            vec.desc()[1].fq_name,
+            "test_counter_vec_rejected_label_sets_total"
+        );
+
+        vec.with_label_values(&["v1"]).inc();
+        assert!(vec.get_metric_with_label_values(&["v2"]).is_err());
+        assert!(vec.get_metric_with_label_values(&["v3"]).is_err());
+
+        let mfs = vec.collect();
+        assert_eq!(mfs.len(), 2);
+        let mf = &mfs[1];
+        assert_eq!(mf.get_name(), "test_counter_vec_rejected_label_sets_total");
+        assert_eq!(mf.get_field_type(), MetricType::COUNTER);
+        let m = &mf.get_metric()[0];
+        assert_eq!(m.get_label().len(), 1);
+        assert_eq!(m.get_label()[0].get_name(), "a");
+        assert!((m.get_counter().get_value() - 2.0).abs() < f64::EPSILON);
+
+        // Vectors without a limit expose no counter.
+        let vec = CounterVec::new(Opts::new("test_counter_vec", "help"), &["l1"]).unwrap();
+        assert_eq!(vec.desc().len(), 1);
+        assert_eq!(vec.collect().len(), 1);
+    }
+
+    #[test]
+    fn test_vec_cardinality_limit_zero() {
+        let opts = Opts::new("test_gauge_vec", "test gauge vec help")
+            .cardinality_limit(0, CardinalityPolicy::Error);
+        assert!(GaugeVec::new(opts, &["l1"]).is_err());
+    }
+
+    #[test]
+    fn test_vec_ttl_zero() {
+        let opts =
+            Opts::new("test_gauge_vec", "test gauge vec help").ttl(Duration::from_micros(10));
+        assert!(GaugeVec::new(opts, &["l1"]).is_err());
+    }
+
+    #[test]
+    fn test_vec_curry_with() {
+        let vec = CounterVec::new(
+            Opts::new("test_counter_vec", "test counter vec help"),
+            &["a", "b", "c"],
+        )
+        .unwrap();
+
+        let mut labels = HashMap::new();
+        labels.insert("b", "x");
+        let curried = vec.curry_with(&labels).unwrap();
+        curried.with_label_values(&["1", "2"]).inc();
+        assert!((vec.with_label_values(&["1", "x", "2"]).get() - 1.0).abs() < f64::EPSILON);
+        assert!(curried
+            .get_metric_with_label_values(&["1", "x", "2"])
+            .is_err());
+
+        let mut labels = HashMap::new();
+        labels.insert("a", "1");
+        labels.insert("c", "3");
+        curried.with(&labels).inc_by(2.0);
+        assert!((vec.with_label_values(&["1", "x", "3"]).get() - 2.0).abs() < f64::EPSILON);
+        labels.insert("b", "y");
+        assert!(curried.get_metric_with(&labels).is_err());
+
+        // Both share the same children and collect into one family.
+        assert_eq!(vec.collect()[0].get_metric().len(), 2);
+        assert_eq!(curried.collect()[0].get_metric().len(), 2);
+
+        let curried2 = curried.curry_with_label_values(&["1"]).unwrap();
+        assert!((curried2.with_label_values(&["3"]).get() - 2.0).abs() < f64::EPSILON);
+        assert!(curried2.remove_label_values(&["2"]).is_ok());
+        assert_eq!(vec.collect()[0].get_metric().len(), 1);
+
+        let mut labels = HashMap::new();
+        labels.insert("b", "z");
+        assert!(curried.curry_with(&labels).is_err());
+        labels.clear();
+        labels.insert("d", "z");
+        assert!(vec.curry_with(&labels).is_err());
+        assert!(curried.curry_with_label_values(&["1", "2", "3"]).is_err());
+    }
+
+    #[test]
+    fn test_vec_curry_reset() {
+        let vec = CounterVec::new(
+            Opts::new("test_counter_vec", "test counter vec help"),
+            &["a", "b"],
+        )
+        .unwrap();
+        let curried = vec.curry_with_label_values(&["1"]).unwrap();
+
+        vec.with_label_values(&["1", "1"]).inc();
+        vec.with_label_values(&["1", "2"]).inc();
+        vec.with_label_values(&["2", "1"]).inc();
+        curried.reset();
+
+        let mfs = vec.collect();
+        assert_eq!(mfs[0].get_metric().len(), 1);
+        assert_eq!(mfs[0].get_metric()[0].get_label()[0].get_value(), "2");
+
+        let mut local = curried.local();
+        local.with_label_values(&["3"]).inc();
+        local.flush();
+        assert!((vec.with_label_values(&["1", "3"]).get() - 1.0).abs() < f64::EPSILON);
+    }
 }
@@ -1,5 +1,7 @@
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
+#![allow(clippy::too_many_arguments)]
+
 use criterion::{criterion_group, criterion_main, Criterion};
 use prometheus::{IntCounter, IntCounterVec, Opts};
 
@@ -56,7 +56,6 @@ pub fn may_flush_metrics() {
 }
 
 /// This example demonstrates the usage of using static metrics with local metrics.
-
 fn main() {
     TLS_HTTP_COUNTER.with(|m| m.foo.post.http1.inc());
     TLS_HTTP_COUNTER.with(|m| m.foo.post.http1.inc());
@@ -6,6 +6,8 @@ Use metric enums to reuse possible values of a label.
 
 */
 
+#![allow(clippy::disallowed_names)]
+
 use prometheus::*;
 
 use lazy_static::lazy_static;
@@ -2,22 +2,19 @@
 
 use std::collections::HashMap;
 
-use proc_macro2::Span;
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
 use syn::token::*;
 use syn::*;
 
 /// Matches `label_enum` keyword.
-struct LabelEnum {
-    pub span: Span,
-}
+struct LabelEnum;
 
 impl Parse for LabelEnum {
     fn parse(input: ParseStream) -> Result<Self> {
         let ident: Ident = input.parse()?;
         if &ident == "label_enum" {
-            return Ok(LabelEnum { span: ident.span() });
+            return Ok(LabelEnum);
         }
         Err(input.error("Expected `label_enum`"))
     }
@@ -0,0 +1,141 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+// The expiry of metric vector children and cached collections is driven by
+// the process wide coarse clock, so it is tested in its own binary.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{CachedCollector, Counter, CounterVec, HistogramOpts, HistogramVec, Opts};
+
+// Children touched at the start expire after both sleeps for sure, as those
+// add up to more than the ttl. Children touched in between outlive the second
+// sleep by far, even though the coarse clock they are stamped with lags by up
+// to 200ms.
+const TTL: Duration = Duration::from_secs(2);
+const FIRST_SLEEP: Duration = Duration::from_millis(1800);
+const SECOND_SLEEP: Duration = Duration::from_millis(400);
+
+#[test]
+fn test_vec_ttl() {
+    let vec = CounterVec::new(
+        Opts::new("test_counter_vec", "test counter vec help").ttl(TTL),
+        &["l1"],
+    )
+    .unwrap();
+
+    vec.with_label_values(&["v1"]).inc();
+    vec.with_label_values(&["v2"]).inc();
+    assert_eq!(vec.collect()[0].get_metric().len(), 2);
+
+    thread::sleep(FIRST_SLEEP);
+    vec.with_label_values(&["v1"]).inc();
+    thread::sleep(SECOND_SLEEP);
+
+    let mfs = vec.collect();
+    let metrics = mfs[0].get_metric();
+    assert_eq!(metrics.len(), 1);
+    assert_eq!(metrics[0].get_label()[0].get_value(), "v1");
+    assert!((metrics[0].get_counter().get_value() - 2.0).abs() < f64::EPSILON);
+
+    // An expired child starts over.
+    assert!(vec.with_label_values(&["v2"]).get().abs() < f64::EPSILON);
+}
+
+#[test]
+fn test_vec_ttl_updated_through_handle() {
+    let vec = CounterVec::new(
+        Opts::new("test_counter_vec", "test counter vec help").ttl(TTL),
+        &["l1"],
+    )
+    .unwrap();
+    let histogram_vec = HistogramVec::new(
+        HistogramOpts::new("test_histogram_vec", "test histogram vec help").ttl(TTL),
+        &["l1"],
+    )
+    .unwrap();
+
+    let counter = vec.with_label_values(&["v1"]);
+    let local = histogram_vec.with_label_values(&["v1"]).local();
+    counter.inc();
+    local.observe(1.0);
+    local.flush();
+
+    // Only update through the kept handle and the local histogram.
+    thread::sleep(FIRST_SLEEP);
+    counter.inc();
+    local.observe(1.0);
+    local.flush();
+    thread::sleep(SECOND_SLEEP);
+
+    let mfs = vec.collect();
+    let metrics = mfs[0].get_metric();
+    assert_eq!(metrics.len(), 1);
+    assert!((metrics[0].get_counter().get_value() - 2.0).abs() < f64::EPSILON);
+
+    let mfs = histogram_vec.collect();
+    let metrics = mfs[0].get_metric();
+    assert_eq!(metrics.len(), 1);
+    assert_eq!(metrics[0].get_histogram().get_sample_count(), 2);
+}
+
+struct CountingCollector {
+    counter: Counter,
+    collected: Arc<AtomicUsize>,
+}
+
+impl Collector for CountingCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.counter.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.collected.fetch_add(1, Ordering::SeqCst);
+        // Make concurrent collections overlap.
+        thread::sleep(Duration::from_millis(50));
+        self.counter.collect()
+    }
+}
+
+#[test]
+fn test_cached_collector() {
+    let counter = Counter::new("test_cached", "test cached help").unwrap();
+    let collected = Arc::new(AtomicUsize::new(0));
+    let cached = Arc::new(CachedCollector::new(
+        CountingCollector {
+            counter: counter.clone(),
+            collected: collected.clone(),
+        },
+        Duration::from_secs(1),
+    ));
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let cached = cached.clone();
+            thread::spawn(move || cached.collect())
+        })
+        .collect();
+    for h in handles {
+        assert_eq!(h.join().unwrap().len(), 1);
+    }
+    assert_eq!(collected.load(Ordering::SeqCst), 1);
+
+    // Cached metrics are returned until the time to live passed.
+    counter.inc();
+    let mfs = cached.collect();
+    assert!(mfs[0].get_metric()[0].get_counter().get_value().abs() < f64::EPSILON);
+    assert_eq!(collected.load(Ordering::SeqCst), 1);
+
+    thread::sleep(Duration::from_millis(1100));
+    let mfs = cached.collect();
+    assert!((mfs[0].get_metric()[0].get_counter().get_value() - 1.0).abs() < f64::EPSILON);
+    assert_eq!(collected.load(Ordering::SeqCst), 2);
+
+    cached.invalidate();
+    cached.collect();
+    assert_eq!(collected.load(Ordering::SeqCst), 3);
+}